@@ -89,7 +89,13 @@ pub fn test_token(event: &Value, _context: &LambdaContext) -> LambdaResult<ApiGa
                 user: model::app::User {
                     user_id: model::app::UserId(data.user_id),
                     email: "testemail".to_string(),
+                    email_verified: false,
+                    email_verification: None,
+                    verification_sent_at: None,
                     tz: None,
+                    feed_token: None,
+                    reminder_channels: vec![],
+                    push_endpoint: None,
                 },
                 app_id: data.app_id,
             }.to_payload(expires_in);
@@ -226,6 +232,12 @@ impl AuthenticationContext {
         Ok(model::app::User {
             user_id: model::app::UserId(user_id.to_string()),
             email: "testemail".to_string(),
+            email_verified: false,
+            email_verification: None,
+            verification_sent_at: None,
+            feed_token: None,
+            reminder_channels: vec![],
+            push_endpoint: None,
             tz: None,
         })
     }
@@ -265,7 +277,13 @@ mod tests {
         let payload = AuthenticationContext {
             user: model::app::User {
                 user_id: model::app::UserId("u1".to_string()),
+                feed_token: None,
+                reminder_channels: vec![],
+                push_endpoint: None,
                 email: "testemail".to_string(),
+                email_verified: false,
+                email_verification: None,
+                verification_sent_at: None,
                 tz: None,
             },
             app_id: "a1".to_owned(),
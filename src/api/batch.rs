@@ -0,0 +1,833 @@
+//! Several operations on items in a single request.
+
+use std::env;
+use std::collections::HashMap;
+use std::thread;
+use std::time::Duration;
+
+use crowbar;
+use chrono;
+use chrono_tz;
+use http;
+use serde::Serialize;
+use serde_dynamodb;
+
+use rusoto_dynamodb::{BatchWriteItemInput, DynamoDb, PutItemInput, PutRequest, WriteRequest};
+
+use model;
+
+use super::common::*;
+use super::context;
+use super::history;
+use super::member;
+use super::project;
+use super::sort;
+use super::sync;
+use super::todo;
+use super::user;
+use super::webhook;
+
+const MAX_OPERATIONS: usize = 100;
+/// maximum number of requests accepted by DynamoDB in one `BatchWriteItem`
+const WRITE_CHUNK_SIZE: usize = 25;
+const MAX_WRITE_ATTEMPTS: u32 = 5;
+
+#[derive(Debug, Fail)]
+#[fail(display = "Too Many Operations: {} (maximum {})", _0, MAX_OPERATIONS)]
+struct TooManyOperations(usize);
+#[derive(Debug, Fail)]
+#[fail(display = "Too Many Children: '{}' has too many children to be deleted in a batch", _0)]
+struct TooManyChildren(String);
+#[derive(Debug, Fail)]
+#[fail(display = "Not Written: {}", _0)]
+struct NotWritten(String);
+
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Operation {
+    Create { item: todo::ItemInput },
+    Update {
+        id: String,
+        item: todo::ItemInput,
+        if_match: Option<String>,
+    },
+    Delete { id: String, if_match: Option<String> },
+}
+
+#[derive(Deserialize)]
+struct BatchInput {
+    operations: Vec<Operation>,
+}
+
+#[derive(Serialize)]
+pub struct OperationResult {
+    pub status: u16,
+    pub item: Option<model::basic_item::BasicItem>,
+    pub error: Option<String>,
+}
+impl OperationResult {
+    fn success(item: model::basic_item::BasicItem) -> OperationResult {
+        OperationResult {
+            status: http::StatusCode::OK.as_u16(),
+            item: Some(item),
+            error: None,
+        }
+    }
+
+    fn failure(status: http::StatusCode, error: SerializableError) -> OperationResult {
+        OperationResult {
+            status: status.as_u16(),
+            item: None,
+            error: Some(format!("{}", error.0)),
+        }
+    }
+
+    fn denied(denied: &member::Denied, object: &'static str, id: String) -> OperationResult {
+        match *denied {
+            member::Denied::NotFound => {
+                OperationResult::failure(
+                    http::StatusCode::NOT_FOUND,
+                    NotFound {
+                        object: object,
+                        id: id,
+                    }.into(),
+                )
+            }
+            member::Denied::Forbidden => {
+                OperationResult::failure(
+                    http::StatusCode::FORBIDDEN,
+                    Forbidden {
+                        object: object,
+                        id: id,
+                    }.into(),
+                )
+            }
+        }
+    }
+
+    /// Result of an operation not written because an earlier one failed.
+    fn not_attempted() -> OperationResult {
+        OperationResult::failure(
+            http::StatusCode::FAILED_DEPENDENCY,
+            NotWritten("an earlier operation of the batch failed".to_string()).into(),
+        )
+    }
+
+    /// Result of an operation whose transaction failed, from the `reasons` of its writes.
+    fn canceled(planned: &Planned, reasons: &[Option<String>]) -> OperationResult {
+        let conflict = reasons.iter().position(|reason| {
+            reason.as_ref().map(|reason| reason.as_str()) == Some(CONDITIONAL_CHECK_FAILED)
+        });
+        if let Some(index) = conflict {
+            let id = planned.changes[index].1.id.to_string();
+            return OperationResult::failure(
+                http::StatusCode::PRECONDITION_FAILED,
+                todo::VersionConflict(id).into(),
+            );
+        }
+        match reasons.iter().filter_map(|reason| reason.clone()).next() {
+            Some(reason) => {
+                OperationResult::failure(
+                    http::StatusCode::SERVICE_UNAVAILABLE,
+                    NotWritten(reason).into(),
+                )
+            }
+            None => {
+                OperationResult::failure(
+                    http::StatusCode::FAILED_DEPENDENCY,
+                    NotWritten("another operation of its transaction failed".to_string()).into(),
+                )
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct BatchResults {
+    pub results: Vec<OperationResult>,
+}
+
+/// Result of an operation and the writes it needs, made together or not at all.
+struct Planned {
+    result: OperationResult,
+    /// items written by the operation, with their state before it
+    changes: Vec<(Option<model::basic_item::BasicItem>, model::basic_item::BasicItem)>,
+}
+
+fn write_request<T: Serialize>(object: &T) -> WriteRequest {
+    WriteRequest {
+        put_request: Some(PutRequest { item: serde_dynamodb::to_hashmap(object).unwrap() }),
+        ..Default::default()
+    }
+}
+
+/// What the operations of a batch share.
+struct Request<'a, D: DynamoDb + 'a> {
+    client: &'a D,
+    table: &'a str,
+    actor: model::Actor,
+    memberships: Vec<model::member::Member>,
+    tz: chrono_tz::Tz,
+    now: chrono::DateTime<chrono::Utc>,
+}
+impl<'a, D: DynamoDb> Request<'a, D> {
+    /// Owner with whom an item added to a project is stored, as `member::project_owner`.
+    fn project_owner(&self, project_id: Option<&str>) -> Result<model::UserId, member::Denied> {
+        let membership = project_id.and_then(|project_id| {
+            self.memberships.iter().find(
+                |member| member.id.to_string() == project_id,
+            )
+        });
+        match membership {
+            Some(member) if !member.role.can_edit() => Err(member::Denied::Forbidden),
+            Some(member) => Ok(member.owner.clone()),
+            None => Ok(self.actor.user_id.clone()),
+        }
+    }
+
+    /// Role of the actor on a project of `owner`, `None` if it is not shared with the actor.
+    fn role(
+        &self,
+        owner: &model::UserId,
+        project_id: &model::ProjectId,
+    ) -> Option<model::member::Role> {
+        if owner == &self.actor.user_id {
+            return Some(model::member::Role::Owner);
+        }
+        self.memberships
+            .iter()
+            .find(|member| &member.owner == owner && &member.id == project_id)
+            .map(|member| member.role)
+    }
+}
+
+/// Contexts, projects and roles read to check the items of a batch, each read once.
+#[derive(Default)]
+struct Known {
+    contexts: HashMap<model::UserId, Vec<model::context::Context>>,
+    projects: HashMap<(model::UserId, model::ProjectId), Option<model::project::Project>>,
+    roles: HashMap<(model::UserId, model::UserId, model::ProjectId), Option<model::member::Role>>,
+}
+impl Known {
+    /// Checks the contexts and the costs of an item, as `todo::check_contexts` and
+    /// `todo::check_costs`.
+    fn check<D: DynamoDb>(
+        &mut self,
+        request: &Request<D>,
+        item: &model::basic_item::BasicItem,
+    ) -> Result<(), SerializableError> {
+        if !item.contexts.is_empty() {
+            let contexts = self.contexts.entry(item.uid.clone()).or_insert_with(|| {
+                context::user_contexts(
+                    request.client,
+                    &env::var("contexts_table").unwrap(),
+                    &item.uid,
+                )
+            });
+            todo::check_known_contexts(item, contexts)?;
+        }
+        if !item.costs.is_empty() {
+            let project = self.projects
+                .entry((item.uid.clone(), item.project_id.clone()))
+                .or_insert_with(|| {
+                    project::find_project(
+                        request.client,
+                        &env::var("projects_table").unwrap(),
+                        &item.uid,
+                        &item.project_id.to_string(),
+                    )
+                });
+            todo::check_project_costs(item, project.as_ref())?;
+        }
+        Ok(())
+    }
+
+    /// Checks the assignee of an item, as `todo::check_assignee`.
+    fn check_assignee<D: DynamoDb>(
+        &mut self,
+        request: &Request<D>,
+        item: &model::basic_item::BasicItem,
+    ) -> Result<(), SerializableError> {
+        let role = match item.assignee {
+            Some(ref assignee) => {
+                *self.roles
+                    .entry((assignee.clone(), item.uid.clone(), item.project_id.clone()))
+                    .or_insert_with(|| {
+                        member::role(request.client, assignee, &item.uid, &item.project_id)
+                    })
+            }
+            None => None,
+        };
+        todo::check_assignee_role(item, role)
+    }
+}
+
+/// Items as they are after the operations already planned, for the caller and the owners of
+/// the projects shared with the caller.
+struct Batch {
+    /// items not in the trash
+    items: Vec<model::basic_item::BasicItem>,
+}
+impl Batch {
+    fn load<D: DynamoDb>(request: &Request<D>) -> Batch {
+        let mut items = todo::user_items(request.client, request.table, &request.actor.user_id);
+        for owner in member::owners(&request.memberships) {
+            items.extend(todo::user_items(request.client, request.table, owner));
+        }
+        Batch { items: items }
+    }
+
+    fn owner_items(&self, owner: &model::UserId) -> Vec<model::basic_item::BasicItem> {
+        self.items
+            .iter()
+            .filter(|item| &item.uid == owner)
+            .cloned()
+            .collect()
+    }
+
+    fn put(&mut self, item: model::basic_item::BasicItem) {
+        match self.items.iter().position(|known| known.id == item.id) {
+            Some(index) => self.items[index] = item,
+            None => self.items.push(item),
+        }
+    }
+
+    fn trash(&mut self, item: &model::basic_item::BasicItem) {
+        self.items.retain(|known| known.id != item.id);
+    }
+
+    fn find(&self, id: &str) -> Option<model::basic_item::BasicItem> {
+        self.items.iter().find(|item| item.id.to_string() == id).cloned()
+    }
+
+    /// Finds an item that the user can edit.
+    fn find_editable<D: DynamoDb>(
+        &self,
+        request: &Request<D>,
+        id: &str,
+    ) -> Result<model::basic_item::BasicItem, member::Denied> {
+        let item = self.find(id).ok_or(member::Denied::NotFound)?;
+        match member::item_role(&request.actor.user_id, &request.memberships, &item) {
+            Some(ref role) if role.can_edit() => Ok(item),
+            Some(_) => Err(member::Denied::Forbidden),
+            None => Err(member::Denied::NotFound),
+        }
+    }
+}
+
+fn check_if_match(
+    if_match: &Option<String>,
+    item: &model::basic_item::BasicItem,
+) -> Result<(), todo::VersionConflict> {
+    match *if_match {
+        Some(ref if_match) if !todo::if_match(if_match, item) => {
+            Err(todo::VersionConflict(item.id.to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Groups writes in requests small enough to be accepted by `BatchWriteItem`.
+fn write_chunks(requests: Vec<WriteRequest>) -> Vec<Vec<WriteRequest>> {
+    requests
+        .chunks(WRITE_CHUNK_SIZE)
+        .map(|chunk| chunk.to_vec())
+        .collect()
+}
+
+/// Sends the writes, retrying with an exponential backoff those that DynamoDB left unprocessed.
+/// Returns the writes still unprocessed after the last attempt.
+fn send_writes<D: DynamoDb>(
+    client: &D,
+    table: &str,
+    requests: Vec<WriteRequest>,
+) -> Vec<WriteRequest> {
+    let mut unprocessed = vec![];
+    for chunk in write_chunks(requests) {
+        let mut request_items = HashMap::new();
+        request_items.insert(table.to_string(), chunk);
+        let mut attempt = 0;
+        while !request_items.is_empty() && attempt < MAX_WRITE_ATTEMPTS {
+            if attempt > 0 {
+                thread::sleep(Duration::from_millis(50 * 2u64.pow(attempt)));
+            }
+            let batch_write = BatchWriteItemInput {
+                request_items: request_items,
+                ..Default::default()
+            };
+            request_items = client
+                .batch_write_item(&batch_write)
+                .unwrap()
+                .unprocessed_items
+                .unwrap_or_else(HashMap::new);
+            attempt += 1;
+        }
+        unprocessed.extend(request_items.remove(table).unwrap_or_else(Vec::new));
+    }
+    unprocessed
+}
+
+/// Ids of the items of writes left unprocessed.
+fn unprocessed_ids(requests: &[WriteRequest]) -> Vec<model::ItemId> {
+    requests
+        .iter()
+        .filter_map(|request| request.put_request.as_ref())
+        .filter_map(|put| put.item.get("id").and_then(|id| id.s.clone()))
+        .map(model::ItemId)
+        .collect()
+}
+
+/// Records what follows the writes of items, as `todo::save_item` does for a single item: their
+/// history, the deletions reported to the members of the projects items moved out of, and the
+/// webhook deliveries. History entries left unprocessed by `BatchWriteItem` are written one by
+/// one.
+pub fn record_changes<D: DynamoDb>(
+    client: &D,
+    changes: &[(Option<&model::basic_item::BasicItem>, &model::basic_item::BasicItem)],
+) {
+    let history_table = env::var("history_table").unwrap();
+    let entries = changes
+        .iter()
+        .filter_map(|&(before, after)| history::entry(before, after))
+        .map(|entry| write_request(&entry))
+        .collect();
+    for request in send_writes(client, &history_table, entries) {
+        if let Some(put) = request.put_request {
+            let put_item = PutItemInput {
+                item: put.item,
+                table_name: history_table.clone(),
+                ..Default::default()
+            };
+            client.put_item(&put_item).unwrap();
+        }
+    }
+    let now = chrono::Utc::now();
+    for &(before, after) in changes {
+        if let Some(before) = before {
+            if before.project_id != after.project_id {
+                sync::record_move(client, before, now);
+            }
+        }
+    }
+    webhook::notify(client, changes);
+}
+
+/// Saves many new items at once and records their history. Unlike `todo::save_item`, writes are
+/// not conditional, so this is only for items with new ids. Returns the ids of the items that
+/// could not be written.
+pub fn write_items<D: DynamoDb>(
+    client: &D,
+    table: &str,
+    items: &[model::basic_item::BasicItem],
+) -> Vec<model::ItemId> {
+    let unwritten = unprocessed_ids(&send_writes(
+        client,
+        table,
+        items.iter().map(write_request).collect(),
+    ));
+    let changes: Vec<_> = items
+        .iter()
+        .filter(|item| !unwritten.contains(&item.id))
+        .map(|item| (None, item))
+        .collect();
+    record_changes(client, &changes);
+    unwritten
+}
+
+fn create<D: DynamoDb>(
+    request: &Request<D>,
+    batch: &mut Batch,
+    known: &mut Known,
+    input: &todo::ItemInput,
+) -> Result<Planned, OperationResult> {
+    // items added to a project shared with the user are stored with its owner
+    let owner = request.project_owner(input.project_id()).map_err(|denied| {
+        OperationResult::denied(
+            &denied,
+            "project",
+            input.project_id().unwrap_or("").to_string(),
+        )
+    })?;
+    let items = batch.owner_items(&owner);
+    let item = input
+        .to_new_item(owner, &request.tz)
+        .and_then(|mut item| {
+            if let Some(ref parent_id) = item.parent_id {
+                let visible = member::items_with_access(
+                    &request.actor.user_id,
+                    &request.memberships,
+                    items.clone(),
+                    false,
+                );
+                todo::check_parent(&items, &visible, &item.id, parent_id)?;
+            }
+            known.check(request, &item)?;
+            known.check_assignee(request, &item)?;
+            item.position = sort::next_position(&items, &item.project_id);
+            item.created(&request.actor, request.now);
+            Ok(item)
+        })
+        .map_err(|error| {
+            OperationResult::failure(http::StatusCode::BAD_REQUEST, error)
+        })?;
+    batch.put(item.clone());
+    Ok(Planned {
+        result: OperationResult::success(item.clone()),
+        changes: vec![(None, item)],
+    })
+}
+
+fn update<D: DynamoDb>(
+    request: &Request<D>,
+    batch: &mut Batch,
+    known: &mut Known,
+    id: String,
+    input: &todo::ItemInput,
+    if_match: &Option<String>,
+) -> Result<Planned, OperationResult> {
+    let existing = batch.find_editable(request, &id).map_err(|denied| {
+        OperationResult::denied(&denied, "todo", id.clone())
+    })?;
+    if check_if_match(if_match, &existing).is_err() {
+        return Err(OperationResult::failure(
+            http::StatusCode::PRECONDITION_FAILED,
+            todo::VersionConflict(id).into(),
+        ));
+    }
+    let items = batch.owner_items(&existing.uid);
+    let updated = input
+        .apply_to(&existing, &request.tz)
+        .and_then(|mut item| {
+            if let Some(ref parent_id) = item.parent_id {
+                if existing.parent_id.as_ref() != Some(parent_id) {
+                    let visible = member::items_with_access(
+                        &request.actor.user_id,
+                        &request.memberships,
+                        items.clone(),
+                        false,
+                    );
+                    todo::check_parent(&items, &visible, &item.id, parent_id)?;
+                }
+            }
+            known.check(request, &item)?;
+            if item.assignee != existing.assignee || item.project_id != existing.project_id {
+                known.check_assignee(request, &item)?;
+            }
+            if item.project_id != existing.project_id {
+                item.position = sort::next_position(&items, &item.project_id);
+            }
+            item.touch(&request.actor, request.now);
+            Ok(item)
+        })
+        .map_err(|error| {
+            OperationResult::failure(http::StatusCode::BAD_REQUEST, error)
+        })?;
+    // an item can only be moved to a project the user can edit
+    if updated.project_id != existing.project_id &&
+        !request
+            .role(&existing.uid, &updated.project_id)
+            .map(|role| role.can_edit())
+            .unwrap_or(false)
+    {
+        return Err(OperationResult::denied(
+            &member::Denied::Forbidden,
+            "project",
+            updated.project_id.to_string(),
+        ));
+    }
+    batch.put(updated.clone());
+    Ok(Planned {
+        result: OperationResult::success(updated.clone()),
+        changes: vec![(Some(existing), updated)],
+    })
+}
+
+fn delete<D: DynamoDb>(
+    request: &Request<D>,
+    batch: &mut Batch,
+    id: String,
+    if_match: &Option<String>,
+) -> Result<Planned, OperationResult> {
+    let existing = batch.find_editable(request, &id).map_err(|denied| {
+        OperationResult::denied(&denied, "todo", id.clone())
+    })?;
+    if check_if_match(if_match, &existing).is_err() {
+        return Err(OperationResult::failure(
+            http::StatusCode::PRECONDITION_FAILED,
+            todo::VersionConflict(id).into(),
+        ));
+    }
+    let mut deleted = existing.clone();
+    deleted.deleted_at = Some(request.now);
+    deleted.touch(&request.actor, request.now);
+
+    // children are attached to the grand parent, as with a single deletion
+    let mut changes = vec![(Some(existing), deleted.clone())];
+    for previous in batch.items.iter().filter(
+        |item| item.parent_id.as_ref() == Some(&deleted.id),
+    )
+    {
+        let mut child = previous.clone();
+        child.parent_id = deleted.parent_id.clone();
+        child.touch(&request.actor, request.now);
+        changes.push((Some(previous.clone()), child));
+    }
+    if changes.len() > MAX_TRANSACTION_ACTIONS {
+        return Err(OperationResult::failure(
+            http::StatusCode::BAD_REQUEST,
+            TooManyChildren(id).into(),
+        ));
+    }
+    batch.trash(&deleted);
+    for &(_, ref child) in changes.iter().skip(1) {
+        batch.put(child.clone());
+    }
+    Ok(Planned {
+        result: OperationResult::success(deleted),
+        changes: changes,
+    })
+}
+
+/// Whether an operation can be written in the same transaction as the pending ones, DynamoDB
+/// limiting the number of writes of a transaction and refusing two writes of the same item.
+fn fits(pending: &[(usize, Planned)], planned: &Planned) -> bool {
+    let written: Vec<&model::ItemId> = pending
+        .iter()
+        .flat_map(|&(_, ref pending)| pending.changes.iter())
+        .map(|&(_, ref item)| &item.id)
+        .collect();
+    written.len() + planned.changes.len() <= MAX_TRANSACTION_ACTIONS &&
+        !planned.changes.iter().any(
+            |&(_, ref item)| written.contains(&&item.id),
+        )
+}
+
+/// Writes the pending operations in one transaction and sets their results from its outcome.
+/// Returns whether they were written.
+fn commit<D: DynamoDb>(
+    request: &Request<D>,
+    pending: Vec<(usize, Planned)>,
+    results: &mut [Option<OperationResult>],
+) -> bool {
+    if pending.is_empty() {
+        return true;
+    }
+    let actions = pending
+        .iter()
+        .flat_map(|&(_, ref planned)| planned.changes.iter())
+        .map(|&(_, ref item)| todo::item_write(request.table, item))
+        .collect();
+    match transact_write(request.client, actions) {
+        Ok(()) => {
+            {
+                let changes: Vec<_> = pending
+                    .iter()
+                    .flat_map(|&(_, ref planned)| planned.changes.iter())
+                    .map(|&(ref before, ref after)| (before.as_ref(), after))
+                    .collect();
+                record_changes(request.client, &changes);
+            }
+            for (index, planned) in pending {
+                results[index] = Some(planned.result);
+            }
+            true
+        }
+        Err(reasons) => {
+            let mut reasons = reasons.into_iter();
+            for (index, planned) in pending {
+                let own: Vec<Option<String>> =
+                    reasons.by_ref().take(planned.changes.len()).collect();
+                results[index] = Some(OperationResult::canceled(&planned, &own));
+            }
+            false
+        }
+    }
+}
+
+/// Applies a list of `create`, `update` and `delete` operations on items, in order, and returns
+/// the result of each one. Operations can refer to items created earlier in the same batch, and
+/// `delete` moves items to the trash as a single deletion does.
+///
+/// Consecutive operations are written together in DynamoDB transactions of up to 25 items, with
+/// the same version checks as single item changes, so that each operation, a deletion with the
+/// children it moves included, is written entirely or not at all. When a transaction fails, its
+/// operations fail, with a 412 status for an item changed concurrently and 424 for the others,
+/// the later operations are not attempted, and the transactions before it stay written.
+///
+/// Operations apply to the caller's own items and to the items of projects shared with the
+/// caller, with the same rights as single item changes.
+pub fn batch(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<BatchResults, SerializableError>> {
+    let input = match parse_body::<BatchInput>(event) {
+        Ok(input) => input,
+        Err(error) => return bad_request(error),
+    };
+    if input.operations.len() > MAX_OPERATIONS {
+        return bad_request(TooManyOperations(input.operations.len()).into());
+    }
+
+    let client = dynamodb_client();
+    let actor = authorized_actor(event);
+    let table = env::var("table").unwrap();
+    let request = Request {
+        client: &client,
+        table: &table,
+        memberships: member::memberships(&client, &actor.user_id),
+        tz: user::current_user(&client, &actor.user_id).timezone(),
+        now: chrono::Utc::now(),
+        actor: actor,
+    };
+    let mut batch = Batch::load(&request);
+    let mut known = Known::default();
+
+    let mut results: Vec<Option<OperationResult>> = vec![];
+    let mut pending: Vec<(usize, Planned)> = vec![];
+    let mut failed = false;
+    for (index, operation) in input.operations.into_iter().enumerate() {
+        if failed {
+            results.push(Some(OperationResult::not_attempted()));
+            continue;
+        }
+        let planned = match operation {
+            Operation::Create { item } => create(&request, &mut batch, &mut known, &item),
+            Operation::Update { id, item, if_match } => {
+                update(&request, &mut batch, &mut known, id, &item, &if_match)
+            }
+            Operation::Delete { id, if_match } => delete(&request, &mut batch, id, &if_match),
+        };
+        match planned {
+            Ok(planned) => {
+                if !fits(&pending, &planned) {
+                    let written = ::std::mem::replace(&mut pending, vec![]);
+                    failed = !commit(&request, written, &mut results);
+                }
+                if failed {
+                    results.push(Some(OperationResult::not_attempted()));
+                } else {
+                    results.push(None);
+                    pending.push((index, planned));
+                }
+            }
+            Err(result) => results.push(Some(result)),
+        }
+    }
+    commit(&request, pending, &mut results);
+    ok(BatchResults {
+        results: results
+            .into_iter()
+            .map(|result| result.unwrap_or_else(OperationResult::not_attempted))
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    fn item(id: &str) -> model::basic_item::BasicItem {
+        model::basic_item::BasicItem {
+            id: model::ItemId(id.to_string()),
+            title: id.to_string(),
+            ..model::basic_item::test_item()
+        }
+    }
+
+    #[test]
+    fn can_parse_operations() {
+        let input: BatchInput = serde_json::from_str(
+            r#"{"operations": [
+                {"op": "create", "item": {"title": "new"}},
+                {"op": "update", "id": "a", "item": {"flagged": true}, "if_match": "\"2\""},
+                {"op": "delete", "id": "b"}
+            ]}"#,
+        ).unwrap();
+
+        assert_eq!(input.operations.len(), 3);
+        match input.operations[1] {
+            Operation::Update { ref id, ref if_match, .. } => {
+                assert_eq!(id, "a");
+                assert_eq!(if_match, &Some("\"2\"".to_string()));
+            }
+            _ => panic!("expected an update"),
+        }
+    }
+
+    #[test]
+    fn should_follow_written_items() {
+        let mut batch = Batch { items: vec![item("a")] };
+        let mut updated = item("a");
+        updated.flagged = true;
+
+        batch.put(updated);
+        batch.put(item("b"));
+
+        assert_eq!(batch.find("a").map(|item| item.flagged), Some(true));
+        batch.trash(&item("a"));
+        assert!(batch.find("a").is_none());
+        assert_eq!(batch.items.len(), 1);
+    }
+
+    #[test]
+    fn should_split_writes_in_chunks() {
+        let requests: Vec<WriteRequest> = (0..60)
+            .map(|index| write_request(&item(&index.to_string())))
+            .collect();
+
+        let chunks = write_chunks(requests);
+
+        let sizes: Vec<usize> = chunks.iter().map(|chunk| chunk.len()).collect();
+        assert_eq!(sizes, vec![25, 25, 10]);
+    }
+
+    #[test]
+    fn should_find_items_of_unprocessed_writes() {
+        let requests = vec![write_request(&item("a")), write_request(&item("b"))];
+
+        assert_eq!(
+            unprocessed_ids(&requests),
+            vec![model::ItemId("a".to_string()), model::ItemId("b".to_string())]
+        );
+    }
+
+    fn planned(ids: &[&str]) -> Planned {
+        Planned {
+            result: OperationResult::success(item(ids[0])),
+            changes: ids.iter().map(|id| (None, item(id))).collect(),
+        }
+    }
+
+    #[test]
+    fn should_write_each_item_once_per_transaction() {
+        let pending = vec![(0, planned(&["a"])), (1, planned(&["b", "c"]))];
+
+        assert!(fits(&pending, &planned(&["d"])));
+        assert!(!fits(&pending, &planned(&["e", "c"])));
+        let ids: Vec<String> = (0..22).map(|index| index.to_string()).collect();
+        let ids: Vec<&str> = ids.iter().map(|id| id.as_str()).collect();
+        assert!(fits(&pending, &planned(&ids)));
+        let mut too_many = ids.clone();
+        too_many.push("x");
+        assert!(!fits(&pending, &planned(&too_many)));
+    }
+
+    #[test]
+    fn should_report_conflicts_of_canceled_transactions() {
+        let deletion = planned(&["a", "child"]);
+
+        let conflict = OperationResult::canceled(
+            &deletion,
+            &[None, Some(CONDITIONAL_CHECK_FAILED.to_string())],
+        );
+        let other = OperationResult::canceled(&deletion, &[None, None]);
+
+        assert_eq!(conflict.status, 412);
+        assert_eq!(
+            conflict.error,
+            Some("Item 'child' was modified by another request".to_string())
+        );
+        assert_eq!(other.status, 424);
+    }
+}
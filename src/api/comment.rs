@@ -0,0 +1,379 @@
+//! Comment threads on items. Everyone who can see an item can comment on it, comments can only
+//! be changed by their author, and removed by their author or the owner of the item.
+
+use std::env;
+use std::collections::HashMap;
+
+use crowbar;
+use chrono;
+use serde_dynamodb;
+use serde_dynamodb::ToQueryInput;
+use uuid;
+
+use rusoto_dynamodb::{AttributeValue, DeleteItemInput, DynamoDb, PutItemInput, QueryInput};
+
+use model;
+
+use super::common::*;
+use super::member;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 100;
+const MAX_BODY_LENGTH: usize = 10_000;
+
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid Comment: {}", _0)]
+struct InvalidComment(&'static str);
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid Cursor: '{}'", _0)]
+struct InvalidCursor(String);
+
+#[derive(Serialize, Debug)]
+pub struct CommentList {
+    pub comments: Vec<model::comment::Comment>,
+    /// to pass as the `after` query string parameter to get the next page, if there is one
+    pub next: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CommentInput {
+    body: Option<String>,
+}
+impl CommentInput {
+    fn body(&self) -> Result<String, SerializableError> {
+        let body = self.body.clone().ok_or_else(|| MissingField("body"))?;
+        if body.trim().is_empty() {
+            Err(InvalidComment("empty body").into())
+        } else if body.len() > MAX_BODY_LENGTH {
+            Err(InvalidComment("body too long").into())
+        } else {
+            Ok(body)
+        }
+    }
+}
+
+/// Number of comments per page, from the `limit` query string parameter.
+fn page_size(event: &crowbar::Value) -> i64 {
+    event["queryStringParameters"]["limit"]
+        .as_str()
+        .and_then(|limit| limit.parse::<i64>().ok())
+        .map(|limit| limit.max(1).min(MAX_PAGE_SIZE))
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+}
+
+fn new_comment(
+    item: &model::basic_item::BasicItem,
+    author: &model::UserId,
+    body: String,
+    now: chrono::DateTime<chrono::Utc>,
+) -> model::comment::Comment {
+    let comment_id = model::CommentId(format!("{}", uuid::Uuid::new_v4().hyphenated()));
+    model::comment::Comment {
+        uid: item.uid.clone(),
+        id: format!(
+            "{}/{}/{}",
+            item.id.to_string(),
+            now.format("%Y%m%dT%H%M%S%.6fZ"),
+            comment_id.to_string()
+        ),
+        comment_id: comment_id,
+        item_id: item.id.clone(),
+        author: author.clone(),
+        body: body,
+        created_at: now,
+        updated_at: None,
+    }
+}
+
+/// A page of the comments of an item, oldest first, starting after the comment `after`.
+fn item_comments<D: DynamoDb>(
+    client: &D,
+    item: &model::basic_item::BasicItem,
+    after: Option<&str>,
+    limit: Option<i64>,
+) -> (Vec<model::comment::Comment>, Option<String>) {
+    let mut names = HashMap::new();
+    names.insert("#uid".to_string(), "uid".to_string());
+    names.insert("#id".to_string(), "id".to_string());
+    let mut values = HashMap::new();
+    values.insert(
+        ":uid".to_string(),
+        AttributeValue {
+            s: Some(item.uid.to_string()),
+            ..Default::default()
+        },
+    );
+    values.insert(
+        ":prefix".to_string(),
+        AttributeValue {
+            s: Some(format!("{}/", item.id.to_string())),
+            ..Default::default()
+        },
+    );
+    let query_input = QueryInput {
+        table_name: env::var("comments_table").unwrap(),
+        key_condition_expression: Some("#uid = :uid AND begins_with(#id, :prefix)".to_string()),
+        expression_attribute_names: Some(names),
+        expression_attribute_values: Some(values),
+        exclusive_start_key: after.map(|after| user_key(&item.uid, after)),
+        limit: limit,
+        ..Default::default()
+    };
+    let output = client.query(&query_input).unwrap();
+    let next = output.last_evaluated_key.and_then(|key| {
+        key.get("id").and_then(|id| id.s.clone())
+    });
+    (
+        output
+            .items
+            .unwrap_or_else(|| vec![])
+            .into_iter()
+            .map(|comment| serde_dynamodb::from_hashmap(comment).unwrap())
+            .collect(),
+        next,
+    )
+}
+
+/// Comments on all the items of the user, by any author.
+pub fn user_comments<D: DynamoDb>(
+    client: &D,
+    user_id: &model::UserId,
+) -> Vec<model::comment::Comment> {
+    let uid_filter = model::comment::CommentQueryInput {
+        uid: Some(user_id.clone()),
+        ..Default::default()
+    };
+    let mut query_input = uid_filter.to_query_input(env::var("comments_table").unwrap());
+    let mut comments = vec![];
+    loop {
+        let output = client.query(&query_input).unwrap();
+        comments.extend(
+            output
+                .items
+                .unwrap_or_else(|| vec![])
+                .into_iter()
+                .map(|comment| serde_dynamodb::from_hashmap(comment).unwrap()),
+        );
+        match output.last_evaluated_key {
+            Some(key) => query_input.exclusive_start_key = Some(key),
+            None => return comments,
+        }
+    }
+}
+
+/// Finds a comment of an item, going through all the pages of comments as DynamoDB returns them
+/// by pages of at most 1 MB.
+fn find_comment<D: DynamoDb>(
+    client: &D,
+    item: &model::basic_item::BasicItem,
+    comment_id: &str,
+) -> Option<model::comment::Comment> {
+    let mut after: Option<String> = None;
+    loop {
+        let (comments, next) =
+            item_comments(client, item, after.as_ref().map(|after| after.as_str()), None);
+        if let Some(comment) = comments.into_iter().find(|comment| {
+            comment.comment_id.to_string() == comment_id
+        })
+        {
+            return Some(comment);
+        }
+        match next {
+            Some(next) => after = Some(next),
+            None => return None,
+        }
+    }
+}
+
+fn save_comment<D: DynamoDb>(client: &D, comment: &model::comment::Comment) {
+    let put_item = PutItemInput {
+        item: serde_dynamodb::to_hashmap(comment).unwrap(),
+        table_name: env::var("comments_table").unwrap(),
+        ..Default::default()
+    };
+    client.put_item(&put_item).unwrap();
+}
+
+/// Lists the comments of an item, oldest first, by pages of `limit` comments.
+pub fn list(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<CommentList, SerializableError>> {
+    let todo_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let client = dynamodb_client();
+    let item = match member::find_item(
+        &client,
+        &env::var("table").unwrap(),
+        &authorized_user_id(event),
+        &todo_id,
+    ) {
+        Some((item, _)) => item,
+        None => return not_found("todo", todo_id),
+    };
+    let after = event["queryStringParameters"]["after"].as_str();
+    if let Some(after) = after {
+        if !after.starts_with(&format!("{}/", todo_id)) {
+            return bad_request(InvalidCursor(after.to_string()).into());
+        }
+    }
+
+    let (comments, next) = item_comments(&client, &item, after, Some(page_size(event)));
+    ok(CommentList {
+        comments: comments,
+        next: next,
+    })
+}
+
+pub fn add(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::comment::Comment,
+        SerializableError,
+    >,
+> {
+    let todo_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let client = dynamodb_client();
+    let user_id = authorized_user_id(event);
+    let item = match member::find_item(&client, &env::var("table").unwrap(), &user_id, &todo_id) {
+        Some((item, _)) => item,
+        None => return not_found("todo", todo_id),
+    };
+
+    match parse_body::<CommentInput>(event).and_then(|input| input.body()) {
+        Ok(body) => {
+            let comment = new_comment(&item, &user_id, body, chrono::Utc::now());
+            save_comment(&client, &comment);
+            ok(comment)
+        }
+        Err(error) => bad_request(error),
+    }
+}
+
+/// Changes the body of a comment, for its author only.
+pub fn update(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::comment::Comment,
+        SerializableError,
+    >,
+> {
+    let todo_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let comment_id = event["pathParameters"]["comment_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let client = dynamodb_client();
+    let user_id = authorized_user_id(event);
+    let item = match member::find_item(&client, &env::var("table").unwrap(), &user_id, &todo_id) {
+        Some((item, _)) => item,
+        None => return not_found("todo", todo_id),
+    };
+    let mut comment = match find_comment(&client, &item, &comment_id) {
+        Some(ref comment) if comment.author != user_id => {
+            return forbidden("comment", comment_id)
+        }
+        Some(comment) => comment,
+        None => return not_found("comment", comment_id),
+    };
+
+    match parse_body::<CommentInput>(event).and_then(|input| input.body()) {
+        Ok(body) => {
+            comment.body = body;
+            comment.updated_at = Some(chrono::Utc::now());
+            save_comment(&client, &comment);
+            ok(comment)
+        }
+        Err(error) => bad_request(error),
+    }
+}
+
+/// Removes a comment, for its author or the owner of the item.
+pub fn delete(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::comment::Comment,
+        SerializableError,
+    >,
+> {
+    let todo_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let comment_id = event["pathParameters"]["comment_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let client = dynamodb_client();
+    let user_id = authorized_user_id(event);
+    let item = match member::find_item(&client, &env::var("table").unwrap(), &user_id, &todo_id) {
+        Some((item, _)) => item,
+        None => return not_found("todo", todo_id),
+    };
+    let comment = match find_comment(&client, &item, &comment_id) {
+        Some(ref comment) if comment.author != user_id && item.uid != user_id => {
+            return forbidden("comment", comment_id)
+        }
+        Some(comment) => comment,
+        None => return not_found("comment", comment_id),
+    };
+
+    let delete_item = DeleteItemInput {
+        key: user_key(&comment.uid, &comment.id),
+        table_name: env::var("comments_table").unwrap(),
+        ..Default::default()
+    };
+    client.delete_item(&delete_item).unwrap();
+    ok(comment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json;
+
+    #[test]
+    fn should_bound_page_size() {
+        let event = |query: &str| -> crowbar::Value {
+            serde_json::from_str(&format!(r#"{{"queryStringParameters": {}}}"#, query)).unwrap()
+        };
+
+        assert_eq!(page_size(&event("null")), DEFAULT_PAGE_SIZE);
+        assert_eq!(page_size(&event(r#"{"limit": "10"}"#)), 10);
+        assert_eq!(page_size(&event(r#"{"limit": "1000"}"#)), MAX_PAGE_SIZE);
+        assert_eq!(page_size(&event(r#"{"limit": "0"}"#)), 1);
+    }
+
+    #[test]
+    fn should_reject_empty_comments() {
+        let input = CommentInput { body: Some("  \n".to_string()) };
+
+        assert!(input.body().is_err());
+        assert!(CommentInput { body: None }.body().is_err());
+    }
+
+    #[test]
+    fn should_sort_comments_by_creation_date() {
+        let item = model::basic_item::test_item();
+        let author = model::UserId("u2".to_string());
+        let first = new_comment(
+            &item,
+            &author,
+            "**first**".to_string(),
+            chrono::Utc.ymd(2026, 10, 19).and_hms(9, 0, 0),
+        );
+        let second = new_comment(
+            &item,
+            &author,
+            "second".to_string(),
+            chrono::Utc.ymd(2026, 10, 19).and_hms(10, 0, 0),
+        );
+
+        assert!(first.id.starts_with("i1/"));
+        assert!(first.id < second.id);
+        assert_eq!(first.uid, item.uid);
+    }
+}
@@ -0,0 +1,384 @@
+use std::env;
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use chrono_tz;
+use crowbar;
+use http;
+use mime;
+use serde::de::DeserializeOwned;
+use serde_json;
+use uuid;
+use serde::ser::{Serialize, Serializer, SerializeStruct};
+use failure::{Error, Fail};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use rusoto_core::{DefaultCredentialsProvider, Region};
+use rusoto_dynamodb::{AttributeValue, DynamoDb, DynamoDbClient, TransactWriteItem,
+                      TransactWriteItemsError, TransactWriteItemsInput};
+use rusoto_core::default_tls_client;
+
+use model;
+
+pub struct SerializableError(pub Error);
+impl Serialize for SerializableError {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("Error", 1)?;
+        state.serialize_field("error", &format!("{}", self.0))?;
+        state.end()
+    }
+}
+impl<F: Fail> From<F> for SerializableError {
+    fn from(failure: F) -> SerializableError {
+        SerializableError(failure.into())
+    }
+}
+
+#[derive(Debug, Fail)]
+#[fail(display = "Missing Body")]
+pub struct MissingBody();
+#[derive(Debug, Fail)]
+#[fail(display = "Missing Field: '{}'", _0)]
+pub struct MissingField(pub &'static str);
+#[derive(Debug, Fail)]
+#[fail(display = "Parsing Error: {}", serde_error)]
+pub struct ParsingError {
+    #[cause]
+    pub serde_error: ::serde_json::Error,
+}
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid UUID for {}: '{}'", field, uuid)]
+pub struct InvalidUUIDError {
+    pub uuid: String,
+    pub field: &'static str,
+    #[cause]
+    pub uuid_error: uuid::ParseError,
+}
+#[derive(Debug, Fail)]
+#[fail(display = "not found: {} with id '{}'", object, id)]
+pub struct NotFound {
+    pub object: &'static str,
+    pub id: String,
+}
+#[derive(Debug, Fail)]
+#[fail(display = "forbidden: {} with id '{}'", object, id)]
+pub struct Forbidden {
+    pub object: &'static str,
+    pub id: String,
+}
+
+/// Region of the lambda, from `AWS_REGION`.
+pub fn aws_region() -> Result<Region, String> {
+    env::var("AWS_REGION")
+        .unwrap_or_else(|_| "us-east-1".to_string())
+        .parse::<Region>()
+        .map_err(|error| error.to_string())
+}
+
+pub fn dynamodb_client() -> impl DynamoDb {
+    let provider = DefaultCredentialsProvider::new().unwrap();
+    DynamoDbClient::new(default_tls_client().unwrap(), provider, aws_region().unwrap())
+}
+
+pub fn authorized_user_id(event: &crowbar::Value) -> model::UserId {
+    event["requestContext"]["authorizer"]["user_id"]
+        .as_str()
+        .unwrap()
+        .to_string()
+        .into()
+}
+
+pub fn authorized_actor(event: &crowbar::Value) -> model::Actor {
+    model::Actor {
+        user_id: authorized_user_id(event),
+        app_id: event["requestContext"]["authorizer"]["app_id"]
+            .as_str()
+            .unwrap()
+            .to_string()
+            .into(),
+    }
+}
+
+/// Value of a request header, header names being case insensitive.
+pub fn header<'a>(event: &'a crowbar::Value, name: &str) -> Option<&'a str> {
+    event["headers"].as_object().and_then(|headers| {
+        headers
+            .iter()
+            .find(|&(key, _)| key.to_lowercase() == name.to_lowercase())
+            .and_then(|(_, value)| value.as_str())
+    })
+}
+
+pub fn parse_body<T: DeserializeOwned>(event: &crowbar::Value) -> Result<T, SerializableError> {
+    event["body"]
+        .as_str()
+        .ok_or_else(|| MissingBody().into())
+        .and_then(|valid_body| {
+            serde_json::from_slice::<T>(valid_body.as_bytes())
+                .map_err(|err| ParsingError { serde_error: err }.into())
+        })
+}
+
+pub fn parse_uuid(value: &str, field: &'static str) -> Result<String, SerializableError> {
+    uuid::Uuid::parse_str(value)
+        .map(|uuid| format!("{}", uuid))
+        .map_err(|err| {
+            InvalidUUIDError {
+                uuid: value.to_string(),
+                field: field,
+                uuid_error: err,
+            }.into()
+        })
+}
+
+/// Parses a date given by a user, either as a full RFC 3339 date time, or as a day which is then
+/// interpreted as midnight in the timezone of the user.
+pub fn parse_date(value: &str, tz: &chrono_tz::Tz) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|date| date.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d").ok().and_then(
+                |day| {
+                    tz.from_local_datetime(&day.and_hms(0, 0, 0))
+                        .earliest()
+                        .map(|date| date.with_timezone(&Utc))
+                },
+            )
+        })
+}
+
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new(key).unwrap();
+    mac.input(data);
+    mac.result().code().to_vec()
+}
+
+/// Lower case hexadecimal encoding.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// maximum number of actions accepted by DynamoDB in one `TransactWriteItems`
+pub const MAX_TRANSACTION_ACTIONS: usize = 25;
+/// reason given by DynamoDB for an action of a canceled transaction whose condition failed
+pub const CONDITIONAL_CHECK_FAILED: &str = "ConditionalCheckFailed";
+
+/// Writes all the actions or none of them. When the transaction fails, returns the reason of
+/// each action, in order, `None` for the actions that did not cause the failure.
+pub fn transact_write<D: DynamoDb>(
+    client: &D,
+    actions: Vec<TransactWriteItem>,
+) -> Result<(), Vec<Option<String>>> {
+    let count = actions.len();
+    let transact_write = TransactWriteItemsInput {
+        transact_items: actions,
+        ..Default::default()
+    };
+    match client.transact_write_items(&transact_write) {
+        Ok(_) => Ok(()),
+        Err(TransactWriteItemsError::TransactionCanceled(message)) => {
+            Err(cancellation_reasons(&message, count))
+        }
+        Err(error) => Err(vec![Some(error.to_string()); count]),
+    }
+}
+
+/// Reasons of a canceled transaction, listed by DynamoDB at the end of its message, like
+/// `Transaction cancelled, please refer cancellation reasons for specific reasons [None,
+/// ConditionalCheckFailed]`. When they cannot be read, the message is the reason of all actions.
+fn cancellation_reasons(message: &str, count: usize) -> Vec<Option<String>> {
+    let reasons: Vec<Option<String>> = match (message.rfind('['), message.rfind(']')) {
+        (Some(start), Some(end)) if start < end => {
+            message[start + 1..end]
+                .split(',')
+                .map(|reason| reason.trim())
+                .map(|reason| if reason == "None" {
+                    None
+                } else {
+                    Some(reason.to_string())
+                })
+                .collect()
+        }
+        _ => vec![],
+    };
+    if reasons.len() == count {
+        reasons
+    } else {
+        vec![Some(message.to_string()); count]
+    }
+}
+
+/// Key of an object in a table partitioned by user.
+pub fn user_key(uid: &model::UserId, id: &str) -> HashMap<String, AttributeValue> {
+    let mut key = HashMap::new();
+    key.insert(
+        "uid".to_string(),
+        AttributeValue {
+            s: Some(uid.to_string()),
+            ..Default::default()
+        },
+    );
+    key.insert(
+        "id".to_string(),
+        AttributeValue {
+            s: Some(id.to_string()),
+            ..Default::default()
+        },
+    );
+    key
+}
+
+pub fn not_found<T>(
+    object: &'static str,
+    id: String,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<T, SerializableError>> {
+    Ok(crowbar::ApiGatewayResponse {
+        status_code: http::StatusCode::NOT_FOUND,
+        body: Some((
+            Err(NotFound { object: object, id: id }.into()),
+            mime::APPLICATION_JSON,
+        )),
+        ..Default::default()
+    })
+}
+
+pub fn forbidden<T>(
+    object: &'static str,
+    id: String,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<T, SerializableError>> {
+    Ok(crowbar::ApiGatewayResponse {
+        status_code: http::StatusCode::FORBIDDEN,
+        body: Some((
+            Err(Forbidden { object: object, id: id }.into()),
+            mime::APPLICATION_JSON,
+        )),
+        ..Default::default()
+    })
+}
+
+pub fn bad_request<T>(
+    error: SerializableError,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<T, SerializableError>> {
+    Ok(crowbar::ApiGatewayResponse {
+        status_code: http::StatusCode::BAD_REQUEST,
+        body: Some((Err(error), mime::APPLICATION_JSON)),
+        ..Default::default()
+    })
+}
+
+pub fn precondition_failed<T>(
+    error: SerializableError,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<T, SerializableError>> {
+    Ok(crowbar::ApiGatewayResponse {
+        status_code: http::StatusCode::PRECONDITION_FAILED,
+        body: Some((Err(error), mime::APPLICATION_JSON)),
+        ..Default::default()
+    })
+}
+
+pub fn too_many_requests<T>(
+    error: SerializableError,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<T, SerializableError>> {
+    Ok(crowbar::ApiGatewayResponse {
+        status_code: http::StatusCode::TOO_MANY_REQUESTS,
+        body: Some((Err(error), mime::APPLICATION_JSON)),
+        ..Default::default()
+    })
+}
+
+pub fn service_unavailable<T>(
+    error: SerializableError,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<T, SerializableError>> {
+    Ok(crowbar::ApiGatewayResponse {
+        status_code: http::StatusCode::SERVICE_UNAVAILABLE,
+        body: Some((Err(error), mime::APPLICATION_JSON)),
+        ..Default::default()
+    })
+}
+
+/// Adds a header to a successful response.
+pub fn with_header<T>(
+    response: crowbar::LambdaResult<crowbar::ApiGatewayResponse<T, SerializableError>>,
+    name: &str,
+    value: String,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<T, SerializableError>> {
+    response.map(|mut response| {
+        response.headers.insert(name.to_string(), value);
+        response
+    })
+}
+
+/// Header listing, separated by commas, the items an operation left unchanged because another
+/// request changed them concurrently.
+pub const SKIPPED_ITEMS_HEADER: &str = "X-Vleue-Skipped-Items";
+
+pub fn with_skipped_items<T>(
+    response: crowbar::LambdaResult<crowbar::ApiGatewayResponse<T, SerializableError>>,
+    skipped: &[model::ItemId],
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<T, SerializableError>> {
+    if skipped.is_empty() {
+        response
+    } else {
+        let ids: Vec<String> = skipped.iter().map(|id| id.to_string()).collect();
+        with_header(response, SKIPPED_ITEMS_HEADER, ids.join(","))
+    }
+}
+
+pub fn ok<T>(body: T) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<T, SerializableError>> {
+    Ok(crowbar::ApiGatewayResponse {
+        status_code: http::StatusCode::OK,
+        body: Some((Ok(body), mime::APPLICATION_JSON)),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_day_in_user_timezone() {
+        let date = parse_date("2026-11-01", &chrono_tz::Europe::Paris);
+
+        assert_eq!(
+            date,
+            Some(Utc.ymd(2026, 10, 31).and_hms(23, 0, 0))
+        );
+    }
+
+    #[test]
+    fn should_find_headers_ignoring_case() {
+        let event: crowbar::Value =
+            serde_json::from_str(r#"{ "headers": { "if-match": "\"3\"" } }"#).unwrap();
+
+        assert_eq!(header(&event, "If-Match"), Some("\"3\""));
+        assert_eq!(header(&event, "ETag"), None);
+    }
+
+    #[test]
+    fn can_parse_full_date() {
+        let date = parse_date("2026-11-01T10:00:00+02:00", &chrono_tz::Europe::Paris);
+
+        assert_eq!(date, Some(Utc.ymd(2026, 11, 1).and_hms(8, 0, 0)));
+    }
+
+    #[test]
+    fn can_read_reasons_of_canceled_transactions() {
+        let message = "Transaction cancelled, please refer cancellation reasons for specific \
+                       reasons [None, ConditionalCheckFailed, None]";
+
+        assert_eq!(
+            cancellation_reasons(message, 3),
+            vec![None, Some(CONDITIONAL_CHECK_FAILED.to_string()), None]
+        );
+        assert_eq!(
+            cancellation_reasons("Throughput exceeded", 2),
+            vec![Some("Throughput exceeded".to_string()); 2]
+        );
+    }
+}
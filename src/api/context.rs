@@ -0,0 +1,206 @@
+use std::env;
+
+use crowbar;
+use chrono;
+use uuid;
+use serde_dynamodb;
+use serde_dynamodb::ToQueryInput;
+
+use rusoto_dynamodb::{DeleteItemInput, DynamoDb, PutItemInput};
+
+use model;
+
+use super::common::*;
+use super::todo;
+
+#[derive(Debug, Fail)]
+#[fail(display = "Context Already Exists: '{}'", _0)]
+struct DuplicateContext(String);
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ContextList {
+    pub contexts: Vec<model::context::Context>,
+}
+
+pub fn user_contexts<D: DynamoDb>(
+    client: &D,
+    table: &str,
+    user_id: &model::UserId,
+) -> Vec<model::context::Context> {
+    let uid_filter = model::context::ContextQueryInput {
+        uid: Some(user_id.clone()),
+        ..Default::default()
+    };
+    client
+        .query(&uid_filter.to_query_input(table.to_string()))
+        .unwrap()
+        .items
+        .unwrap_or_else(|| vec![])
+        .into_iter()
+        .map(|context| serde_dynamodb::from_hashmap(context).unwrap())
+        .collect()
+}
+
+pub fn save_context<D: DynamoDb>(client: &D, table: &str, context: &model::context::Context) {
+    let put_item = PutItemInput {
+        item: serde_dynamodb::to_hashmap(context).unwrap(),
+        table_name: table.to_string(),
+        ..Default::default()
+    };
+    client.put_item(&put_item).unwrap();
+}
+
+/// Context names are displayed prefixed by `@`, add it if the user did not.
+pub fn normalize_name(name: &str) -> String {
+    let name = name.trim();
+    if name.starts_with('@') {
+        name.to_string()
+    } else {
+        format!("@{}", name)
+    }
+}
+
+#[derive(Deserialize)]
+struct ContextInput {
+    name: Option<String>,
+}
+
+pub fn list(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<ContextList, SerializableError>> {
+    let table = env::var("contexts_table").unwrap();
+    let client = dynamodb_client();
+    ok(ContextList {
+        contexts: user_contexts(&client, &table, &authorized_user_id(event)),
+    })
+}
+
+pub fn add(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::context::Context,
+        SerializableError,
+    >,
+> {
+    let table = env::var("contexts_table").unwrap();
+    let client = dynamodb_client();
+    let user_id = authorized_user_id(event);
+    let data_result = parse_body::<ContextInput>(event)
+        .and_then(|input| {
+            input.name.ok_or_else(|| MissingField("name").into())
+        })
+        .map(|name| normalize_name(&name))
+        .and_then(|name| {
+            let existing = user_contexts(&client, &table, &user_id);
+            if existing.iter().any(|context| {
+                context.name.to_lowercase() == name.to_lowercase()
+            })
+            {
+                Err(DuplicateContext(name).into())
+            } else {
+                Ok(name)
+            }
+        });
+
+    match data_result {
+        Ok(name) => {
+            let context = model::context::Context {
+                uid: user_id,
+                id: model::ContextId(format!("{}", uuid::Uuid::new_v4().hyphenated())),
+                name: name,
+            };
+            save_context(&client, &table, &context);
+            ok(context)
+        }
+        Err(error) => bad_request(error),
+    }
+}
+
+/// Deletes a context, and removes it from all items using it. Items changed concurrently keep it
+/// and are listed in the `X-Vleue-Skipped-Items` header.
+pub fn delete(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::context::Context,
+        SerializableError,
+    >,
+> {
+    let context_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let table = env::var("contexts_table").unwrap();
+    let client = dynamodb_client();
+    let actor = authorized_actor(event);
+    let user_id = actor.user_id.clone();
+    let context = match user_contexts(&client, &table, &user_id).into_iter().find(
+        |context| {
+            context.id.to_string() == context_id
+        },
+    ) {
+        Some(context) => context,
+        None => return not_found("context", context_id),
+    };
+
+    let items_table = env::var("table").unwrap();
+    let mut skipped = vec![];
+    for item in todo::all_user_items(&client, &items_table, &user_id) {
+        if item.contexts.contains(&context.id) {
+            let mut updated = item.clone();
+            updated.contexts.retain(|id| id != &context.id);
+            updated.touch(&actor, chrono::Utc::now());
+            // an item changed concurrently keeps the contexts given by the other request
+            if todo::save_item(&client, &items_table, Some(&item), &updated).is_err() {
+                skipped.push(item.id.clone());
+            }
+        }
+    }
+    let delete_item = DeleteItemInput {
+        key: user_key(&user_id, &context_id),
+        table_name: table,
+        ..Default::default()
+    };
+    client.delete_item(&delete_item).unwrap();
+
+    with_skipped_items(ok(context), &skipped)
+}
+
+/// Lists the items in a context.
+pub fn todos(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::api::ItemList,
+        SerializableError,
+    >,
+> {
+    let context_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let client = dynamodb_client();
+    let user_id = authorized_user_id(event);
+    let context = match user_contexts(&client, &env::var("contexts_table").unwrap(), &user_id)
+        .into_iter()
+        .find(|context| context.id.to_string() == context_id) {
+        Some(context) => context,
+        None => return not_found("context", context_id),
+    };
+
+    let items = todo::user_items(&client, &env::var("table").unwrap(), &user_id)
+        .into_iter()
+        .filter(|item| item.contexts.contains(&context.id))
+        .collect();
+    ok(model::api::ItemList { items: items })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_prefix_context_names() {
+        assert_eq!(normalize_name("home"), "@home");
+        assert_eq!(normalize_name(" @phone "), "@phone");
+    }
+}
@@ -0,0 +1,268 @@
+//! Export of all the data of a user.
+
+use std::env;
+
+use crowbar;
+use chrono::{DateTime, Utc};
+use http;
+use mime;
+use serde_json;
+
+use model;
+
+use super::comment;
+use super::common::*;
+use super::context;
+use super::ical;
+use super::member;
+use super::project;
+use super::template;
+use super::todo;
+use super::user;
+use super::webhook;
+
+const CSV_COLUMNS: &[&str] = &[
+    "id",
+    "title",
+    "description",
+    "project",
+    "contexts",
+    "due",
+    "flagged",
+    "status",
+    "created_at",
+    "completed_at",
+];
+
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid Export Format: '{}'", _0)]
+struct InvalidFormat(String);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Json,
+    Csv,
+    ICalendar,
+}
+impl Format {
+    /// JSON if no format is given.
+    fn from_event(event: &crowbar::Value) -> Result<Format, InvalidFormat> {
+        match event["queryStringParameters"]["format"].as_str() {
+            None | Some("json") => Ok(Format::Json),
+            Some("csv") => Ok(Format::Csv),
+            Some("ics") => Ok(Format::ICalendar),
+            Some(format) => Err(InvalidFormat(format.to_string())),
+        }
+    }
+
+    fn mime(&self) -> mime::Mime {
+        match *self {
+            Format::Json => mime::APPLICATION_JSON,
+            Format::Csv => mime::TEXT_CSV,
+            Format::ICalendar => "text/calendar".parse().unwrap(),
+        }
+    }
+}
+
+/// All the data of a user, items in the trash included. Attachments are listed with their items,
+/// without their content.
+#[derive(Serialize, Debug)]
+pub struct Export {
+    pub user: user::Profile,
+    pub projects: Vec<model::project::Project>,
+    pub contexts: Vec<model::context::Context>,
+    pub items: Vec<model::basic_item::BasicItem>,
+    /// comments on the items of the user, by any author
+    pub comments: Vec<model::comment::Comment>,
+    pub templates: Vec<model::template::Template>,
+    pub webhooks: Vec<model::webhook::Webhook>,
+    /// projects of other users shared with the user
+    pub memberships: Vec<model::member::Member>,
+    /// members of the projects of the user
+    pub members: Vec<model::member::Member>,
+}
+
+/// Quotes a CSV field if needed.
+fn csv_field(value: &str) -> String {
+    if value.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') ||
+        value.trim() != value
+    {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn format_date(date: &Option<DateTime<Utc>>) -> String {
+    date.map(|date| date.to_rfc3339()).unwrap_or_else(
+        || "".to_string(),
+    )
+}
+
+/// Renders the items with one line each, naming projects and contexts so that the file can be
+/// imported back.
+fn to_csv(export: &Export) -> String {
+    let mut lines = vec![CSV_COLUMNS.join(",")];
+    for item in &export.items {
+        let project = export
+            .projects
+            .iter()
+            .find(|project| project.id == item.project_id)
+            .map(|project| project.name.clone())
+            .unwrap_or_else(|| "".to_string());
+        let contexts: Vec<String> = export
+            .contexts
+            .iter()
+            .filter(|context| item.contexts.contains(&context.id))
+            .map(|context| context.name.clone())
+            .collect();
+        let fields = vec![
+            item.id.to_string(),
+            item.title.clone(),
+            item.description.clone(),
+            project,
+            contexts.join(";"),
+            format_date(&item.due),
+            item.flagged.to_string(),
+            item.status.name.clone(),
+            format_date(&item.created_at),
+            format_date(&item.completed_at),
+        ];
+        lines.push(
+            fields
+                .iter()
+                .map(|field| csv_field(field))
+                .collect::<Vec<String>>()
+                .join(","),
+        );
+    }
+    lines.iter().map(|line| format!("{}\r\n", line)).collect()
+}
+
+/// Exports the data of the user in the format given by the `format` query string parameter:
+/// `json` (default), `csv` or `ics`. CSV and iCalendar exports only contain the items not in the
+/// trash.
+pub fn export(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<String, SerializableError>> {
+    let format = match Format::from_event(event) {
+        Ok(format) => format,
+        Err(error) => return bad_request(error.into()),
+    };
+    let client = dynamodb_client();
+    let user_id = authorized_user_id(event);
+    let projects = project::user_projects(&client, &env::var("projects_table").unwrap(), &user_id);
+    let mut export = Export {
+        user: user::Profile::from(&user::current_user(&client, &user_id)),
+        contexts: context::user_contexts(&client, &env::var("contexts_table").unwrap(), &user_id),
+        items: todo::all_user_items(&client, &env::var("table").unwrap(), &user_id),
+        comments: vec![],
+        templates: vec![],
+        webhooks: vec![],
+        memberships: vec![],
+        members: vec![],
+        projects: projects,
+    };
+    if format == Format::Json {
+        export.comments = comment::user_comments(&client, &user_id);
+        export.templates =
+            template::user_templates(&client, &env::var("templates_table").unwrap(), &user_id);
+        export.webhooks =
+            webhook::user_webhooks(&client, &env::var("webhooks_table").unwrap(), &user_id);
+        export.memberships = member::memberships(&client, &user_id);
+        export.members = export
+            .projects
+            .iter()
+            .flat_map(|project| member::project_members(&client, &project.id))
+            .collect();
+    }
+
+    let body = match format {
+        Format::Json => serde_json::to_string(&export).unwrap(),
+        Format::Csv => {
+            export.items.retain(|item| item.deleted_at.is_none());
+            to_csv(&export)
+        }
+        Format::ICalendar => {
+            export.items.retain(|item| item.deleted_at.is_none());
+            ical::calendar(
+                "Vleue",
+                &export.items,
+                &export.projects,
+                &export.contexts,
+                Utc::now(),
+            )
+        }
+    };
+    Ok(crowbar::ApiGatewayResponse {
+        status_code: http::StatusCode::OK,
+        body: Some((Ok(body), format.mime())),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn should_quote_csv_fields_when_needed() {
+        assert_eq!(csv_field("Buy milk"), "Buy milk");
+        assert_eq!(csv_field("milk, eggs"), "\"milk, eggs\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn can_export_items_as_csv() {
+        let user_id = model::UserId("u1".to_string());
+        let export = Export {
+            user: user::Profile {
+                user_id: user_id.clone(),
+                email: "".to_string(),
+                email_verified: false,
+                verification_sent_at: None,
+                tz: None,
+                reminder_channels: vec![],
+                push_endpoint: None,
+            },
+            projects: vec![
+                model::project::Project {
+                    uid: user_id.clone(),
+                    id: model::ProjectId("p1".to_string()),
+                    name: "Work".to_string(),
+                    costs_info: Default::default(),
+                },
+            ],
+            contexts: vec![
+                model::context::Context {
+                    uid: user_id.clone(),
+                    id: model::ContextId("c1".to_string()),
+                    name: "@phone".to_string(),
+                },
+            ],
+            items: vec![
+                model::basic_item::BasicItem {
+                    uid: user_id.clone(),
+                    title: "Call Bob, again".to_string(),
+                    status: model::State::done(),
+                    contexts: vec![model::ContextId("c1".to_string())],
+                    due: Some(Utc.ymd(2026, 10, 20).and_hms(0, 0, 0)),
+                    ..model::basic_item::test_item()
+                },
+            ],
+            comments: vec![],
+            templates: vec![],
+            webhooks: vec![],
+            memberships: vec![],
+            members: vec![],
+        };
+
+        assert_eq!(
+            to_csv(&export),
+            "id,title,description,project,contexts,due,flagged,status,created_at,completed_at\r\n\
+             i1,\"Call Bob, again\",,Work,@phone,2026-10-20T00:00:00+00:00,false,done,,\r\n"
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! Read-only iCalendar feed of the items with a due date, for calendar applications that can not
+//! authenticate with a JWT. The feed is accessed with a token in its URL, that the user can
+//! regenerate or revoke.
+
+use std::env;
+use std::collections::HashMap;
+
+use crowbar;
+use chrono::Utc;
+use http;
+use serde_dynamodb;
+use uuid;
+
+use rusoto_dynamodb::{AttributeValue, DeleteItemInput, DynamoDb, GetItemInput, PutItemInput};
+
+use model;
+
+use super::common::*;
+use super::context;
+use super::ical;
+use super::project;
+use super::todo;
+use super::user;
+
+#[derive(Serialize, Debug)]
+pub struct FeedInfo {
+    pub token: String,
+    /// URL to subscribe to, when it can be built from the request
+    pub url: Option<String>,
+}
+
+fn token_key(token: &str) -> HashMap<String, AttributeValue> {
+    let mut key = HashMap::new();
+    key.insert(
+        "token".to_string(),
+        AttributeValue {
+            s: Some(token.to_string()),
+            ..Default::default()
+        },
+    );
+    key
+}
+
+fn find_feed_token<D: DynamoDb>(client: &D, token: &str) -> Option<model::FeedToken> {
+    let get_item = GetItemInput {
+        key: token_key(token),
+        table_name: env::var("feed_tokens_table").unwrap(),
+        ..Default::default()
+    };
+    client.get_item(&get_item).unwrap().item.map(|feed_token| {
+        serde_dynamodb::from_hashmap(feed_token).unwrap()
+    })
+}
+
+fn remove_feed_token<D: DynamoDb>(client: &D, token: &str) {
+    let delete_item = DeleteItemInput {
+        key: token_key(token),
+        table_name: env::var("feed_tokens_table").unwrap(),
+        ..Default::default()
+    };
+    client.delete_item(&delete_item).unwrap();
+}
+
+/// Random token, long enough not to be guessed.
+pub fn new_token() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+/// Token from the path of the feed, calendar applications often expecting a `.ics` extension.
+fn token_from_path(path: &str) -> &str {
+    path.trim_end_matches(".ics")
+}
+
+/// Public URL of the feed, from the host and stage of the request.
+fn feed_url(event: &crowbar::Value, token: &str) -> Option<String> {
+    header(event, "Host").map(|host| match event["requestContext"]["stage"].as_str() {
+        Some(stage) => format!("https://{}/{}/feed/{}.ics", host, stage, token),
+        None => format!("https://{}/feed/{}.ics", host, token),
+    })
+}
+
+/// Creates a feed token for the caller, revoking the previous one if any.
+pub fn create(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<FeedInfo, SerializableError>> {
+    let client = dynamodb_client();
+    let mut user = user::current_user(&client, &authorized_user_id(event));
+    if let Some(ref previous) = user.feed_token {
+        remove_feed_token(&client, previous);
+    }
+
+    let feed_token = model::FeedToken {
+        token: new_token(),
+        user_id: user.user_id.clone(),
+    };
+    let put_item = PutItemInput {
+        item: serde_dynamodb::to_hashmap(&feed_token).unwrap(),
+        table_name: env::var("feed_tokens_table").unwrap(),
+        ..Default::default()
+    };
+    client.put_item(&put_item).unwrap();
+    user.feed_token = Some(feed_token.token.clone());
+    user::save_user(&client, &user);
+
+    ok(FeedInfo {
+        url: feed_url(event, &feed_token.token),
+        token: feed_token.token,
+    })
+}
+
+/// Revokes the feed token of the caller.
+pub fn revoke(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<user::Profile, SerializableError>> {
+    let client = dynamodb_client();
+    let mut user = user::current_user(&client, &authorized_user_id(event));
+    if let Some(token) = user.feed_token.take() {
+        remove_feed_token(&client, &token);
+        user::save_user(&client, &user);
+    }
+    ok(user::Profile::from(&user))
+}
+
+/// Renders the items with a due date of the owner of the token, not authorized by the JWT
+/// authorizer.
+pub fn feed(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<String, SerializableError>> {
+    let token = token_from_path(event["pathParameters"]["token"].as_str().unwrap_or(""));
+    let client = dynamodb_client();
+    let user_id = match find_feed_token(&client, token) {
+        Some(feed_token) => feed_token.user_id,
+        None => return not_found("feed", token.to_string()),
+    };
+
+    let items: Vec<model::basic_item::BasicItem> =
+        todo::user_items(&client, &env::var("table").unwrap(), &user_id)
+            .into_iter()
+            .filter(|item| item.due.is_some())
+            .collect();
+    let projects = project::user_projects(&client, &env::var("projects_table").unwrap(), &user_id);
+    let contexts = context::user_contexts(&client, &env::var("contexts_table").unwrap(), &user_id);
+    Ok(crowbar::ApiGatewayResponse {
+        status_code: http::StatusCode::OK,
+        body: Some((
+            Ok(ical::calendar("Vleue", &items, &projects, &contexts, Utc::now())),
+            "text/calendar".parse().unwrap(),
+        )),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn should_accept_token_with_extension() {
+        assert_eq!(token_from_path("abc123.ics"), "abc123");
+        assert_eq!(token_from_path("abc123"), "abc123");
+    }
+
+    #[test]
+    fn can_build_feed_url() {
+        let event: crowbar::Value = serde_json::from_str(
+            r#"{"headers": {"Host": "api.example.com"}, "requestContext": {"stage": "prod"}}"#,
+        ).unwrap();
+
+        assert_eq!(
+            feed_url(&event, "abc123"),
+            Some("https://api.example.com/prod/feed/abc123.ics".to_string())
+        );
+    }
+
+    #[test]
+    fn should_generate_different_tokens() {
+        let token = new_token();
+
+        assert_eq!(token.len(), 64);
+        assert!(token != new_token());
+    }
+}
@@ -0,0 +1,282 @@
+//! Append-only log of the changes made to each item.
+
+use std::env;
+use std::collections::HashMap;
+
+use crowbar;
+use serde_dynamodb;
+use serde_json;
+
+use rusoto_dynamodb::{AttributeValue, DynamoDb, PutItemInput, QueryInput};
+
+use model;
+
+use super::common::*;
+use super::member;
+
+/// fields maintained by the server or derived from other fields, not worth an entry
+const IGNORED_FIELDS: &[&str] = &[
+    "uid",
+    "id",
+    "created_at",
+    "created_by",
+    "updated_at",
+    "updated_by",
+    "completed_at",
+    "version",
+];
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 100;
+
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid Cursor: '{}'", _0)]
+struct InvalidCursor(String);
+
+#[derive(Serialize, Debug)]
+pub struct History {
+    pub entries: Vec<model::history::HistoryEntry>,
+    /// to pass as the `after` query string parameter to get the next page, if there is one
+    pub next: Option<String>,
+}
+
+/// Number of entries per page, from the `limit` query string parameter.
+fn page_size(event: &crowbar::Value) -> i64 {
+    event["queryStringParameters"]["limit"]
+        .as_str()
+        .and_then(|limit| limit.parse::<i64>().ok())
+        .map(|limit| limit.max(1).min(MAX_PAGE_SIZE))
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+}
+
+/// Fields that differ between two states of an item, `before` being `None` for a new item.
+pub fn diff(
+    before: Option<&model::basic_item::BasicItem>,
+    after: &model::basic_item::BasicItem,
+) -> Vec<model::history::FieldChange> {
+    let empty = serde_json::Map::new();
+    let before_value = before.map(|before| serde_json::to_value(before).unwrap());
+    let before_fields = before_value
+        .as_ref()
+        .and_then(|value| value.as_object())
+        .unwrap_or(&empty);
+    let after_value = serde_json::to_value(after).unwrap();
+    let after_fields = after_value.as_object().unwrap_or(&empty);
+
+    after_fields
+        .iter()
+        .filter(|&(field, _)| !IGNORED_FIELDS.contains(&field.as_str()))
+        .filter_map(|(field, after)| {
+            let before = before_fields.get(field).unwrap_or(&serde_json::Value::Null);
+            if before == after {
+                None
+            } else {
+                Some(model::history::FieldChange {
+                    field: field.clone(),
+                    before: before.to_string(),
+                    after: after.to_string(),
+                })
+            }
+        })
+        .collect()
+}
+
+/// History entry for a change made to an item, `None` if nothing visible changed.
+pub fn entry(
+    before: Option<&model::basic_item::BasicItem>,
+    after: &model::basic_item::BasicItem,
+) -> Option<model::history::HistoryEntry> {
+    let changes = diff(before, after);
+    match (after.updated_at, after.updated_by.clone()) {
+        (Some(at), Some(actor)) if !changes.is_empty() => {
+            Some(model::history::HistoryEntry {
+                uid: after.uid.clone(),
+                id: format!("{}/{:020}", after.id.to_string(), after.version),
+                item_id: after.id.clone(),
+                version: after.version,
+                at: at,
+                actor: actor,
+                changes: changes,
+            })
+        }
+        _ => None,
+    }
+}
+
+pub fn save_entry<D: DynamoDb>(client: &D, entry: &model::history::HistoryEntry) {
+    let put_item = PutItemInput {
+        item: serde_dynamodb::to_hashmap(entry).unwrap(),
+        table_name: env::var("history_table").unwrap(),
+        ..Default::default()
+    };
+    client.put_item(&put_item).unwrap();
+}
+
+/// Records the changes made to an item.
+pub fn record<D: DynamoDb>(
+    client: &D,
+    before: Option<&model::basic_item::BasicItem>,
+    after: &model::basic_item::BasicItem,
+) {
+    if let Some(entry) = entry(before, after) {
+        save_entry(client, &entry);
+    }
+}
+
+/// A page of the history of an item, oldest first, starting after the entry `after`.
+fn item_history<D: DynamoDb>(
+    client: &D,
+    table: &str,
+    user_id: &model::UserId,
+    item_id: &str,
+    after: Option<&str>,
+    limit: Option<i64>,
+) -> (Vec<model::history::HistoryEntry>, Option<String>) {
+    let mut names = HashMap::new();
+    names.insert("#uid".to_string(), "uid".to_string());
+    names.insert("#id".to_string(), "id".to_string());
+    let mut values = HashMap::new();
+    values.insert(
+        ":uid".to_string(),
+        AttributeValue {
+            s: Some(user_id.to_string()),
+            ..Default::default()
+        },
+    );
+    values.insert(
+        ":prefix".to_string(),
+        AttributeValue {
+            s: Some(format!("{}/", item_id)),
+            ..Default::default()
+        },
+    );
+    let query_input = QueryInput {
+        table_name: table.to_string(),
+        key_condition_expression: Some("#uid = :uid AND begins_with(#id, :prefix)".to_string()),
+        expression_attribute_names: Some(names),
+        expression_attribute_values: Some(values),
+        exclusive_start_key: after.map(|after| user_key(user_id, after)),
+        limit: limit,
+        ..Default::default()
+    };
+    let output = client.query(&query_input).unwrap();
+    let next = output.last_evaluated_key.and_then(|key| {
+        key.get("id").and_then(|id| id.s.clone())
+    });
+    (
+        output
+            .items
+            .unwrap_or_else(|| vec![])
+            .into_iter()
+            .map(|entry| serde_dynamodb::from_hashmap(entry).unwrap())
+            .collect(),
+        next,
+    )
+}
+
+/// Lists the changes made to an item, oldest first, by pages of `limit` entries.
+pub fn history(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<History, SerializableError>> {
+    let todo_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let client = dynamodb_client();
+    let user_id = authorized_user_id(event);
+    // the history is stored with the owner of the item
+    let owner = member::find_item(&client, &env::var("table").unwrap(), &user_id, &todo_id)
+        .map(|(item, _)| item.uid);
+    let after = event["queryStringParameters"]["after"].as_str();
+    if let Some(after) = after {
+        if !after.starts_with(&format!("{}/", todo_id)) {
+            return bad_request(InvalidCursor(after.to_string()).into());
+        }
+    }
+    let (entries, next) = item_history(
+        &client,
+        &env::var("history_table").unwrap(),
+        owner.as_ref().unwrap_or(&user_id),
+        &todo_id,
+        after,
+        Some(page_size(event)),
+    );
+    // the history of a purged item is still available to its owner
+    if entries.is_empty() && owner.is_none() {
+        return not_found("todo", todo_id);
+    }
+    ok(History {
+        entries: entries,
+        next: next,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono;
+    use chrono::TimeZone;
+
+    fn actor() -> model::Actor {
+        model::Actor {
+            user_id: model::UserId("u1".to_string()),
+            app_id: model::AppId("a1".to_string()),
+        }
+    }
+
+    #[test]
+    fn should_bound_page_size() {
+        let event = |query: &str| -> crowbar::Value {
+            serde_json::from_str(&format!(r#"{{"queryStringParameters": {}}}"#, query)).unwrap()
+        };
+
+        assert_eq!(page_size(&event("null")), DEFAULT_PAGE_SIZE);
+        assert_eq!(page_size(&event(r#"{"limit": "10"}"#)), 10);
+        assert_eq!(page_size(&event(r#"{"limit": "1000"}"#)), MAX_PAGE_SIZE);
+    }
+
+    #[test]
+    fn should_list_changed_fields() {
+        let before = model::basic_item::test_item();
+        let mut after = model::basic_item::test_item();
+        after.title = "renamed".to_string();
+        after.flagged = true;
+        after.touch(&actor(), chrono::Utc.ymd(2026, 10, 1).and_hms(0, 0, 0));
+
+        let changes = diff(Some(&before), &after);
+
+        assert_eq!(
+            changes,
+            vec![
+                model::history::FieldChange {
+                    field: "flagged".to_string(),
+                    before: "false".to_string(),
+                    after: "true".to_string(),
+                },
+                model::history::FieldChange {
+                    field: "title".to_string(),
+                    before: "\"item\"".to_string(),
+                    after: "\"renamed\"".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn should_record_actor_and_version() {
+        let mut after = model::basic_item::test_item();
+        after.created(&actor(), chrono::Utc.ymd(2026, 10, 1).and_hms(0, 0, 0));
+
+        let entry = entry(None, &after).unwrap();
+
+        assert_eq!(entry.id, "i1/00000000000000000001");
+        assert_eq!(entry.actor, actor());
+        assert!(entry.changes.iter().any(|change| change.field == "title"));
+    }
+
+    #[test]
+    fn should_not_record_metadata_only_changes() {
+        let before = model::basic_item::test_item();
+        let mut after = model::basic_item::test_item();
+        after.touch(&actor(), chrono::Utc.ymd(2026, 10, 1).and_hms(0, 0, 0));
+
+        assert!(entry(Some(&before), &after).is_none());
+    }
+}
@@ -0,0 +1,169 @@
+//! Rendering of items as iCalendar `VTODO` components (RFC 5545).
+
+use chrono::{DateTime, Utc};
+
+use model;
+
+/// content lines longer than this many octets must be folded
+const MAX_LINE_LENGTH: usize = 75;
+
+fn format_date(date: &DateTime<Utc>) -> String {
+    date.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Splits a content line in lines of at most 75 octets, continuation lines starting with a space.
+fn fold_line(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_LENGTH {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+/// Renders an item as a `VTODO`, its project and contexts being listed as categories.
+fn vtodo(
+    item: &model::basic_item::BasicItem,
+    projects: &[model::project::Project],
+    contexts: &[model::context::Context],
+    now: DateTime<Utc>,
+) -> Vec<String> {
+    let mut lines = vec![
+        "BEGIN:VTODO".to_string(),
+        format!("UID:{}", item.id.to_string()),
+        format!("DTSTAMP:{}", format_date(&item.updated_at.unwrap_or(now))),
+        format!("SUMMARY:{}", escape_text(&item.title)),
+    ];
+    if !item.description.is_empty() {
+        lines.push(format!("DESCRIPTION:{}", escape_text(&item.description)));
+    }
+    if let Some(ref created_at) = item.created_at {
+        lines.push(format!("CREATED:{}", format_date(created_at)));
+    }
+    if let Some(ref updated_at) = item.updated_at {
+        lines.push(format!("LAST-MODIFIED:{}", format_date(updated_at)));
+    }
+    if let Some(ref due) = item.due {
+        lines.push(format!("DUE:{}", format_date(due)));
+    }
+    if item.status.is_done() {
+        lines.push("STATUS:COMPLETED".to_string());
+        if let Some(ref completed_at) = item.completed_at {
+            lines.push(format!("COMPLETED:{}", format_date(completed_at)));
+        }
+    } else {
+        lines.push("STATUS:NEEDS-ACTION".to_string());
+    }
+    if item.flagged {
+        lines.push("PRIORITY:1".to_string());
+    }
+    let categories: Vec<String> = projects
+        .iter()
+        .filter(|project| project.id == item.project_id)
+        .map(|project| escape_text(&project.name))
+        .chain(
+            contexts
+                .iter()
+                .filter(|context| item.contexts.contains(&context.id))
+                .map(|context| escape_text(&context.name)),
+        )
+        .collect();
+    if !categories.is_empty() {
+        lines.push(format!("CATEGORIES:{}", categories.join(",")));
+    }
+    if let Some(ref parent_id) = item.parent_id {
+        lines.push(format!("RELATED-TO:{}", parent_id.to_string()));
+    }
+    lines.push("END:VTODO".to_string());
+    lines
+}
+
+/// Renders items as an iCalendar object.
+pub fn calendar(
+    name: &str,
+    items: &[model::basic_item::BasicItem],
+    projects: &[model::project::Project],
+    contexts: &[model::context::Context],
+    now: DateTime<Utc>,
+) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Vleue//Vleue//EN".to_string(),
+        format!("X-WR-CALNAME:{}", escape_text(name)),
+    ];
+    for item in items {
+        lines.extend(vtodo(item, projects, contexts, now));
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| fold_line(line)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn item() -> model::basic_item::BasicItem {
+        model::basic_item::BasicItem {
+            title: "Call Bob, about the report; again".to_string(),
+            flagged: true,
+            due: Some(Utc.ymd(2026, 10, 20).and_hms(15, 0, 0)),
+            ..model::basic_item::test_item()
+        }
+    }
+
+    #[test]
+    fn can_render_vtodo() {
+        let projects = vec![
+            model::project::Project {
+                uid: model::UserId("u1".to_string()),
+                id: model::ProjectId("p1".to_string()),
+                name: "Work".to_string(),
+                costs_info: Default::default(),
+            },
+        ];
+
+        let lines = vtodo(&item(), &projects, &[], Utc.ymd(2026, 10, 1).and_hms(0, 0, 0));
+
+        assert_eq!(
+            lines,
+            vec![
+                "BEGIN:VTODO",
+                "UID:i1",
+                "DTSTAMP:20261001T000000Z",
+                "SUMMARY:Call Bob\\, about the report\\; again",
+                "DUE:20261020T150000Z",
+                "STATUS:NEEDS-ACTION",
+                "PRIORITY:1",
+                "CATEGORIES:Work",
+                "END:VTODO",
+            ]
+        );
+    }
+
+    #[test]
+    fn should_fold_long_lines() {
+        let folded = fold_line(&format!("SUMMARY:{}", "a".repeat(100)));
+
+        let lines: Vec<&str> = folded.split("\r\n").collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].len(), 75);
+        assert!(lines[1].starts_with(' '));
+        assert_eq!(lines[2], "");
+    }
+}
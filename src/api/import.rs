@@ -0,0 +1,691 @@
+//! Import of items exported from other tools: todo.txt, Taskwarrior JSON export, or CSV.
+//!
+//! Items do not have tags, so tags of the imported tasks become contexts.
+
+use std::env;
+
+use crowbar;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use chrono_tz;
+use serde_json;
+use uuid;
+
+use model;
+
+use super::batch;
+use super::common::*;
+use super::context;
+use super::project;
+use super::sort;
+use super::todo;
+use super::user;
+
+/// project of the imported items that do not name one
+const DEFAULT_PROJECT: &str = "Inbox";
+
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid Import Format: '{}'", _0)]
+struct InvalidFormat(String);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    TodoTxt,
+    Taskwarrior,
+    Csv,
+}
+impl Format {
+    fn from_event(event: &crowbar::Value) -> Result<Format, InvalidFormat> {
+        match event["queryStringParameters"]["format"].as_str() {
+            Some("todotxt") => Ok(Format::TodoTxt),
+            Some("taskwarrior") => Ok(Format::Taskwarrior),
+            Some("csv") => Ok(Format::Csv),
+            Some(format) => Err(InvalidFormat(format.to_string())),
+            None => Err(InvalidFormat("".to_string())),
+        }
+    }
+}
+
+/// Item read from an import, before it is attached to the projects and contexts of the user.
+#[derive(Debug, Default, PartialEq)]
+struct ImportedItem {
+    title: String,
+    description: String,
+    project: Option<String>,
+    contexts: Vec<String>,
+    due: Option<DateTime<Utc>>,
+    flagged: bool,
+    status: Option<String>,
+}
+
+/// Result of the parsing of each line, or each task for Taskwarrior, numbered from 1.
+type ParsedLines = Vec<(usize, Result<ImportedItem, String>)>;
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct LineError {
+    pub line: usize,
+    pub error: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ImportReport {
+    pub items: Vec<model::basic_item::BasicItem>,
+    /// projects and contexts named in the import that did not exist yet
+    pub projects: Vec<model::project::Project>,
+    pub contexts: Vec<model::context::Context>,
+    pub errors: Vec<LineError>,
+}
+
+fn non_empty_lines(input: &str) -> Vec<(usize, &str)> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|&(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| (index + 1, line))
+        .collect()
+}
+
+fn is_day(word: &str) -> bool {
+    NaiveDate::parse_from_str(word, "%Y-%m-%d").is_ok()
+}
+
+/// Parses a todo.txt line, like `x (A) 2026-10-01 Call Bob +Work @phone due:2026-10-20`. A
+/// priority of `(A)` flags the item, and completion and creation dates are ignored.
+fn parse_todo_txt_line(line: &str, tz: &chrono_tz::Tz) -> Result<ImportedItem, String> {
+    let mut item = ImportedItem::default();
+    let mut words = line.split_whitespace().peekable();
+    if words.peek() == Some(&"x") {
+        item.status = Some(model::DONE_STATE.to_string());
+        words.next();
+    }
+    if let Some(&priority) = words.peek() {
+        if priority.len() == 3 && priority.starts_with('(') && priority.ends_with(')') {
+            item.flagged = priority == "(A)";
+            words.next();
+        }
+    }
+    while words.peek().map(|word| is_day(word)) == Some(true) {
+        words.next();
+    }
+
+    let mut title = vec![];
+    for word in words {
+        if word.len() > 1 && word.starts_with('+') {
+            if item.project.is_none() {
+                item.project = Some(word[1..].to_string());
+            }
+        } else if word.len() > 1 && word.starts_with('@') {
+            item.contexts.push(word.to_string());
+        } else if word.starts_with("due:") {
+            let due = &word[4..];
+            item.due = Some(parse_date(due, tz).ok_or_else(
+                || format!("invalid due date '{}'", due),
+            )?);
+        } else {
+            title.push(word);
+        }
+    }
+    item.title = title.join(" ");
+    if item.title.is_empty() {
+        return Err("missing title".to_string());
+    }
+    Ok(item)
+}
+
+fn parse_todo_txt(input: &str, tz: &chrono_tz::Tz) -> ParsedLines {
+    non_empty_lines(input)
+        .into_iter()
+        .map(|(line, text)| (line, parse_todo_txt_line(text, tz)))
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct TaskwarriorAnnotation {
+    description: String,
+}
+
+#[derive(Deserialize)]
+struct TaskwarriorTask {
+    description: Option<String>,
+    project: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    due: Option<String>,
+    status: Option<String>,
+    priority: Option<String>,
+    #[serde(default)]
+    annotations: Vec<TaskwarriorAnnotation>,
+}
+impl TaskwarriorTask {
+    /// Deleted tasks are not imported.
+    fn to_imported_item(&self) -> Result<Option<ImportedItem>, String> {
+        let status = match self.status.as_ref().map(|status| status.as_str()) {
+            Some("deleted") => return Ok(None),
+            Some("completed") => Some(model::DONE_STATE.to_string()),
+            _ => None,
+        };
+        let title = match self.description {
+            Some(ref description) if !description.trim().is_empty() => description.clone(),
+            _ => return Err("missing description".to_string()),
+        };
+        let due = match self.due {
+            Some(ref due) => {
+                Some(Utc.datetime_from_str(due, "%Y%m%dT%H%M%SZ").map_err(|_| {
+                    format!("invalid due date '{}'", due)
+                })?)
+            }
+            None => None,
+        };
+        Ok(Some(ImportedItem {
+            title: title,
+            description: self.annotations
+                .iter()
+                .map(|annotation| annotation.description.clone())
+                .collect::<Vec<String>>()
+                .join("\n"),
+            project: self.project.clone(),
+            contexts: self.tags.iter().map(|tag| format!("@{}", tag)).collect(),
+            due: due,
+            flagged: self.priority.as_ref().map(|priority| priority.as_str()) == Some("H"),
+            status: status,
+        }))
+    }
+}
+
+/// Parses the output of `task export`, either a JSON array, or one JSON task per line as in older
+/// versions.
+fn parse_taskwarrior(input: &str) -> ParsedLines {
+    let values: Vec<(usize, Result<serde_json::Value, String>)> =
+        if input.trim().starts_with('[') {
+            match serde_json::from_str::<Vec<serde_json::Value>>(input) {
+                Ok(values) => {
+                    values
+                        .into_iter()
+                        .enumerate()
+                        .map(|(index, value)| (index + 1, Ok(value)))
+                        .collect()
+                }
+                Err(error) => return vec![(error.line(), Err(error.to_string()))],
+            }
+        } else {
+            non_empty_lines(input)
+                .into_iter()
+                .map(|(line, text)| {
+                    (line, serde_json::from_str(text).map_err(|error| error.to_string()))
+                })
+                .collect()
+        };
+
+    values
+        .into_iter()
+        .filter_map(|(line, value)| {
+            match value.and_then(|value| {
+                serde_json::from_value::<TaskwarriorTask>(value)
+                    .map_err(|error| error.to_string())
+                    .and_then(|task| task.to_imported_item())
+            }) {
+                Ok(Some(item)) => Some((line, Ok(item))),
+                Ok(None) => None,
+                Err(error) => Some((line, Err(error))),
+            }
+        })
+        .collect()
+}
+
+/// Splits a CSV line in fields, fields can be quoted with `"` and a quote in a quoted field is
+/// doubled. Fields spanning several lines are not supported.
+fn split_csv_line(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            ',' if !quoted => fields.push(::std::mem::replace(&mut field, String::new())),
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("unterminated quoted field".to_string());
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+/// Splits a CSV file in records, with the number of their first line. Quoted fields can span
+/// several lines, empty lines between records are skipped.
+fn csv_records(input: &str) -> Vec<(usize, Result<Vec<String>, String>)> {
+    let mut records = vec![];
+    let mut pending: Option<(usize, String)> = None;
+    for (index, line) in input.lines().enumerate() {
+        let (start, text) = match pending.take() {
+            Some((start, text)) => (start, format!("{}\n{}", text, line)),
+            None if line.trim().is_empty() => continue,
+            None => (index + 1, line.to_string()),
+        };
+        match split_csv_line(&text) {
+            // the quoted field continues on the next line
+            Err(_) => pending = Some((start, text)),
+            Ok(fields) => records.push((start, Ok(fields))),
+        }
+    }
+    if let Some((start, text)) = pending {
+        records.push((start, split_csv_line(&text)));
+    }
+    records
+}
+
+fn csv_item(
+    columns: &[String],
+    fields: &[String],
+    tz: &chrono_tz::Tz,
+) -> Result<ImportedItem, String> {
+    let mut item = ImportedItem::default();
+    for (column, value) in columns.iter().zip(fields) {
+        let value = value.trim();
+        match column.as_str() {
+            "title" => item.title = value.to_string(),
+            "description" => item.description = value.to_string(),
+            "project" if !value.is_empty() => item.project = Some(value.to_string()),
+            "contexts" => {
+                item.contexts = value
+                    .split(|c| c == ';' || c == ' ')
+                    .filter(|name| !name.is_empty())
+                    .map(|name| name.to_string())
+                    .collect()
+            }
+            "due" if !value.is_empty() => {
+                item.due = Some(parse_date(value, tz).ok_or_else(
+                    || format!("invalid due date '{}'", value),
+                )?)
+            }
+            "flagged" => {
+                item.flagged = ["true", "yes", "1"].contains(&value.to_lowercase().as_str())
+            }
+            "status" if !value.is_empty() => item.status = Some(value.to_string()),
+            _ => (),
+        }
+    }
+    if item.title.is_empty() {
+        return Err("missing title".to_string());
+    }
+    Ok(item)
+}
+
+/// Parses a CSV file with a header line naming its columns, among `title`, `description`,
+/// `project`, `contexts` (separated by spaces or `;`), `due`, `flagged` and `status`. Other
+/// columns are ignored.
+fn parse_csv(input: &str, tz: &chrono_tz::Tz) -> ParsedLines {
+    let mut records = csv_records(input).into_iter();
+    let columns: Vec<String> = match records.next() {
+        Some((_, Ok(columns))) => {
+            columns
+                .iter()
+                .map(|column| column.trim().to_lowercase())
+                .collect()
+        }
+        Some((line, Err(error))) => return vec![(line, Err(error))],
+        None => return vec![],
+    };
+    if !columns.iter().any(|column| column == "title") {
+        return vec![(1, Err("missing title column".to_string()))];
+    }
+
+    records
+        .map(|(line, fields)| {
+            (
+                line,
+                fields.and_then(|fields| csv_item(&columns, &fields, tz)),
+            )
+        })
+        .collect()
+}
+
+/// New projects for the names used in the import that do not match an existing project, names
+/// being compared ignoring case.
+fn missing_projects(
+    user_id: &model::UserId,
+    existing: &[model::project::Project],
+    names: &[String],
+) -> Vec<model::project::Project> {
+    let mut created: Vec<model::project::Project> = vec![];
+    for name in names {
+        if !existing.iter().chain(created.iter()).any(|project| {
+            project.name.to_lowercase() == name.to_lowercase()
+        })
+        {
+            created.push(model::project::Project {
+                uid: user_id.clone(),
+                id: model::ProjectId(format!("{}", uuid::Uuid::new_v4().hyphenated())),
+                name: name.to_string(),
+                costs_info: Default::default(),
+            });
+        }
+    }
+    created
+}
+
+/// New contexts for the names used in the import that do not match an existing context.
+fn missing_contexts(
+    user_id: &model::UserId,
+    existing: &[model::context::Context],
+    names: &[String],
+) -> Vec<model::context::Context> {
+    let mut created: Vec<model::context::Context> = vec![];
+    for name in names.iter().map(|name| context::normalize_name(name)) {
+        if !existing.iter().chain(created.iter()).any(|context| {
+            context.name.to_lowercase() == name.to_lowercase()
+        })
+        {
+            created.push(model::context::Context {
+                uid: user_id.clone(),
+                id: model::ContextId(format!("{}", uuid::Uuid::new_v4().hyphenated())),
+                name: name,
+            });
+        }
+    }
+    created
+}
+
+/// Imports items from the body, in the format given by the `format` query string parameter:
+/// `todotxt`, `taskwarrior` or `csv`. Lines that can not be imported are reported with their
+/// error, the others are imported.
+pub fn import(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<ImportReport, SerializableError>> {
+    let format = match Format::from_event(event) {
+        Ok(format) => format,
+        Err(error) => return bad_request(error.into()),
+    };
+    let body = match event["body"].as_str() {
+        Some(body) => body,
+        None => return bad_request(MissingBody().into()),
+    };
+    let client = dynamodb_client();
+    let actor = authorized_actor(event);
+    let user_id = actor.user_id.clone();
+    let tz = user::current_user(&client, &user_id).timezone();
+
+    let parsed = match format {
+        Format::TodoTxt => parse_todo_txt(body, &tz),
+        Format::Taskwarrior => parse_taskwarrior(body),
+        Format::Csv => parse_csv(body, &tz),
+    };
+    let mut imported = vec![];
+    let mut imported_lines = vec![];
+    let mut errors = vec![];
+    for (line, result) in parsed {
+        match result {
+            Ok(item) => {
+                imported.push(item);
+                imported_lines.push(line);
+            }
+            Err(error) => {
+                errors.push(LineError {
+                    line: line,
+                    error: error,
+                })
+            }
+        }
+    }
+
+    let projects_table = env::var("projects_table").unwrap();
+    let mut projects = project::user_projects(&client, &projects_table, &user_id);
+    let project_names: Vec<String> = imported
+        .iter()
+        .map(|item| {
+            item.project.clone().unwrap_or_else(|| DEFAULT_PROJECT.to_string())
+        })
+        .collect();
+    let new_projects = missing_projects(&user_id, &projects, &project_names);
+    for new_project in &new_projects {
+        project::save_project(&client, &projects_table, new_project);
+    }
+    projects.extend(new_projects.iter().cloned());
+
+    let contexts_table = env::var("contexts_table").unwrap();
+    let mut contexts = context::user_contexts(&client, &contexts_table, &user_id);
+    let context_names: Vec<String> = imported
+        .iter()
+        .flat_map(|item| item.contexts.iter().cloned())
+        .collect();
+    let new_contexts = missing_contexts(&user_id, &contexts, &context_names);
+    for new_context in &new_contexts {
+        context::save_context(&client, &contexts_table, new_context);
+    }
+    contexts.extend(new_contexts.iter().cloned());
+
+    let table = env::var("table").unwrap();
+    let existing_items = todo::user_items(&client, &table, &user_id);
+    let now = Utc::now();
+    let mut items: Vec<model::basic_item::BasicItem> = vec![];
+    for (imported, project_name) in imported.into_iter().zip(project_names) {
+        let project_id = projects
+            .iter()
+            .find(|project| project.name.to_lowercase() == project_name.to_lowercase())
+            .unwrap()
+            .id
+            .clone();
+        let mut context_ids = vec![];
+        for name in &imported.contexts {
+            let name = context::normalize_name(name).to_lowercase();
+            let context_id = contexts
+                .iter()
+                .find(|context| context.name.to_lowercase() == name)
+                .unwrap()
+                .id
+                .clone();
+            if !context_ids.contains(&context_id) {
+                context_ids.push(context_id);
+            }
+        }
+        let position = ::std::cmp::max(
+            sort::next_position(&existing_items, &project_id),
+            sort::next_position(&items, &project_id),
+        );
+        let mut item = model::basic_item::BasicItem {
+            uid: user_id.clone(),
+            id: model::ItemId(format!("{}", uuid::Uuid::new_v4().hyphenated())),
+            title: imported.title,
+            description: imported.description,
+            status: model::State { name: imported.status.unwrap_or_else(|| "".to_string()) },
+            flagged: imported.flagged,
+            project_id: project_id,
+            parent_id: None,
+            contexts: context_ids,
+            costs: vec![],
+            due: imported.due,
+            position: position,
+            created_at: None,
+            created_by: None,
+            updated_at: None,
+            updated_by: None,
+            completed_at: None,
+            version: 0,
+            deleted_at: None,
+            assignee: None,
+            attachments: vec![],
+            reminders: vec![],
+        };
+        item.created(&actor, now);
+        items.push(item);
+    }
+    let unwritten = batch::write_items(&client, &table, &items);
+    for (item, line) in items.iter().zip(imported_lines) {
+        if unwritten.contains(&item.id) {
+            errors.push(LineError {
+                line: line,
+                error: "could not be saved".to_string(),
+            });
+        }
+    }
+    errors.sort_by_key(|error| error.line);
+    items.retain(|item| !unwritten.contains(&item.id));
+
+    ok(ImportReport {
+        items: items,
+        projects: new_projects,
+        contexts: new_contexts,
+        errors: errors,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_todo_txt_line() {
+        let item = parse_todo_txt_line(
+            "x (A) 2026-10-02 2026-10-01 Call Bob +Work @phone due:2026-10-20",
+            &chrono_tz::Tz::UTC,
+        );
+
+        assert_eq!(
+            item,
+            Ok(ImportedItem {
+                title: "Call Bob".to_string(),
+                description: "".to_string(),
+                project: Some("Work".to_string()),
+                contexts: vec!["@phone".to_string()],
+                due: Some(Utc.ymd(2026, 10, 20).and_hms(0, 0, 0)),
+                flagged: true,
+                status: Some(model::DONE_STATE.to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn should_report_todo_txt_errors_with_line_numbers() {
+        let parsed = parse_todo_txt("Buy milk\n\n+Home @errands\nPay rent due:soon", &chrono_tz::Tz::UTC);
+
+        let errors: Vec<(usize, String)> = parsed
+            .into_iter()
+            .filter_map(|(line, result)| result.err().map(|error| (line, error)))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (3, "missing title".to_string()),
+                (4, "invalid due date 'soon'".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn can_parse_taskwarrior_export() {
+        let parsed = parse_taskwarrior(
+            r#"[
+                {"description": "Write report", "project": "Work", "tags": ["office"],
+                 "due": "20261020T120000Z", "priority": "H", "status": "pending",
+                 "annotations": [{"entry": "20261001T000000Z", "description": "Q3 numbers"}]},
+                {"description": "Old task", "status": "deleted"},
+                {"project": "Home", "status": "pending"}
+            ]"#,
+        );
+
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(
+            parsed[0].1,
+            Ok(ImportedItem {
+                title: "Write report".to_string(),
+                description: "Q3 numbers".to_string(),
+                project: Some("Work".to_string()),
+                contexts: vec!["@office".to_string()],
+                due: Some(Utc.ymd(2026, 10, 20).and_hms(12, 0, 0)),
+                flagged: true,
+                status: None,
+            })
+        );
+        assert_eq!(parsed[1], (3, Err("missing description".to_string())));
+    }
+
+    #[test]
+    fn can_split_quoted_csv_fields() {
+        assert_eq!(
+            split_csv_line(r#"Buy milk,"2 bottles, ""organic""",Home"#),
+            Ok(vec![
+                "Buy milk".to_string(),
+                "2 bottles, \"organic\"".to_string(),
+                "Home".to_string(),
+            ])
+        );
+        assert!(split_csv_line(r#"Buy milk,"2 bottles"#).is_err());
+    }
+
+    #[test]
+    fn can_parse_csv_with_header() {
+        let parsed = parse_csv(
+            "Title,Project,Contexts,Flagged,Notes\nCall Bob,Work,@phone;@office,yes,ignored\n,Work,,,",
+            &chrono_tz::Tz::UTC,
+        );
+
+        assert_eq!(
+            parsed[0],
+            (
+                2,
+                Ok(ImportedItem {
+                    title: "Call Bob".to_string(),
+                    description: "".to_string(),
+                    project: Some("Work".to_string()),
+                    contexts: vec!["@phone".to_string(), "@office".to_string()],
+                    due: None,
+                    flagged: true,
+                    status: None,
+                }),
+            )
+        );
+        assert_eq!(parsed[1], (3, Err("missing title".to_string())));
+    }
+
+    #[test]
+    fn can_parse_csv_with_multi_line_fields() {
+        let parsed = parse_csv(
+            "title,description\r\nCall Bob,\"first line\r\n\r\nsecond line\"\r\nPay rent,\r\n\"Unterminated",
+            &chrono_tz::Tz::UTC,
+        );
+
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[0].0, 2);
+        assert_eq!(
+            parsed[0].1.as_ref().map(|item| item.description.as_str()),
+            Ok("first line\n\nsecond line")
+        );
+        assert_eq!(parsed[1].0, 5);
+        assert_eq!(
+            parsed[2],
+            (6, Err("unterminated quoted field".to_string()))
+        );
+    }
+
+    #[test]
+    fn should_only_create_unknown_projects_once() {
+        let user_id = model::UserId("u1".to_string());
+        let existing = vec![
+            model::project::Project {
+                uid: user_id.clone(),
+                id: model::ProjectId("p1".to_string()),
+                name: "Work".to_string(),
+                costs_info: Default::default(),
+            },
+        ];
+
+        let created = missing_projects(&user_id, &existing, &[
+                "work".to_string(),
+                "Home".to_string(),
+                "home".to_string(),
+            ]);
+
+        assert_eq!(created.len(), 1);
+        assert_eq!(created[0].name, "Home");
+    }
+}
@@ -0,0 +1,497 @@
+//! Sharing of projects with other users. The items of a shared project stay stored with its
+//! owner, and are visible to the members of the project. Editors can change them as the owner
+//! does, viewers can only read them.
+
+use std::env;
+use std::collections::HashMap;
+
+use crowbar;
+use chrono;
+use serde_dynamodb;
+use serde_dynamodb::ToQueryInput;
+
+use rusoto_dynamodb::{AttributeValue, DeleteItemInput, DynamoDb, PutItemInput, QueryInput};
+
+use model;
+use model::member::Role;
+
+use super::common::*;
+use super::project;
+use super::sync;
+use super::todo;
+
+/// global secondary index of the members table, on the project id
+const PROJECT_INDEX: &str = "project_index";
+
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid Role: '{}'", _0)]
+struct InvalidRole(String);
+#[derive(Debug, Fail)]
+#[fail(display = "The owner of a project can not be one of its members")]
+struct OwnerAsMember();
+
+#[derive(Serialize, Debug)]
+pub struct MemberList {
+    pub owner: model::UserId,
+    pub members: Vec<model::member::Member>,
+}
+
+#[derive(Deserialize)]
+struct MemberInput {
+    user_id: Option<String>,
+    role: Option<String>,
+}
+
+/// Why an item can not be accessed.
+#[derive(Debug, PartialEq)]
+pub enum Denied {
+    /// the item does not exist, or is not shared with the user
+    NotFound,
+    /// the item is shared with the user, without the rights needed
+    Forbidden,
+}
+impl Denied {
+    pub fn response<T>(
+        &self,
+        object: &'static str,
+        id: String,
+    ) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<T, SerializableError>> {
+        match *self {
+            Denied::NotFound => not_found(object, id),
+            Denied::Forbidden => forbidden(object, id),
+        }
+    }
+}
+
+/// Projects of other users shared with the user.
+pub fn memberships<D: DynamoDb>(
+    client: &D,
+    user_id: &model::UserId,
+) -> Vec<model::member::Member> {
+    let uid_filter = model::member::MemberQueryInput {
+        uid: Some(user_id.clone()),
+        ..Default::default()
+    };
+    client
+        .query(&uid_filter.to_query_input(
+            env::var("members_table").unwrap(),
+        ))
+        .unwrap()
+        .items
+        .unwrap_or_else(|| vec![])
+        .into_iter()
+        .map(|member| serde_dynamodb::from_hashmap(member).unwrap())
+        .collect()
+}
+
+pub fn find_membership<D: DynamoDb>(
+    client: &D,
+    user_id: &model::UserId,
+    project_id: &str,
+) -> Option<model::member::Member> {
+    let member_filter = model::member::MemberQueryInput {
+        uid: Some(user_id.clone()),
+        id: Some(project_id.to_string().into()),
+        ..Default::default()
+    };
+    client
+        .query(&member_filter.to_query_input(
+            env::var("members_table").unwrap(),
+        ))
+        .unwrap()
+        .items
+        .unwrap_or_else(|| vec![])
+        .pop()
+        .map(|member| serde_dynamodb::from_hashmap(member).unwrap())
+}
+
+pub fn project_members<D: DynamoDb>(
+    client: &D,
+    project_id: &model::ProjectId,
+) -> Vec<model::member::Member> {
+    let mut names = HashMap::new();
+    names.insert("#id".to_string(), "id".to_string());
+    let mut values = HashMap::new();
+    values.insert(
+        ":id".to_string(),
+        AttributeValue {
+            s: Some(project_id.to_string()),
+            ..Default::default()
+        },
+    );
+    let query_input = QueryInput {
+        table_name: env::var("members_table").unwrap(),
+        index_name: Some(PROJECT_INDEX.to_string()),
+        key_condition_expression: Some("#id = :id".to_string()),
+        expression_attribute_names: Some(names),
+        expression_attribute_values: Some(values),
+        ..Default::default()
+    };
+    client
+        .query(&query_input)
+        .unwrap()
+        .items
+        .unwrap_or_else(|| vec![])
+        .into_iter()
+        .map(|member| serde_dynamodb::from_hashmap(member).unwrap())
+        .collect()
+}
+
+/// Owners of the projects of memberships, each listed once.
+pub fn owners(memberships: &[model::member::Member]) -> Vec<&model::UserId> {
+    let mut owners: Vec<&model::UserId> = memberships.iter().map(|member| &member.owner).collect();
+    owners.sort_by(|a, b| a.0.cmp(&b.0));
+    owners.dedup();
+    owners
+}
+
+/// Role of a user on an item, `None` if the item is not visible to the user.
+pub fn item_role(
+    user_id: &model::UserId,
+    memberships: &[model::member::Member],
+    item: &model::basic_item::BasicItem,
+) -> Option<Role> {
+    if &item.uid == user_id {
+        return Some(Role::Owner);
+    }
+    memberships
+        .iter()
+        .find(|member| {
+            member.owner == item.uid && member.id == item.project_id
+        })
+        .map(|member| member.role)
+}
+
+/// Items of `items` that the user can read, or edit if `edit` is set.
+pub fn items_with_access(
+    user_id: &model::UserId,
+    memberships: &[model::member::Member],
+    items: Vec<model::basic_item::BasicItem>,
+    edit: bool,
+) -> Vec<model::basic_item::BasicItem> {
+    items
+        .into_iter()
+        .filter(|item| match item_role(user_id, memberships, item) {
+            Some(role) => !edit || role.can_edit(),
+            None => false,
+        })
+        .collect()
+}
+
+/// Restricts items of an owner to those the user can read, or edit if `edit` is set, so that
+/// the items of projects not shared with the user are not reached through the hierarchy.
+pub fn accessible_items<D: DynamoDb>(
+    client: &D,
+    user_id: &model::UserId,
+    items: Vec<model::basic_item::BasicItem>,
+    edit: bool,
+) -> Vec<model::basic_item::BasicItem> {
+    if items.iter().all(|item| &item.uid == user_id) {
+        return items;
+    }
+    items_with_access(user_id, &memberships(client, user_id), items, edit)
+}
+
+/// Role of a user on the items of a project owned by `owner`, `None` if the project is not
+/// shared with the user.
+pub fn role<D: DynamoDb>(
+    client: &D,
+    user_id: &model::UserId,
+    owner: &model::UserId,
+    project_id: &model::ProjectId,
+) -> Option<Role> {
+    if user_id == owner {
+        return Some(Role::Owner);
+    }
+    find_membership(client, user_id, &project_id.to_string()).and_then(|member| {
+        if &member.owner == owner {
+            Some(member.role)
+        } else {
+            None
+        }
+    })
+}
+
+/// Owner with whom an item added by the user to a project is stored: the owner of the project if
+/// it is shared with the user, the user otherwise.
+pub fn project_owner<D: DynamoDb>(
+    client: &D,
+    user_id: &model::UserId,
+    project_id: Option<&str>,
+) -> Result<model::UserId, Denied> {
+    match project_id.and_then(|project_id| find_membership(client, user_id, project_id)) {
+        Some(ref member) if !member.role.can_edit() => Err(Denied::Forbidden),
+        Some(member) => Ok(member.owner),
+        None => Ok(user_id.clone()),
+    }
+}
+
+/// Finds an item of the user or of a project shared with the user, with the role of the user
+/// on it.
+pub fn find_item<D: DynamoDb>(
+    client: &D,
+    table: &str,
+    user_id: &model::UserId,
+    todo_id: &str,
+) -> Option<(model::basic_item::BasicItem, Role)> {
+    if let Some(item) = todo::find_item(client, table, user_id, todo_id) {
+        return Some((item, Role::Owner));
+    }
+    let memberships = memberships(client, user_id);
+    owners(&memberships)
+        .into_iter()
+        .filter_map(|owner| todo::find_item(client, table, owner, todo_id))
+        .filter_map(|item| {
+            item_role(user_id, &memberships, &item).map(|role| (item, role))
+        })
+        .next()
+}
+
+/// Owner of an item not in the trash that the user can read, or edit if `edit` is set.
+pub fn item_owner<D: DynamoDb>(
+    client: &D,
+    table: &str,
+    user_id: &model::UserId,
+    todo_id: &str,
+    edit: bool,
+) -> Result<model::UserId, Denied> {
+    match find_item(client, table, user_id, todo_id) {
+        Some((ref item, _)) if item.deleted_at.is_some() => Err(Denied::NotFound),
+        Some((_, role)) if edit && !role.can_edit() => Err(Denied::Forbidden),
+        Some((item, _)) => Ok(item.uid),
+        None => Err(Denied::NotFound),
+    }
+}
+
+/// Items of the projects shared with the user, including those in the trash.
+pub fn shared_items<D: DynamoDb>(
+    client: &D,
+    table: &str,
+    user_id: &model::UserId,
+) -> Vec<model::basic_item::BasicItem> {
+    let memberships = memberships(client, user_id);
+    owners(&memberships)
+        .into_iter()
+        .flat_map(|owner| todo::all_user_items(client, table, owner))
+        .filter(|item| item_role(user_id, &memberships, item).is_some())
+        .collect()
+}
+
+/// Projects of other users shared with the user.
+pub fn shared_projects<D: DynamoDb>(
+    client: &D,
+    user_id: &model::UserId,
+) -> Vec<model::project::Project> {
+    let table = env::var("projects_table").unwrap();
+    memberships(client, user_id)
+        .into_iter()
+        .filter_map(|member| {
+            project::find_project(client, &table, &member.owner, &member.id.to_string())
+        })
+        .collect()
+}
+
+/// Finds a project of the user or shared with the user, with the role of the user on it.
+pub fn find_project<D: DynamoDb>(
+    client: &D,
+    user_id: &model::UserId,
+    project_id: &str,
+) -> Option<(model::project::Project, Role)> {
+    let table = env::var("projects_table").unwrap();
+    if let Some(project) = project::find_project(client, &table, user_id, project_id) {
+        return Some((project, Role::Owner));
+    }
+    find_membership(client, user_id, project_id).and_then(|member| {
+        project::find_project(client, &table, &member.owner, project_id)
+            .map(|project| (project, member.role))
+    })
+}
+
+/// Lists the members of a project, for its owner and members.
+pub fn list(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<MemberList, SerializableError>> {
+    let project_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let client = dynamodb_client();
+    match find_project(&client, &authorized_user_id(event), &project_id) {
+        Some((project, _)) => {
+            ok(MemberList {
+                members: project_members(&client, &project.id),
+                owner: project.uid,
+            })
+        }
+        None => not_found("project", project_id),
+    }
+}
+
+/// Adds a member to a project, or changes the role of a member. Only the owner of the project
+/// can manage its members.
+pub fn add(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::member::Member,
+        SerializableError,
+    >,
+> {
+    let project_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let client = dynamodb_client();
+    let user_id = authorized_user_id(event);
+    let project = match find_project(&client, &user_id, &project_id) {
+        Some((project, Role::Owner)) => project,
+        Some(_) => return forbidden("project", project_id),
+        None => return not_found("project", project_id),
+    };
+
+    let data_result = parse_body::<MemberInput>(event).and_then(|input| {
+        let member_id: model::UserId = input
+            .user_id
+            .ok_or_else(|| MissingField("user_id"))?
+            .into();
+        if member_id == project.uid {
+            return Err(OwnerAsMember().into());
+        }
+        let role = match input.role.as_ref().map(|role| role.as_str()) {
+            None | Some("viewer") => Role::Viewer,
+            Some("editor") => Role::Editor,
+            Some(role) => return Err(InvalidRole(role.to_string()).into()),
+        };
+        Ok(model::member::Member {
+            uid: member_id,
+            id: project.id.clone(),
+            owner: project.uid.clone(),
+            role: role,
+            added_at: Some(chrono::Utc::now()),
+        })
+    });
+
+    match data_result {
+        Ok(member) => {
+            let put_item = PutItemInput {
+                item: serde_dynamodb::to_hashmap(&member).unwrap(),
+                table_name: env::var("members_table").unwrap(),
+                ..Default::default()
+            };
+            client.put_item(&put_item).unwrap();
+            ok(member)
+        }
+        Err(error) => bad_request(error),
+    }
+}
+
+/// Removes a member from a project. Members can leave a project, other members can only be
+/// removed by the owner. The items of the project are reported as deleted to the next
+/// synchronisations of the former member.
+pub fn remove(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::member::Member,
+        SerializableError,
+    >,
+> {
+    let project_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let member_id: model::UserId = event["pathParameters"]["user_id"]
+        .as_str()
+        .unwrap()
+        .to_string()
+        .into();
+    let client = dynamodb_client();
+    let user_id = authorized_user_id(event);
+    match find_project(&client, &user_id, &project_id) {
+        Some((_, Role::Owner)) => (),
+        Some(_) if member_id == user_id => (),
+        Some(_) => return forbidden("project", project_id),
+        None => return not_found("project", project_id),
+    }
+    let member = match find_membership(&client, &member_id, &project_id) {
+        Some(member) => member,
+        None => return not_found("member", member_id.to_string()),
+    };
+
+    let delete_item = DeleteItemInput {
+        key: user_key(&member_id, &project_id),
+        table_name: env::var("members_table").unwrap(),
+        ..Default::default()
+    };
+    client.delete_item(&delete_item).unwrap();
+    let items: Vec<model::basic_item::BasicItem> =
+        todo::all_user_items(&client, &env::var("table").unwrap(), &member.owner)
+            .into_iter()
+            .filter(|item| item.project_id == member.id)
+            .collect();
+    sync::record_revocation(&client, &member.uid, &items, chrono::Utc::now());
+    ok(member)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(uid: &str, project_id: &str) -> model::basic_item::BasicItem {
+        model::basic_item::BasicItem {
+            uid: model::UserId(uid.to_string()),
+            project_id: model::ProjectId(project_id.to_string()),
+            ..model::basic_item::test_item()
+        }
+    }
+
+    fn memberships() -> Vec<model::member::Member> {
+        vec![
+            model::member::Member {
+                uid: model::UserId("u1".to_string()),
+                id: model::ProjectId("p2".to_string()),
+                owner: model::UserId("u2".to_string()),
+                role: Role::Viewer,
+                added_at: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn should_only_give_access_to_items_of_shared_projects() {
+        let user_id = model::UserId("u1".to_string());
+        let items = vec![item("u2", "p2"), item("u2", "p3")];
+
+        let readable: Vec<model::ProjectId> =
+            items_with_access(&user_id, &memberships(), items.clone(), false)
+                .into_iter()
+                .map(|item| item.project_id)
+                .collect();
+
+        assert_eq!(readable, vec![model::ProjectId("p2".to_string())]);
+        assert!(items_with_access(&user_id, &memberships(), items, true).is_empty());
+    }
+
+    #[test]
+    fn should_own_own_items() {
+        let user_id = model::UserId("u1".to_string());
+
+        assert_eq!(
+            item_role(&user_id, &memberships(), &item("u1", "p1")),
+            Some(Role::Owner)
+        );
+    }
+
+    #[test]
+    fn can_access_items_of_shared_projects_only() {
+        let user_id = model::UserId("u1".to_string());
+
+        assert_eq!(
+            item_role(&user_id, &memberships(), &item("u2", "p2")),
+            Some(Role::Viewer)
+        );
+        assert_eq!(item_role(&user_id, &memberships(), &item("u2", "p3")), None);
+        assert_eq!(item_role(&user_id, &memberships(), &item("u3", "p2")), None);
+    }
+
+    #[test]
+    fn should_not_let_viewers_edit() {
+        assert!(Role::Owner.can_edit());
+        assert!(Role::Editor.can_edit());
+        assert!(!Role::Viewer.can_edit());
+    }
+}
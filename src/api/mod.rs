@@ -1,2 +1,24 @@
 pub mod todo;
 pub mod auth;
+pub mod context;
+pub mod project;
+pub mod user;
+pub mod search;
+pub mod batch;
+pub mod sync;
+pub mod trash;
+pub mod history;
+pub mod import;
+pub mod export;
+pub mod feed;
+pub mod webhook;
+pub mod member;
+pub mod comment;
+pub mod attachment;
+pub mod reminder;
+pub mod template;
+mod ical;
+mod query;
+mod quick;
+mod sort;
+mod common;
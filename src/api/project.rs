@@ -0,0 +1,360 @@
+use std::env;
+
+use crowbar;
+use uuid;
+use serde_dynamodb;
+use serde_dynamodb::ToQueryInput;
+
+use rusoto_dynamodb::{DynamoDb, PutItemInput};
+
+use model;
+
+use super::common::*;
+use super::member;
+use super::todo;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProjectList {
+    pub projects: Vec<model::project::Project>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct CategorySummary {
+    pub id: model::CostCategoryId,
+    pub name: String,
+    pub total: u64,
+}
+
+/// Estimated cost of the open items of a project.
+#[derive(Serialize, Debug)]
+pub struct ProjectSummary {
+    pub project_id: model::ProjectId,
+    pub unit: String,
+    pub open_items: usize,
+    pub categories: Vec<CategorySummary>,
+    pub total: u64,
+}
+
+pub fn user_projects<D: DynamoDb>(
+    client: &D,
+    table: &str,
+    user_id: &model::UserId,
+) -> Vec<model::project::Project> {
+    let uid_filter = model::project::ProjectQueryInput {
+        uid: Some(user_id.clone()),
+        ..Default::default()
+    };
+    client
+        .query(&uid_filter.to_query_input(table.to_string()))
+        .unwrap()
+        .items
+        .unwrap_or_else(|| vec![])
+        .into_iter()
+        .map(|project| serde_dynamodb::from_hashmap(project).unwrap())
+        .collect()
+}
+
+pub fn find_project<D: DynamoDb>(
+    client: &D,
+    table: &str,
+    user_id: &model::UserId,
+    project_id: &str,
+) -> Option<model::project::Project> {
+    let project_filter = model::project::ProjectQueryInput {
+        uid: Some(user_id.clone()),
+        id: Some(project_id.to_string().into()),
+        ..Default::default()
+    };
+    client
+        .query(&project_filter.to_query_input(table.to_string()))
+        .unwrap()
+        .items
+        .unwrap_or_else(|| vec![])
+        .pop()
+        .map(|project| serde_dynamodb::from_hashmap(project).unwrap())
+}
+
+pub fn save_project<D: DynamoDb>(client: &D, table: &str, project: &model::project::Project) {
+    let put_item = PutItemInput {
+        item: serde_dynamodb::to_hashmap(project).unwrap(),
+        table_name: table.to_string(),
+        ..Default::default()
+    };
+    client.put_item(&put_item).unwrap();
+}
+
+fn summarize(
+    project: &model::project::Project,
+    items: &[model::basic_item::BasicItem],
+) -> ProjectSummary {
+    let open_items: Vec<&model::basic_item::BasicItem> = items
+        .iter()
+        .filter(|item| item.project_id == project.id && !item.status.is_done())
+        .collect();
+    let categories: Vec<CategorySummary> = project
+        .costs_info
+        .categories
+        .iter()
+        .map(|category| {
+            CategorySummary {
+                id: category.id.clone(),
+                name: category.name.clone(),
+                total: open_items
+                    .iter()
+                    .flat_map(|item| item.costs.iter())
+                    .filter(|cost| cost.category == category.id)
+                    .map(|cost| u64::from(cost.cost))
+                    .sum(),
+            }
+        })
+        .collect();
+    ProjectSummary {
+        project_id: project.id.clone(),
+        unit: project.costs_info.unit.clone(),
+        open_items: open_items.len(),
+        total: categories.iter().map(|category| category.total).sum(),
+        categories: categories,
+    }
+}
+
+#[derive(Deserialize)]
+struct CostInfoInput {
+    unit: Option<String>,
+    categories: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct ProjectInput {
+    name: Option<String>,
+    costs_info: Option<CostInfoInput>,
+}
+impl ProjectInput {
+    /// Cost categories are given by name, a category keeps its id as long as its name is
+    /// not changed.
+    fn costs_info(&self, existing: &model::CostInfo) -> model::CostInfo {
+        match self.costs_info {
+            None => existing.clone(),
+            Some(ref input) => {
+                model::CostInfo {
+                    unit: input.unit.clone().unwrap_or_else(|| existing.unit.clone()),
+                    categories: match input.categories {
+                        None => existing.categories.clone(),
+                        Some(ref names) => {
+                            names
+                                .iter()
+                                .map(|name| {
+                                    existing
+                                        .categories
+                                        .iter()
+                                        .find(|category| &category.name == name)
+                                        .cloned()
+                                        .unwrap_or_else(|| {
+                                            model::CostCategory {
+                                                id: model::CostCategoryId(format!(
+                                                    "{}",
+                                                    uuid::Uuid::new_v4().hyphenated()
+                                                )),
+                                                name: name.clone(),
+                                            }
+                                        })
+                                })
+                                .collect()
+                        }
+                    },
+                }
+            }
+        }
+    }
+}
+
+/// Lists the projects of the user, followed by the projects shared with the user.
+pub fn list(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<ProjectList, SerializableError>> {
+    let table = env::var("projects_table").unwrap();
+    let client = dynamodb_client();
+    let user_id = authorized_user_id(event);
+    let mut projects = user_projects(&client, &table, &user_id);
+    projects.extend(member::shared_projects(&client, &user_id));
+    ok(ProjectList { projects: projects })
+}
+
+pub fn add(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::project::Project,
+        SerializableError,
+    >,
+> {
+    let table = env::var("projects_table").unwrap();
+    let client = dynamodb_client();
+    let data_result = parse_body::<ProjectInput>(event).and_then(|input| {
+        let name = input.name.clone().ok_or_else(|| MissingField("name"))?;
+        Ok(model::project::Project {
+            uid: authorized_user_id(event),
+            id: model::ProjectId(format!("{}", uuid::Uuid::new_v4().hyphenated())),
+            name: name,
+            costs_info: input.costs_info(&Default::default()),
+        })
+    });
+
+    match data_result {
+        Ok(project) => {
+            save_project(&client, &table, &project);
+            ok(project)
+        }
+        Err(error) => bad_request(error),
+    }
+}
+
+pub fn get(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::project::Project,
+        SerializableError,
+    >,
+> {
+    let project_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let client = dynamodb_client();
+    match member::find_project(&client, &authorized_user_id(event), &project_id) {
+        Some((project, _)) => ok(project),
+        None => not_found("project", project_id),
+    }
+}
+
+pub fn update(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::project::Project,
+        SerializableError,
+    >,
+> {
+    let project_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let table = env::var("projects_table").unwrap();
+    let client = dynamodb_client();
+    let existing = match member::find_project(&client, &authorized_user_id(event), &project_id) {
+        Some((_, ref role)) if !role.can_edit() => return forbidden("project", project_id),
+        Some((project, _)) => project,
+        None => return not_found("project", project_id),
+    };
+
+    match parse_body::<ProjectInput>(event) {
+        Ok(input) => {
+            let project = model::project::Project {
+                uid: existing.uid.clone(),
+                id: existing.id.clone(),
+                name: input.name.clone().unwrap_or_else(|| existing.name.clone()),
+                costs_info: input.costs_info(&existing.costs_info),
+            };
+            save_project(&client, &table, &project);
+            ok(project)
+        }
+        Err(error) => bad_request(error),
+    }
+}
+
+/// Aggregates the estimated costs of the open items of a project, by cost category.
+pub fn summary(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<ProjectSummary, SerializableError>> {
+    let project_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let client = dynamodb_client();
+    let project = match member::find_project(&client, &authorized_user_id(event), &project_id) {
+        Some((project, _)) => project,
+        None => return not_found("project", project_id),
+    };
+
+    let items = todo::user_items(&client, &env::var("table").unwrap(), &project.uid);
+    ok(summarize(&project, &items))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project() -> model::project::Project {
+        model::project::Project {
+            uid: model::UserId("u1".to_string()),
+            id: model::ProjectId("p1".to_string()),
+            name: "project".to_string(),
+            costs_info: model::CostInfo {
+                unit: "hours".to_string(),
+                categories: vec![
+                    model::CostCategory {
+                        id: model::CostCategoryId("dev".to_string()),
+                        name: "dev".to_string(),
+                    },
+                    model::CostCategory {
+                        id: model::CostCategoryId("review".to_string()),
+                        name: "review".to_string(),
+                    },
+                ],
+            },
+        }
+    }
+
+    fn item(
+        project_id: &str,
+        status: &str,
+        costs: Vec<(&str, u32)>,
+    ) -> model::basic_item::BasicItem {
+        model::basic_item::BasicItem {
+            id: model::ItemId(format!("{}", uuid::Uuid::new_v4().hyphenated())),
+            status: model::State { name: status.to_string() },
+            project_id: model::ProjectId(project_id.to_string()),
+            costs: costs
+                .into_iter()
+                .map(|(category, cost)| {
+                    model::Cost {
+                        category: model::CostCategoryId(category.to_string()),
+                        cost: cost,
+                    }
+                })
+                .collect(),
+            ..model::basic_item::test_item()
+        }
+    }
+
+    #[test]
+    fn can_summarize_open_items_costs() {
+        let items = vec![
+            item("p1", "", vec![("dev", 3), ("review", 1)]),
+            item("p1", "", vec![("dev", 2)]),
+            item("p1", model::DONE_STATE, vec![("dev", 10)]),
+            item("p2", "", vec![("dev", 10)]),
+        ];
+
+        let summary = summarize(&project(), &items);
+
+        assert_eq!(summary.open_items, 2);
+        assert_eq!(summary.total, 6);
+        assert_eq!(summary.categories[0].total, 5);
+        assert_eq!(summary.categories[1].total, 1);
+    }
+
+    #[test]
+    fn should_keep_ids_of_existing_categories() {
+        let input = ProjectInput {
+            name: None,
+            costs_info: Some(CostInfoInput {
+                unit: None,
+                categories: Some(vec!["review".to_string(), "test".to_string()]),
+            }),
+        };
+
+        let costs_info = input.costs_info(&project().costs_info);
+
+        assert_eq!(costs_info.unit, "hours");
+        assert_eq!(costs_info.categories.len(), 2);
+        assert_eq!(costs_info.categories[0].id.to_string(), "review");
+        assert_eq!(costs_info.categories[1].name, "test");
+    }
+}
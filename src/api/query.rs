@@ -0,0 +1,561 @@
+//! Filter expressions for listing items, like `status:done flagged:true due<2026-11-01 report`.
+//!
+//! A query is a list of terms separated by spaces, all of which must match. A term is either
+//! `field:value` or a comparison like `due<value`, and anything else is searched in the title and
+//! description of items. Values containing spaces can be quoted.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz;
+
+use rusoto_dynamodb::AttributeValue;
+
+use model;
+
+use super::common::parse_date;
+
+#[derive(Debug, Fail, PartialEq)]
+#[fail(display = "Invalid Query at position {}: {}", position, message)]
+pub struct QuerySyntaxError {
+    pub position: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Status,
+    Flagged,
+    Project,
+    Context,
+    Parent,
+    Due,
+}
+impl Field {
+    fn from_name(name: &str) -> Option<Field> {
+        match name {
+            "status" => Some(Field::Status),
+            "flagged" => Some(Field::Flagged),
+            "project" => Some(Field::Project),
+            "context" => Some(Field::Context),
+            "parent" => Some(Field::Parent),
+            "due" => Some(Field::Due),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Equal,
+    Lower,
+    LowerOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+impl Comparison {
+    fn operator(&self) -> &'static str {
+        match *self {
+            Comparison::Equal => "=",
+            Comparison::Lower => "<",
+            Comparison::LowerOrEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Text(String),
+    Bool(bool),
+    Date(DateTime<Utc>),
+    /// a day in the timezone of the user, from its start included to the start of the next day
+    Day(DateTime<Utc>, DateTime<Utc>),
+    /// the field is not set
+    Empty,
+}
+
+/// Bounds on the due date matching a comparison with a day, as operators and dates.
+fn day_bounds(
+    comparison: Comparison,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Vec<(Comparison, DateTime<Utc>)> {
+    match comparison {
+        Comparison::Equal => vec![(Comparison::GreaterOrEqual, start), (Comparison::Lower, end)],
+        Comparison::Lower => vec![(Comparison::Lower, start)],
+        Comparison::LowerOrEqual => vec![(Comparison::Lower, end)],
+        Comparison::Greater => vec![(Comparison::GreaterOrEqual, end)],
+        Comparison::GreaterOrEqual => vec![(Comparison::GreaterOrEqual, start)],
+    }
+}
+
+fn compare_dates(due: &DateTime<Utc>, comparison: Comparison, date: &DateTime<Utc>) -> bool {
+    match comparison {
+        Comparison::Equal => due == date,
+        Comparison::Lower => due < date,
+        Comparison::LowerOrEqual => due <= date,
+        Comparison::Greater => due > date,
+        Comparison::GreaterOrEqual => due >= date,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Compare {
+        field: Field,
+        comparison: Comparison,
+        value: Value,
+    },
+    /// words to find in the title or the description
+    Text(String),
+}
+
+/// A DynamoDB filter expression, with its placeholders.
+#[derive(Debug, Default)]
+pub struct FilterExpression {
+    pub expression: String,
+    pub names: HashMap<String, String>,
+    pub values: HashMap<String, AttributeValue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Query {
+    pub conditions: Vec<Condition>,
+}
+impl Query {
+    /// Parses a query. Days are interpreted in the timezone `tz`.
+    pub fn parse(input: &str, tz: &chrono_tz::Tz) -> Result<Query, QuerySyntaxError> {
+        let mut conditions = vec![];
+        for (position, term) in split_terms(input)? {
+            conditions.push(parse_term(position, &term, tz)?);
+        }
+        Ok(Query { conditions: conditions })
+    }
+
+    /// Evaluates the query on an item in memory.
+    pub fn matches(&self, item: &model::basic_item::BasicItem) -> bool {
+        self.conditions.iter().all(|condition| condition.matches(item))
+    }
+
+    /// Converts the conditions that DynamoDB can evaluate to a filter expression. Other
+    /// conditions must be evaluated in memory with `matches`.
+    pub fn to_filter_expression(&self) -> Option<FilterExpression> {
+        let mut filter = FilterExpression::default();
+        let mut expressions = vec![];
+        for (index, condition) in self.conditions.iter().enumerate() {
+            if let Some(expression) = condition.to_filter_expression(index, &mut filter) {
+                expressions.push(expression);
+            }
+        }
+        if expressions.is_empty() {
+            None
+        } else {
+            filter.expression = expressions.join(" AND ");
+            Some(filter)
+        }
+    }
+}
+
+impl Condition {
+    fn matches(&self, item: &model::basic_item::BasicItem) -> bool {
+        match *self {
+            Condition::Text(ref text) => {
+                let text = text.to_lowercase();
+                item.title.to_lowercase().contains(&text) ||
+                    item.description.to_lowercase().contains(&text)
+            }
+            Condition::Compare {
+                field,
+                comparison,
+                ref value,
+            } => {
+                match (field, value) {
+                    (Field::Status, &Value::Text(ref name)) => &item.status.name == name,
+                    (Field::Flagged, &Value::Bool(flagged)) => item.flagged == flagged,
+                    (Field::Project, &Value::Text(ref id)) => &item.project_id.to_string() == id,
+                    (Field::Context, &Value::Text(ref id)) => {
+                        item.contexts.iter().any(|context| &context.to_string() == id)
+                    }
+                    (Field::Parent, &Value::Text(ref id)) => {
+                        item.parent_id.as_ref().map(|parent| parent.to_string()) ==
+                            Some(id.clone())
+                    }
+                    (Field::Parent, &Value::Empty) => item.parent_id.is_none(),
+                    (Field::Due, &Value::Empty) => item.due.is_none(),
+                    (Field::Due, &Value::Date(ref date)) => {
+                        item.due
+                            .map(|due| compare_dates(&due, comparison, date))
+                            .unwrap_or(false)
+                    }
+                    (Field::Due, &Value::Day(start, end)) => {
+                        item.due
+                            .map(|due| {
+                                day_bounds(comparison, start, end).iter().all(
+                                    |&(comparison, ref date)| compare_dates(&due, comparison, date),
+                                )
+                            })
+                            .unwrap_or(false)
+                    }
+                    _ => false,
+                }
+            }
+        }
+    }
+
+    /// Text search is case insensitive and is not translated, as DynamoDB `contains` is case
+    /// sensitive.
+    fn to_filter_expression(
+        &self,
+        index: usize,
+        filter: &mut FilterExpression,
+    ) -> Option<String> {
+        let (field, comparison, value) = match *self {
+            Condition::Text(_) => return None,
+            Condition::Compare {
+                field,
+                comparison,
+                ref value,
+            } => (field, comparison, value),
+        };
+        let name = format!("#q{}", index);
+        let placeholder = format!(":q{}", index);
+        let attribute = match field {
+            Field::Status => "status",
+            Field::Flagged => "flagged",
+            Field::Project => "project_id",
+            Field::Context => "contexts",
+            Field::Parent => "parent_id",
+            Field::Due => "due",
+        };
+        filter.names.insert(name.clone(), attribute.to_string());
+        let attribute_value = match *value {
+            Value::Empty => {
+                filter.values.insert(
+                    ":qnull".to_string(),
+                    AttributeValue {
+                        s: Some("NULL".to_string()),
+                        ..Default::default()
+                    },
+                );
+                return Some(format!(
+                    "(attribute_not_exists({0}) OR attribute_type({0}, :qnull))",
+                    name
+                ));
+            }
+            Value::Text(ref text) => AttributeValue {
+                s: Some(text.clone()),
+                ..Default::default()
+            },
+            Value::Bool(flagged) => AttributeValue {
+                bool: Some(flagged),
+                ..Default::default()
+            },
+            Value::Date(ref date) => date_value(date),
+            Value::Day(start, end) => {
+                let mut expressions = vec![];
+                for (bound, (comparison, date)) in day_bounds(comparison, start, end)
+                    .into_iter()
+                    .enumerate()
+                {
+                    let placeholder = format!("{}b{}", placeholder, bound);
+                    filter.values.insert(placeholder.clone(), date_value(&date));
+                    expressions.push(
+                        format!("{} {} {}", name, comparison.operator(), placeholder),
+                    );
+                }
+                return Some(format!("({})", expressions.join(" AND ")));
+            }
+        };
+        filter.values.insert(placeholder.clone(), attribute_value);
+        match field {
+            Field::Status => {
+                filter.names.insert("#qname".to_string(), "name".to_string());
+                Some(format!("{}.#qname = {}", name, placeholder))
+            }
+            Field::Context => Some(format!("contains({}, {})", name, placeholder)),
+            _ => Some(format!("{} {} {}", name, comparison.operator(), placeholder)),
+        }
+    }
+}
+
+/// Dates are compared as strings in the format they are stored in.
+fn date_value(date: &DateTime<Utc>) -> AttributeValue {
+    AttributeValue {
+        s: Some(model::basic_item::due_date::format(date)),
+        ..Default::default()
+    }
+}
+
+/// Start of a `YYYY-MM-DD` day and of the next day, in the timezone `tz`.
+fn day_range(value: &str, tz: &chrono_tz::Tz) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let day = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    let start = parse_date(&day.format("%Y-%m-%d").to_string(), tz)?;
+    let end = parse_date(&day.succ().format("%Y-%m-%d").to_string(), tz)?;
+    Some((start, end))
+}
+
+/// Splits the input on spaces outside of quotes, keeping the position of each term.
+fn split_terms(input: &str) -> Result<Vec<(usize, String)>, QuerySyntaxError> {
+    let mut terms = vec![];
+    let mut current: Option<(usize, String)> = None;
+    let mut quote_start: Option<usize> = None;
+    for (position, c) in input.char_indices() {
+        if c == '"' {
+            quote_start = match quote_start {
+                Some(_) => None,
+                None => Some(position),
+            };
+            current.get_or_insert_with(|| (position, String::new()));
+        } else if c.is_whitespace() && quote_start.is_none() {
+            if let Some(term) = current.take() {
+                terms.push(term);
+            }
+        } else {
+            current
+                .get_or_insert_with(|| (position, String::new()))
+                .1
+                .push(c);
+        }
+    }
+    if let Some(position) = quote_start {
+        return Err(QuerySyntaxError {
+            position: position,
+            message: "unterminated quote".to_string(),
+        });
+    }
+    if let Some(term) = current.take() {
+        terms.push(term);
+    }
+    Ok(terms)
+}
+
+fn parse_term(
+    position: usize,
+    term: &str,
+    tz: &chrono_tz::Tz,
+) -> Result<Condition, QuerySyntaxError> {
+    let operator_position = term.find(|c: char| c == ':' || c == '<' || c == '>');
+    let (name, rest) = match operator_position {
+        Some(index) if index > 0 => (&term[..index], &term[index..]),
+        _ => return Ok(Condition::Text(term.to_string())),
+    };
+    let field = match Field::from_name(name) {
+        Some(field) => field,
+        None if rest.starts_with(':') => return Ok(Condition::Text(term.to_string())),
+        None => {
+            return Err(QuerySyntaxError {
+                position: position,
+                message: format!("unknown field '{}'", name),
+            })
+        }
+    };
+    let (comparison, raw_value) = if rest.starts_with("<=") {
+        (Comparison::LowerOrEqual, &rest[2..])
+    } else if rest.starts_with(">=") {
+        (Comparison::GreaterOrEqual, &rest[2..])
+    } else if rest.starts_with('<') {
+        (Comparison::Lower, &rest[1..])
+    } else if rest.starts_with('>') {
+        (Comparison::Greater, &rest[1..])
+    } else {
+        (Comparison::Equal, &rest[1..])
+    };
+    let value_position = position + term.len() - raw_value.len();
+    if raw_value.is_empty() {
+        return Err(QuerySyntaxError {
+            position: value_position,
+            message: format!("missing value for '{}'", name),
+        });
+    }
+    if comparison != Comparison::Equal && field != Field::Due {
+        return Err(QuerySyntaxError {
+            position: position + name.len(),
+            message: format!("'{}' can only be compared with ':'", name),
+        });
+    }
+
+    let value = match field {
+        Field::Status | Field::Project | Field::Context => Value::Text(raw_value.to_string()),
+        Field::Flagged => {
+            match raw_value {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                _ => {
+                    return Err(QuerySyntaxError {
+                        position: value_position,
+                        message: format!("expected 'true' or 'false', found '{}'", raw_value),
+                    })
+                }
+            }
+        }
+        Field::Parent => {
+            match raw_value {
+                "none" => Value::Empty,
+                _ => Value::Text(raw_value.to_string()),
+            }
+        }
+        Field::Due => {
+            match raw_value {
+                "none" if comparison == Comparison::Equal => Value::Empty,
+                _ => {
+                    if let Some((start, end)) = day_range(raw_value, tz) {
+                        Value::Day(start, end)
+                    } else {
+                        match parse_date(raw_value, tz) {
+                            Some(date) => Value::Date(date),
+                            None => {
+                                return Err(QuerySyntaxError {
+                                    position: value_position,
+                                    message: format!("invalid date '{}'", raw_value),
+                                })
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    Ok(Condition::Compare {
+        field: field,
+        comparison: comparison,
+        value: value,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn item() -> model::basic_item::BasicItem {
+        model::basic_item::BasicItem {
+            title: "Write the Report".to_string(),
+            status: model::State::done(),
+            flagged: true,
+            due: Some(Utc.ymd(2026, 10, 20).and_hms(12, 0, 0)),
+            ..model::basic_item::test_item()
+        }
+    }
+
+    #[test]
+    fn can_parse_a_query() {
+        let query = Query::parse(
+            "status:done flagged:true project:p1 due<2026-11-01 \"the report\"",
+            &chrono_tz::Tz::UTC,
+        );
+
+        assert_eq!(
+            query,
+            Ok(Query {
+                conditions: vec![
+                    Condition::Compare {
+                        field: Field::Status,
+                        comparison: Comparison::Equal,
+                        value: Value::Text("done".to_string()),
+                    },
+                    Condition::Compare {
+                        field: Field::Flagged,
+                        comparison: Comparison::Equal,
+                        value: Value::Bool(true),
+                    },
+                    Condition::Compare {
+                        field: Field::Project,
+                        comparison: Comparison::Equal,
+                        value: Value::Text("p1".to_string()),
+                    },
+                    Condition::Compare {
+                        field: Field::Due,
+                        comparison: Comparison::Lower,
+                        value: Value::Day(
+                            Utc.ymd(2026, 11, 1).and_hms(0, 0, 0),
+                            Utc.ymd(2026, 11, 2).and_hms(0, 0, 0),
+                        ),
+                    },
+                    Condition::Text("the report".to_string()),
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn can_evaluate_a_query_in_memory() {
+        let matching = Query::parse("status:done due<2026-11-01 report", &chrono_tz::Tz::UTC);
+        let not_matching = Query::parse("flagged:false", &chrono_tz::Tz::UTC);
+
+        assert!(matching.unwrap().matches(&item()));
+        assert!(!not_matching.unwrap().matches(&item()));
+    }
+
+    #[test]
+    fn should_match_items_due_any_time_of_the_day() {
+        let tz = chrono_tz::Europe::Paris;
+        let query = Query::parse("due:2026-10-20", &tz).unwrap();
+        let due_on = |due: DateTime<Utc>| {
+            model::basic_item::BasicItem {
+                due: Some(due),
+                ..model::basic_item::test_item()
+            }
+        };
+
+        // 2026-10-20 is from 2026-10-19T22:00:00Z to 2026-10-20T22:00:00Z in Paris
+        assert!(query.matches(&due_on(Utc.ymd(2026, 10, 19).and_hms(22, 0, 0))));
+        assert!(query.matches(&due_on(Utc.ymd(2026, 10, 20).and_hms(15, 0, 0))));
+        assert!(!query.matches(&due_on(Utc.ymd(2026, 10, 20).and_hms(22, 0, 0))));
+        assert!(
+            Query::parse("due<=2026-10-20", &tz).unwrap().matches(&due_on(
+                Utc.ymd(2026, 10, 20).and_hms(15, 0, 0),
+            ))
+        );
+
+        let filter = query.to_filter_expression().unwrap();
+        assert_eq!(filter.expression, "(#q0 >= :q0b0 AND #q0 < :q0b1)");
+        assert_eq!(
+            filter.values[":q0b0"].s,
+            Some("2026-10-19T22:00:00Z".to_string())
+        );
+    }
+
+    #[test]
+    fn should_reject_invalid_values() {
+        let query = Query::parse("status:done flagged:maybe", &chrono_tz::Tz::UTC);
+
+        assert_eq!(
+            query.unwrap_err().to_string(),
+            "Invalid Query at position 20: expected 'true' or 'false', found 'maybe'"
+        );
+    }
+
+    #[test]
+    fn should_reject_comparison_on_text_fields() {
+        let query = Query::parse("status>done", &chrono_tz::Tz::UTC);
+
+        assert_eq!(
+            query.unwrap_err().to_string(),
+            "Invalid Query at position 6: 'status' can only be compared with ':'"
+        );
+    }
+
+    #[test]
+    fn should_reject_unterminated_quotes() {
+        let query = Query::parse("flagged:true \"the report", &chrono_tz::Tz::UTC);
+
+        assert_eq!(
+            query.unwrap_err().to_string(),
+            "Invalid Query at position 13: unterminated quote"
+        );
+    }
+
+    #[test]
+    fn should_not_translate_text_search_to_filter_expression() {
+        let query = Query::parse("flagged:true report", &chrono_tz::Tz::UTC).unwrap();
+
+        let filter = query.to_filter_expression().unwrap();
+
+        assert_eq!(filter.expression, "#q0 = :q0");
+        assert_eq!(filter.names["#q0"], "flagged");
+        assert_eq!(filter.values[":q0"].bool, Some(true));
+    }
+}
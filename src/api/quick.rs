@@ -0,0 +1,266 @@
+//! Parsing of items typed on one line, like `Call Bob tomorrow 3pm #work @phone +urgent !`.
+//!
+//! Dates and times are in the timezone of the user. Items do not have tags, so `+tags` become
+//! contexts as in imports.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz;
+
+/// relative days further than this, like `in 99999999 days`, are left in the title
+const MAX_RELATIVE_DAYS: i64 = 36_500;
+const WEEKDAYS: &[(&str, Weekday)] = &[
+    ("monday", Weekday::Mon),
+    ("tuesday", Weekday::Tue),
+    ("wednesday", Weekday::Wed),
+    ("thursday", Weekday::Thu),
+    ("friday", Weekday::Fri),
+    ("saturday", Weekday::Sat),
+    ("sunday", Weekday::Sun),
+];
+
+/// Item typed on one line, with the names of its project and contexts.
+#[derive(Debug, Default, PartialEq)]
+pub struct QuickItem {
+    pub title: String,
+    pub project: Option<String>,
+    /// names of the contexts, starting with `@`
+    pub contexts: Vec<String>,
+    pub due: Option<DateTime<Utc>>,
+    pub flagged: bool,
+}
+
+/// Parses a day: `today`, `tomorrow`, the name of a weekday for its next occurrence, or a
+/// `YYYY-MM-DD` date.
+fn parse_day(word: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let word = word.to_lowercase();
+    match word.as_str() {
+        "today" => Some(today),
+        "tomorrow" => Some(today.succ()),
+        _ => {
+            WEEKDAYS
+                .iter()
+                .find(|&&(name, _)| name == word)
+                .map(|&(_, weekday)| {
+                    let days = (7 + weekday.num_days_from_monday() -
+                                    today.weekday().num_days_from_monday()) %
+                        7;
+                    today + Duration::days(if days == 0 { 7 } else { i64::from(days) })
+                })
+                .or_else(|| NaiveDate::parse_from_str(&word, "%Y-%m-%d").ok())
+        }
+    }
+}
+
+/// Parses `in <n> days` or `in <n> weeks` at the start of `words`.
+fn parse_relative_day(words: &[&str], today: NaiveDate) -> Option<NaiveDate> {
+    if words.len() < 3 || words[0].to_lowercase() != "in" {
+        return None;
+    }
+    let count = words[1].parse::<i64>().ok()?;
+    let days = match words[2].to_lowercase().as_str() {
+        "day" | "days" => count,
+        "week" | "weeks" => count.checked_mul(7)?,
+        _ => return None,
+    };
+    if days < -MAX_RELATIVE_DAYS || days > MAX_RELATIVE_DAYS {
+        return None;
+    }
+    today.checked_add_signed(Duration::days(days))
+}
+
+/// Parses a time of the day: `noon`, `3pm`, `3:30pm` or `15:30`. A number alone is not a time.
+fn parse_time(word: &str) -> Option<NaiveTime> {
+    let word = word.to_lowercase();
+    if word == "noon" {
+        return Some(NaiveTime::from_hms(12, 0, 0));
+    }
+    let (clock, offset) = if word.ends_with("am") {
+        (&word[..word.len() - 2], Some(0))
+    } else if word.ends_with("pm") {
+        (&word[..word.len() - 2], Some(12))
+    } else {
+        (word.as_str(), None)
+    };
+    let mut parts = clock.splitn(2, ':');
+    let hour = parts.next()?.parse::<u32>().ok()?;
+    let minute = match parts.next() {
+        Some(minute) if minute.len() == 2 => minute.parse::<u32>().ok()?,
+        Some(_) => return None,
+        None if offset.is_some() => 0,
+        None => return None,
+    };
+    let hour = match offset {
+        Some(offset) if hour >= 1 && hour <= 12 => hour % 12 + offset,
+        Some(_) => return None,
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+/// Due date from the day and time found in the text. A day alone is midnight of that day, as for
+/// dates given without time, and a time alone is its next occurrence.
+fn due(
+    day: Option<NaiveDate>,
+    time: Option<NaiveTime>,
+    tz: &chrono_tz::Tz,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    let today = now.with_timezone(tz).date().naive_local();
+    let local = match (day, time) {
+        (None, None) => return None,
+        (Some(day), None) => day.and_hms(0, 0, 0),
+        (Some(day), Some(time)) => day.and_time(time),
+        (None, Some(time)) if today.and_time(time) > now.with_timezone(tz).naive_local() => {
+            today.and_time(time)
+        }
+        (None, Some(time)) => today.succ().and_time(time),
+    };
+    tz.from_local_datetime(&local).earliest().map(|date| {
+        date.with_timezone(&Utc)
+    })
+}
+
+/// Parses a line typed by the user. Only the first project, day and time are used, others are
+/// kept in the title.
+pub fn parse(text: &str, tz: &chrono_tz::Tz, now: DateTime<Utc>) -> Result<QuickItem, String> {
+    let today = now.with_timezone(tz).date().naive_local();
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut item = QuickItem::default();
+    let mut day = None;
+    let mut time = None;
+    let mut title = vec![];
+    let mut index = 0;
+    while index < words.len() {
+        let word = words[index];
+        index += 1;
+        if word == "!" {
+            item.flagged = true;
+        } else if word.len() > 1 && word.starts_with('#') && item.project.is_none() {
+            item.project = Some(word[1..].to_string());
+        } else if word.len() > 1 && word.starts_with('@') {
+            item.contexts.push(word.to_string());
+        } else if word.len() > 1 && word.starts_with('+') {
+            item.contexts.push(format!("@{}", &word[1..]));
+        } else if day.is_none() && parse_day(word, today).is_some() {
+            day = parse_day(word, today);
+        } else if day.is_none() && parse_relative_day(&words[index - 1..], today).is_some() {
+            day = parse_relative_day(&words[index - 1..], today);
+            index += 2;
+        } else if time.is_none() && parse_time(word).is_some() {
+            time = parse_time(word);
+        } else if time.is_none() && word.to_lowercase() == "at" &&
+                   words.get(index).and_then(|next| parse_time(next)).is_some()
+        {
+            // "at 3pm", the time is read with the next word
+        } else {
+            title.push(word);
+        }
+    }
+    item.title = title.join(" ");
+    if item.title.is_empty() {
+        return Err("missing title".to_string());
+    }
+    item.due = due(day, time, tz, now);
+    Ok(item)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Monday 19 October 2026, 10:00 in Paris
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2026, 10, 19).and_hms(8, 0, 0)
+    }
+
+    #[test]
+    fn can_parse_quick_item() {
+        let item = parse(
+            "Call Bob tomorrow 3pm #work !",
+            &chrono_tz::Europe::Paris,
+            now(),
+        );
+
+        assert_eq!(
+            item,
+            Ok(QuickItem {
+                title: "Call Bob".to_string(),
+                project: Some("work".to_string()),
+                contexts: vec![],
+                due: Some(Utc.ymd(2026, 10, 20).and_hms(13, 0, 0)),
+                flagged: true,
+            })
+        );
+    }
+
+    #[test]
+    fn should_turn_tags_into_contexts() {
+        let item = parse("Buy milk @errands +home", &chrono_tz::Tz::UTC, now()).unwrap();
+
+        assert_eq!(item.title, "Buy milk");
+        assert_eq!(
+            item.contexts,
+            vec!["@errands".to_string(), "@home".to_string()]
+        );
+        assert_eq!(item.due, None);
+    }
+
+    #[test]
+    fn can_parse_days() {
+        let tz = chrono_tz::Europe::Paris;
+        let midnight = |day: u32| {
+            tz.ymd(2026, 10, day).and_hms(0, 0, 0).with_timezone(&Utc)
+        };
+
+        assert_eq!(parse("Pay rent friday", &tz, now()).unwrap().due, Some(midnight(23)));
+        assert_eq!(parse("Pay rent monday", &tz, now()).unwrap().due, Some(midnight(26)));
+        assert_eq!(parse("Pay rent in 3 days", &tz, now()).unwrap().due, Some(midnight(22)));
+        assert_eq!(
+            parse("Pay rent 2026-10-30", &tz, now()).unwrap().due,
+            Some(midnight(30))
+        );
+    }
+
+    #[test]
+    fn should_keep_out_of_range_relative_days_in_title() {
+        let item = parse("Pay rent in 99999999999 days", &chrono_tz::Tz::UTC, now()).unwrap();
+
+        assert_eq!(item.title, "Pay rent in 99999999999 days");
+        assert_eq!(item.due, None);
+        assert_eq!(
+            parse("Pay rent in 9223372036854775807 weeks", &chrono_tz::Tz::UTC, now())
+                .unwrap()
+                .due,
+            None
+        );
+        assert_eq!(
+            parse("Pay rent in -9223372036854775808 days", &chrono_tz::Tz::UTC, now())
+                .unwrap()
+                .due,
+            None
+        );
+    }
+
+    #[test]
+    fn should_use_next_occurrence_of_time_alone() {
+        let tz = chrono_tz::Europe::Paris;
+
+        assert_eq!(
+            parse("Standup at 11:30", &tz, now()).unwrap().due,
+            Some(Utc.ymd(2026, 10, 19).and_hms(9, 30, 0))
+        );
+        assert_eq!(
+            parse("Standup 9am", &tz, now()).unwrap().due,
+            Some(Utc.ymd(2026, 10, 20).and_hms(7, 0, 0))
+        );
+    }
+
+    #[test]
+    fn should_keep_numbers_and_second_project_in_title() {
+        let item = parse("Buy 3 apples #home #work", &chrono_tz::Tz::UTC, now()).unwrap();
+
+        assert_eq!(item.title, "Buy 3 apples #work");
+        assert_eq!(item.project, Some("home".to_string()));
+        assert!(parse("#home tomorrow !", &chrono_tz::Tz::UTC, now()).is_err());
+    }
+}
@@ -0,0 +1,677 @@
+//! Reminders sent before the due date of items, on the channels chosen by each user. Reminders of
+//! items due on a day, without time, are relative to 9:00 of that day in the timezone of the user.
+//! Each reminder is recorded as a notification before being sent, so it is sent at most once.
+
+use std::env;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crowbar;
+use chrono;
+use chrono::{TimeZone, Timelike};
+use chrono_tz;
+use reqwest;
+use serde_dynamodb;
+
+use base64;
+use ring::{rand, signature};
+use serde_json;
+use untrusted;
+
+use rusoto_core::{DefaultCredentialsProvider, default_tls_client};
+use rusoto_dynamodb::{AttributeValue, DynamoDb, PutItemError, PutItemInput, QueryInput,
+                      ScanInput};
+use rusoto_ses::{Body, Content, Destination, Message, SendEmailRequest, Ses, SesClient};
+
+use model;
+use model::notification::{EMAIL_CHANNEL, WEB_PUSH_CHANNEL};
+
+use super::common::*;
+use super::user;
+
+/// hour of the day, in the timezone of the user, of the reminders of items due on a day
+const DAY_REMINDER_HOUR: u32 = 9;
+/// reminders missed by less than this, for example when a scheduled run failed, are still sent
+const CATCH_UP_MINUTES: i64 = 60;
+const PUSH_TTL_SECONDS: u32 = 3600;
+/// validity of the VAPID tokens sent to push services, which accept at most 24 hours
+const VAPID_VALIDITY_HOURS: i64 = 12;
+/// hosts of the push services of browsers, a leading dot matching any subdomain
+const PUSH_SERVICES: &[&str] = &[
+    "fcm.googleapis.com",
+    "updates.push.services.mozilla.com",
+    "web.push.apple.com",
+    ".notify.windows.com",
+];
+const TIMEOUT_SECONDS: u64 = 5;
+const MAX_LISTED_NOTIFICATIONS: i64 = 50;
+
+#[derive(Debug, Fail)]
+#[fail(display = "Unknown Push Service: '{}'", _0)]
+pub struct UnknownPushService(pub String);
+
+/// Way of notifying a user.
+pub trait Channel {
+    /// Name of the channel, as chosen in the settings of the user.
+    fn name(&self) -> &'static str;
+    fn send(
+        &self,
+        user: &model::User,
+        notification: &model::notification::Notification,
+    ) -> Result<(), String>;
+}
+
+/// Sends a text email through SES, in the region of the lambda, from the address set in
+/// `notification_sender`.
+pub fn send_email(to: &str, subject: &str, text: &str) -> Result<(), String> {
+    let provider = DefaultCredentialsProvider::new().map_err(
+        |error| error.to_string(),
+    )?;
+    let client = SesClient::new(
+        default_tls_client().map_err(|error| error.to_string())?,
+        provider,
+        aws_region()?,
+    );
+    let request = SendEmailRequest {
+        source: env::var("notification_sender").unwrap(),
+        destination: Destination {
+            to_addresses: Some(vec![to.to_string()]),
+            ..Default::default()
+        },
+        message: Message {
+            subject: Content {
+                data: subject.to_string(),
+                charset: None,
+            },
+            body: Body {
+                text: Some(Content {
+                    data: text.to_string(),
+                    charset: None,
+                }),
+                html: None,
+            },
+        },
+        ..Default::default()
+    };
+    client.send_email(&request).map(|_| ()).map_err(
+        |error| error.to_string(),
+    )
+}
+
+/// Sends an email to the address of the user, once they verified it.
+pub struct EmailChannel;
+impl Channel for EmailChannel {
+    fn name(&self) -> &'static str {
+        EMAIL_CHANNEL
+    }
+
+    fn send(
+        &self,
+        user: &model::User,
+        notification: &model::notification::Notification,
+    ) -> Result<(), String> {
+        if user.email.is_empty() {
+            return Err("no email address".to_string());
+        }
+        if !user.email_verified {
+            return Err("email address not verified".to_string());
+        }
+        send_email(&user.email, &notification.title, &notification.message)
+    }
+}
+
+/// Origin of a push endpoint, which must belong to the push service of a browser.
+pub fn push_service_origin(endpoint: &str) -> Result<String, UnknownPushService> {
+    let unknown = || UnknownPushService(endpoint.to_string());
+    let parsed = reqwest::Url::parse(endpoint).map_err(|_| unknown())?;
+    if parsed.scheme() != "https" || parsed.port().is_some() || !parsed.username().is_empty() ||
+        parsed.password().is_some()
+    {
+        return Err(unknown());
+    }
+    let host = parsed.host_str().ok_or_else(unknown)?.to_lowercase();
+    let known = PUSH_SERVICES.iter().any(|service| if service.starts_with('.') {
+        host.ends_with(service)
+    } else {
+        host == *service
+    });
+    if known {
+        Ok(format!("https://{}", host))
+    } else {
+        Err(unknown())
+    }
+}
+
+fn base64url(bytes: &[u8]) -> String {
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+#[derive(Serialize)]
+struct VapidClaims<'a> {
+    aud: &'a str,
+    exp: i64,
+    sub: &'a str,
+}
+
+/// Header and claims of the VAPID token for a push service, the signed part of the token.
+fn vapid_token_input(audience: &str, expires_at: i64, subject: &str) -> String {
+    let claims = VapidClaims {
+        aud: audience,
+        exp: expires_at,
+        sub: subject,
+    };
+    format!(
+        "{}.{}",
+        base64url(br#"{"typ":"JWT","alg":"ES256"}"#),
+        base64url(serde_json::to_string(&claims).unwrap().as_bytes())
+    )
+}
+
+/// Application server key identifying the sender of push messages to push services (RFC 8292),
+/// read from `vapid_private_key`, the base64url PKCS#8 document of a P-256 key, and
+/// `vapid_public_key`, the base64url uncompressed point of its public key, which browsers use
+/// to subscribe.
+struct Vapid {
+    key_pair: signature::EcdsaKeyPair,
+    public_key: String,
+    subject: String,
+}
+impl Vapid {
+    fn from_env() -> Result<Vapid, String> {
+        let pkcs8 = env::var("vapid_private_key")
+            .ok()
+            .and_then(|key| base64::decode_config(&key, base64::URL_SAFE_NO_PAD).ok())
+            .ok_or_else(|| "missing or invalid vapid_private_key".to_string())?;
+        let key_pair = signature::EcdsaKeyPair::from_pkcs8(
+            &signature::ECDSA_P256_SHA256_FIXED_SIGNING,
+            untrusted::Input::from(&pkcs8),
+        ).map_err(|_| "invalid vapid_private_key".to_string())?;
+        Ok(Vapid {
+            key_pair: key_pair,
+            public_key: env::var("vapid_public_key").map_err(
+                |_| "missing vapid_public_key".to_string(),
+            )?,
+            subject: format!("mailto:{}", env::var("notification_sender").unwrap()),
+        })
+    }
+
+    /// `Authorization` header of a push message sent to the push service at `origin`.
+    fn authorization(
+        &self,
+        origin: &str,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<String, String> {
+        let expires_at = now + chrono::Duration::hours(VAPID_VALIDITY_HOURS);
+        let input = vapid_token_input(origin, expires_at.timestamp(), &self.subject);
+        let signature = self.key_pair
+            .sign(&rand::SystemRandom::new(), untrusted::Input::from(input.as_bytes()))
+            .map_err(|_| "could not sign the VAPID token".to_string())?;
+        Ok(format!(
+            "vapid t={}.{}, k={}",
+            input,
+            base64url(signature.as_ref()),
+            self.public_key
+        ))
+    }
+}
+
+/// Sends a push message without payload to the push endpoint of the user, authenticated with the
+/// VAPID key of the application. The service worker receiving it fetches the latest notifications
+/// to display them.
+pub struct WebPushChannel;
+impl Channel for WebPushChannel {
+    fn name(&self) -> &'static str {
+        WEB_PUSH_CHANNEL
+    }
+
+    fn send(
+        &self,
+        user: &model::User,
+        _notification: &model::notification::Notification,
+    ) -> Result<(), String> {
+        let endpoint = user.push_endpoint.as_ref().ok_or_else(
+            || "no push endpoint".to_string(),
+        )?;
+        let origin = push_service_origin(endpoint).map_err(|error| error.to_string())?;
+        let authorization = Vapid::from_env()?.authorization(&origin, chrono::Utc::now())?;
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(TIMEOUT_SECONDS))
+            .redirect(reqwest::RedirectPolicy::none())
+            .build()
+            .map_err(|error| error.to_string())?;
+        let mut headers = reqwest::header::Headers::new();
+        headers.set_raw("TTL", PUSH_TTL_SECONDS.to_string());
+        headers.set_raw("Authorization", authorization);
+        let response = client
+            .post(endpoint.as_str())
+            .headers(headers)
+            .send()
+            .map_err(|error| error.to_string())?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("push service answered {}", response.status()))
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReminderReport {
+    pub sent: usize,
+    pub failed: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NotificationList {
+    pub notifications: Vec<model::notification::Notification>,
+}
+
+/// Time the reminders of an item are relative to: its due date, or 9:00 of its due day when it
+/// is due at midnight in the timezone of the user.
+fn reference_time(
+    due: chrono::DateTime<chrono::Utc>,
+    tz: &chrono_tz::Tz,
+) -> chrono::DateTime<chrono::Utc> {
+    let local = due.with_timezone(tz);
+    if local.hour() == 0 && local.minute() == 0 && local.second() == 0 {
+        tz.from_local_datetime(&local.date().naive_local().and_hms(DAY_REMINDER_HOUR, 0, 0))
+            .earliest()
+            .map(|date| date.with_timezone(&chrono::Utc))
+            .unwrap_or(due)
+    } else {
+        due
+    }
+}
+
+/// Dates of the reminders of an item that should be sent at `now`.
+fn due_reminders(
+    item: &model::basic_item::BasicItem,
+    tz: &chrono_tz::Tz,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Vec<chrono::DateTime<chrono::Utc>> {
+    let reference = match item.due {
+        Some(due) if !item.status.is_done() && item.deleted_at.is_none() => {
+            reference_time(due, tz)
+        }
+        _ => return vec![],
+    };
+    let since = now - chrono::Duration::minutes(CATCH_UP_MINUTES);
+    item.reminders
+        .iter()
+        .map(|offset| {
+            reference - chrono::Duration::minutes(i64::from(*offset))
+        })
+        .filter(|remind_at| *remind_at > since && *remind_at <= now)
+        .collect()
+}
+
+fn new_notification(
+    user: &model::User,
+    item: &model::basic_item::BasicItem,
+    remind_at: chrono::DateTime<chrono::Utc>,
+) -> model::notification::Notification {
+    let tz = user.timezone();
+    let due = item.due.unwrap();
+    let local = due.with_timezone(&tz);
+    let message = if reference_time(due, &tz) == due {
+        format!("\"{}\" is due on {}", item.title, local.format("%Y-%m-%d at %H:%M"))
+    } else {
+        format!("\"{}\" is due on {}", item.title, local.format("%Y-%m-%d"))
+    };
+    model::notification::Notification {
+        uid: user.user_id.clone(),
+        id: format!(
+            "{}/{}",
+            remind_at.format("%Y%m%dT%H%M%SZ"),
+            item.id.to_string()
+        ),
+        item_id: item.id.clone(),
+        title: format!("Reminder: {}", item.title),
+        message: message,
+        remind_at: remind_at,
+        due: due,
+    }
+}
+
+/// Records a notification, returning `false` if it was already recorded by a previous run.
+fn record<D: DynamoDb>(client: &D, notification: &model::notification::Notification) -> bool {
+    let mut names = HashMap::new();
+    names.insert("#id".to_string(), "id".to_string());
+    let put_item = PutItemInput {
+        item: serde_dynamodb::to_hashmap(notification).unwrap(),
+        table_name: env::var("notifications_table").unwrap(),
+        condition_expression: Some("attribute_not_exists(#id)".to_string()),
+        expression_attribute_names: Some(names),
+        ..Default::default()
+    };
+    match client.put_item(&put_item) {
+        Ok(_) => true,
+        Err(PutItemError::ConditionalCheckFailed(_)) => false,
+        Err(error) => panic!("error recording notification {}: {}", notification.id, error),
+    }
+}
+
+/// Sends a notification on the channels enabled by the user, returning the errors of the
+/// channels that failed.
+fn deliver(
+    channels: &[Box<dyn Channel>],
+    user: &model::User,
+    notification: &model::notification::Notification,
+) -> Vec<String> {
+    channels
+        .iter()
+        .filter(|channel| {
+            user.reminder_channels.iter().any(
+                |name| name == channel.name(),
+            )
+        })
+        .filter_map(|channel| {
+            channel.send(user, notification).err().map(|error| {
+                format!("{}: {}", channel.name(), error)
+            })
+        })
+        .collect()
+}
+
+/// Items of all users having reminders and a due date.
+fn items_with_reminders<D: DynamoDb>(
+    client: &D,
+    table: &str,
+) -> Vec<model::basic_item::BasicItem> {
+    let mut names = HashMap::new();
+    names.insert("#due".to_string(), "due".to_string());
+    names.insert("#reminders".to_string(), "reminders".to_string());
+    let mut values = HashMap::new();
+    values.insert(
+        ":string".to_string(),
+        AttributeValue {
+            s: Some("S".to_string()),
+            ..Default::default()
+        },
+    );
+    values.insert(
+        ":zero".to_string(),
+        AttributeValue {
+            n: Some("0".to_string()),
+            ..Default::default()
+        },
+    );
+
+    let mut items = vec![];
+    let mut start_key = None;
+    loop {
+        let scan_input = ScanInput {
+            table_name: table.to_string(),
+            filter_expression: Some(
+                "attribute_type(#due, :string) AND size(#reminders) > :zero".to_string(),
+            ),
+            expression_attribute_names: Some(names.clone()),
+            expression_attribute_values: Some(values.clone()),
+            exclusive_start_key: start_key,
+            ..Default::default()
+        };
+        let output = client.scan(&scan_input).unwrap();
+        items.extend(
+            output
+                .items
+                .unwrap_or_else(|| vec![])
+                .into_iter()
+                .map(|item| serde_dynamodb::from_hashmap(item).unwrap()),
+        );
+        start_key = output.last_evaluated_key;
+        if start_key.is_none() {
+            return items;
+        }
+    }
+}
+
+/// Scheduled task sending the reminders that are due. Reminders go to the assignee of the item,
+/// or to its owner when it is not assigned.
+pub fn remind(
+    _event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<ReminderReport> {
+    let client = dynamodb_client();
+    let channels: Vec<Box<dyn Channel>> = vec![Box::new(EmailChannel), Box::new(WebPushChannel)];
+    let now = chrono::Utc::now();
+    let mut users: HashMap<model::UserId, model::User> = HashMap::new();
+    let mut report = ReminderReport { sent: 0, failed: 0 };
+    for item in items_with_reminders(&client, &env::var("table").unwrap()) {
+        let user_id = item.assignee.clone().unwrap_or_else(|| item.uid.clone());
+        let user = users
+            .entry(user_id.clone())
+            .or_insert_with(|| user::current_user(&client, &user_id));
+        for remind_at in due_reminders(&item, &user.timezone(), now) {
+            let notification = new_notification(user, &item, remind_at);
+            if !record(&client, &notification) {
+                continue;
+            }
+            let errors = deliver(&channels, user, &notification);
+            if errors.is_empty() {
+                report.sent += 1;
+            } else {
+                println!("{}: {}", notification.id, errors.join(", "));
+                report.failed += 1;
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Lists the latest notifications of the user, newest first.
+pub fn notifications(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<NotificationList, SerializableError>> {
+    let client = dynamodb_client();
+    let mut names = HashMap::new();
+    names.insert("#uid".to_string(), "uid".to_string());
+    let mut values = HashMap::new();
+    values.insert(
+        ":uid".to_string(),
+        AttributeValue {
+            s: Some(authorized_user_id(event).to_string()),
+            ..Default::default()
+        },
+    );
+    let query_input = QueryInput {
+        table_name: env::var("notifications_table").unwrap(),
+        key_condition_expression: Some("#uid = :uid".to_string()),
+        expression_attribute_names: Some(names),
+        expression_attribute_values: Some(values),
+        scan_index_forward: Some(false),
+        limit: Some(MAX_LISTED_NOTIFICATIONS),
+        ..Default::default()
+    };
+    ok(NotificationList {
+        notifications: client
+            .query(&query_input)
+            .unwrap()
+            .items
+            .unwrap_or_else(|| vec![])
+            .into_iter()
+            .map(|notification| serde_dynamodb::from_hashmap(notification).unwrap())
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Records the notifications sent, failing if asked to.
+    struct RecordingChannel {
+        name: &'static str,
+        fail: bool,
+        sent: Rc<RefCell<Vec<String>>>,
+    }
+    impl Channel for RecordingChannel {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn send(
+            &self,
+            _user: &model::User,
+            notification: &model::notification::Notification,
+        ) -> Result<(), String> {
+            if self.fail {
+                return Err("unavailable".to_string());
+            }
+            self.sent.borrow_mut().push(
+                format!("{} {}", self.name, notification.id),
+            );
+            Ok(())
+        }
+    }
+
+    fn user(channels: &[&str]) -> model::User {
+        model::User {
+            user_id: model::UserId("u1".to_string()),
+            email: "testemail".to_string(),
+            email_verified: true,
+            email_verification: None,
+            verification_sent_at: None,
+            tz: Some("Europe/Paris".to_string()),
+            feed_token: None,
+            reminder_channels: channels.iter().map(|channel| channel.to_string()).collect(),
+            push_endpoint: None,
+        }
+    }
+
+    fn item(
+        due: chrono::DateTime<chrono::Utc>,
+        reminders: Vec<u32>,
+    ) -> model::basic_item::BasicItem {
+        model::basic_item::BasicItem {
+            due: Some(due),
+            version: 1,
+            reminders: reminders,
+            ..model::basic_item::test_item()
+        }
+    }
+
+    #[test]
+    fn should_remind_before_due_date() {
+        let due = chrono::Utc.ymd(2026, 10, 19).and_hms(15, 30, 0);
+        let item = item(due, vec![1440, 30]);
+        let tz = chrono_tz::Europe::Paris;
+
+        assert_eq!(
+            due_reminders(&item, &tz, chrono::Utc.ymd(2026, 10, 19).and_hms(15, 10, 0)),
+            vec![chrono::Utc.ymd(2026, 10, 19).and_hms(15, 0, 0)]
+        );
+        assert!(
+            due_reminders(&item, &tz, chrono::Utc.ymd(2026, 10, 19).and_hms(14, 50, 0)).is_empty()
+        );
+        assert!(
+            due_reminders(&item, &tz, chrono::Utc.ymd(2026, 10, 19).and_hms(16, 10, 0)).is_empty()
+        );
+    }
+
+    #[test]
+    fn should_remind_of_day_items_in_the_morning_of_the_user() {
+        let tz = chrono_tz::Europe::Paris;
+        // midnight in Paris
+        let due = chrono::Utc.ymd(2026, 10, 18).and_hms(22, 0, 0);
+
+        assert_eq!(
+            reference_time(due, &tz),
+            chrono::Utc.ymd(2026, 10, 19).and_hms(7, 0, 0)
+        );
+        assert_eq!(reference_time(due, &chrono_tz::Tz::UTC), due);
+    }
+
+    #[test]
+    fn should_not_remind_of_done_items() {
+        let due = chrono::Utc.ymd(2026, 10, 19).and_hms(15, 30, 0);
+        let mut item = item(due, vec![0]);
+        item.status = model::State { name: model::DONE_STATE.to_string() };
+
+        assert!(due_reminders(&item, &chrono_tz::Tz::UTC, due).is_empty());
+    }
+
+    #[test]
+    fn should_send_on_enabled_channels_only() {
+        let sent = Rc::new(RefCell::new(vec![]));
+        let channels: Vec<Box<dyn Channel>> = vec![
+            Box::new(RecordingChannel {
+                name: EMAIL_CHANNEL,
+                fail: false,
+                sent: sent.clone(),
+            }),
+            Box::new(RecordingChannel {
+                name: WEB_PUSH_CHANNEL,
+                fail: true,
+                sent: sent.clone(),
+            }),
+        ];
+        let due = chrono::Utc.ymd(2026, 10, 19).and_hms(15, 30, 0);
+        let email_only = user(&[EMAIL_CHANNEL]);
+        let notification = new_notification(&email_only, &item(due, vec![0]), due);
+
+        assert!(deliver(&channels, &email_only, &notification).is_empty());
+        assert_eq!(*sent.borrow(), vec!["email 20261019T153000Z/i1".to_string()]);
+        assert_eq!(notification.message, "\"item\" is due on 2026-10-19 at 17:30");
+
+        let all_channels = user(&[EMAIL_CHANNEL, WEB_PUSH_CHANNEL]);
+        assert_eq!(
+            deliver(&channels, &all_channels, &notification),
+            vec!["web_push: unavailable".to_string()]
+        );
+    }
+
+    #[test]
+    fn should_not_email_unverified_addresses() {
+        let mut user = user(&[EMAIL_CHANNEL]);
+        user.email_verified = false;
+        let due = chrono::Utc.ymd(2026, 10, 19).and_hms(15, 30, 0);
+        let notification = new_notification(&user, &item(due, vec![0]), due);
+
+        assert_eq!(
+            EmailChannel.send(&user, &notification),
+            Err("email address not verified".to_string())
+        );
+    }
+
+    #[test]
+    fn should_only_push_to_known_push_services() {
+        assert_eq!(
+            push_service_origin("https://fcm.googleapis.com/fcm/send/abc").ok(),
+            Some("https://fcm.googleapis.com".to_string())
+        );
+        assert_eq!(
+            push_service_origin("https://wns2-par02p.notify.windows.com/w/?token=abc").ok(),
+            Some("https://wns2-par02p.notify.windows.com".to_string())
+        );
+        assert!(push_service_origin("https://push.example.com/abc").is_err());
+        assert!(push_service_origin("https://notify.windows.com.example.com/abc").is_err());
+        assert!(push_service_origin("http://fcm.googleapis.com/fcm/send/abc").is_err());
+        assert!(push_service_origin("https://fcm.googleapis.com:8443/fcm/send/abc").is_err());
+    }
+
+    #[test]
+    fn can_build_vapid_token_for_push_service() {
+        let input = vapid_token_input(
+            "https://fcm.googleapis.com",
+            1792418400,
+            "mailto:reminders@example.com",
+        );
+        let parts: Vec<Vec<u8>> = input
+            .split('.')
+            .map(|part| base64::decode_config(part, base64::URL_SAFE_NO_PAD).unwrap())
+            .collect();
+
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0], br#"{"typ":"JWT","alg":"ES256"}"#.to_vec());
+        assert_eq!(
+            String::from_utf8(parts[1].clone()).unwrap(),
+            concat!(
+                r#"{"aud":"https://fcm.googleapis.com","exp":1792418400,"#,
+                r#""sub":"mailto:reminders@example.com"}"#
+            )
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! Full-text search of items, ranked by the words of the query found in their title and
+//! description.
+
+use std::env;
+use std::collections::HashMap;
+
+use crowbar;
+
+use model;
+
+use super::common::*;
+use super::member;
+use super::todo;
+
+const TITLE_WEIGHT: f64 = 2.0;
+const DESCRIPTION_WEIGHT: f64 = 1.0;
+/// a query word that is only a prefix of an indexed word counts less than an exact match
+const PREFIX_FACTOR: f64 = 0.5;
+const DEFAULT_LIMIT: usize = 20;
+
+#[derive(Debug, Fail)]
+#[fail(display = "Missing Query")]
+struct MissingQuery();
+
+#[derive(Serialize, Debug)]
+pub struct SearchResult {
+    pub item: model::basic_item::BasicItem,
+    pub score: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SearchResults {
+    pub results: Vec<SearchResult>,
+}
+
+/// Splits a text in lowercase words.
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_lowercase())
+        .collect()
+}
+
+/// Inverted index over the title and description of items.
+struct SearchIndex {
+    items: HashMap<model::ItemId, model::basic_item::BasicItem>,
+    /// weighted number of occurrences of each word, by item
+    postings: HashMap<String, HashMap<model::ItemId, f64>>,
+}
+impl SearchIndex {
+    fn new() -> SearchIndex {
+        SearchIndex {
+            items: HashMap::new(),
+            postings: HashMap::new(),
+        }
+    }
+
+    fn from_items(items: Vec<model::basic_item::BasicItem>) -> SearchIndex {
+        let mut index = SearchIndex::new();
+        for item in items {
+            index.insert(item);
+        }
+        index
+    }
+
+    fn insert(&mut self, item: model::basic_item::BasicItem) {
+        self.remove(&item.id);
+        for (text, weight) in vec![
+            (&item.title, TITLE_WEIGHT),
+            (&item.description, DESCRIPTION_WEIGHT),
+        ]
+        {
+            for token in tokenize(text) {
+                *self.postings
+                    .entry(token)
+                    .or_insert_with(HashMap::new)
+                    .entry(item.id.clone())
+                    .or_insert(0.0) += weight;
+            }
+        }
+        self.items.insert(item.id.clone(), item);
+    }
+
+    fn remove(&mut self, id: &model::ItemId) {
+        if self.items.remove(id).is_some() {
+            for posting in self.postings.values_mut() {
+                posting.remove(id);
+            }
+            self.postings.retain(|_, posting| !posting.is_empty());
+        }
+    }
+
+    /// Scores items with tf-idf over the words of the query, best matches first.
+    fn search(&self, query: &str, limit: usize) -> Vec<SearchResult> {
+        let mut scores: HashMap<&model::ItemId, f64> = HashMap::new();
+        let item_count = self.items.len() as f64;
+        for query_token in tokenize(query) {
+            for (token, posting) in &self.postings {
+                let factor = if *token == query_token {
+                    1.0
+                } else if token.starts_with(&query_token) {
+                    PREFIX_FACTOR
+                } else {
+                    continue;
+                };
+                let idf = (1.0 + item_count / posting.len() as f64).ln();
+                for (id, frequency) in posting {
+                    *scores.entry(id).or_insert(0.0) += factor * frequency * idf;
+                }
+            }
+        }
+
+        let mut ranked: Vec<(&model::ItemId, f64)> = scores.into_iter().collect();
+        ranked.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1).unwrap().then_with(|| {
+                self.items[a.0].title.cmp(&self.items[b.0].title)
+            })
+        });
+        ranked
+            .into_iter()
+            .take(limit)
+            .map(|(id, score)| {
+                SearchResult {
+                    item: self.items[id].clone(),
+                    score: score,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Searches the caller's items and the items of projects shared with the caller for the words in
+/// the `q` query string parameter.
+pub fn search(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<SearchResults, SerializableError>> {
+    let query = match event["queryStringParameters"]["q"].as_str() {
+        Some(query) if !tokenize(query).is_empty() => query.to_string(),
+        _ => return bad_request(MissingQuery().into()),
+    };
+    let limit = event["queryStringParameters"]["limit"]
+        .as_str()
+        .and_then(|limit| limit.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_LIMIT);
+
+    let client = dynamodb_client();
+    let table = env::var("table").unwrap();
+    let user_id = authorized_user_id(event);
+    let mut items = todo::user_items(&client, &table, &user_id);
+    items.extend(
+        member::shared_items(&client, &table, &user_id)
+            .into_iter()
+            .filter(|item| item.deleted_at.is_none()),
+    );
+    let index = SearchIndex::from_items(items);
+
+    ok(SearchResults { results: index.search(&query, limit) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: &str, title: &str, description: &str) -> model::basic_item::BasicItem {
+        model::basic_item::BasicItem {
+            id: model::ItemId(id.to_string()),
+            title: title.to_string(),
+            description: description.to_string(),
+            ..model::basic_item::test_item()
+        }
+    }
+
+    fn index() -> SearchIndex {
+        SearchIndex::from_items(vec![
+            item("a", "Buy milk", "at the grocery store"),
+            item("b", "Call the plumber", "about the kitchen sink and the milk stain"),
+            item("c", "Write report", "quarterly numbers"),
+        ])
+    }
+
+    #[test]
+    fn can_tokenize_text() {
+        assert_eq!(
+            tokenize("Call Bob, re: Q3-report!"),
+            vec!["call", "bob", "re", "q3", "report"]
+        );
+    }
+
+    #[test]
+    fn should_rank_title_matches_first() {
+        let results = index().search("milk", 10);
+
+        let ids: Vec<String> = results.iter().map(|result| result.item.id.to_string()).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn can_match_prefixes() {
+        let results = index().search("plumb", 10);
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].item.id.to_string(), "b");
+    }
+
+    #[test]
+    fn should_not_find_removed_items() {
+        let mut index = index();
+        index.remove(&model::ItemId("a".to_string()));
+
+        let results = index.search("milk", 10);
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].item.id.to_string(), "b");
+    }
+}
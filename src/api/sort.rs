@@ -0,0 +1,261 @@
+//! Ordering of items, either with the `sort` query string parameter of the listing, or manually
+//! with a position inside each project.
+
+use std::cmp::Ordering;
+
+use model;
+
+/// space left between consecutive positions, so that an item can usually be moved by updating
+/// only its own position
+pub const POSITION_GAP: i64 = 1024;
+
+#[derive(Debug, Fail, PartialEq)]
+#[fail(display = "Invalid Sort: '{}'", _0)]
+pub struct InvalidSort(pub String);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortField {
+    CreatedAt,
+    UpdatedAt,
+    Due,
+    Title,
+    /// flagged items first
+    Flagged,
+    /// manual order inside each project
+    Position,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SortKey {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+/// Parses a comma separated list of fields, each one optionally prefixed with `-` to sort in
+/// descending order, like `flagged,-due`.
+pub fn parse_sort(input: &str) -> Result<Vec<SortKey>, InvalidSort> {
+    input
+        .split(',')
+        .map(|key| key.trim())
+        .filter(|key| !key.is_empty())
+        .map(|key| {
+            let (descending, name) = if key.starts_with('-') {
+                (true, &key[1..])
+            } else {
+                (false, key)
+            };
+            let field = match name {
+                "created_at" => SortField::CreatedAt,
+                "updated_at" => SortField::UpdatedAt,
+                "due" => SortField::Due,
+                "title" => SortField::Title,
+                "flagged" => SortField::Flagged,
+                "position" => SortField::Position,
+                _ => return Err(InvalidSort(key.to_string())),
+            };
+            Ok(SortKey {
+                field: field,
+                descending: descending,
+            })
+        })
+        .collect()
+}
+
+/// Items without a value for a field are always after the others.
+fn compare_options<T: Ord>(a: &Option<T>, b: &Option<T>, descending: bool) -> Ordering {
+    match (a, b) {
+        (&Some(ref a), &Some(ref b)) if descending => b.cmp(a),
+        (&Some(ref a), &Some(ref b)) => a.cmp(b),
+        (&Some(_), &None) => Ordering::Less,
+        (&None, &Some(_)) => Ordering::Greater,
+        (&None, &None) => Ordering::Equal,
+    }
+}
+
+fn compare(
+    a: &model::basic_item::BasicItem,
+    b: &model::basic_item::BasicItem,
+    key: &SortKey,
+) -> Ordering {
+    let ordering = match key.field {
+        SortField::CreatedAt => {
+            return compare_options(&a.created_at, &b.created_at, key.descending)
+        }
+        SortField::UpdatedAt => {
+            return compare_options(&a.updated_at, &b.updated_at, key.descending)
+        }
+        SortField::Due => return compare_options(&a.due, &b.due, key.descending),
+        SortField::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+        SortField::Flagged => b.flagged.cmp(&a.flagged),
+        SortField::Position => {
+            a.project_id.0.cmp(&b.project_id.0).then(
+                a.position.cmp(&b.position),
+            )
+        }
+    };
+    if key.descending {
+        ordering.reverse()
+    } else {
+        ordering
+    }
+}
+
+pub fn sort_items(items: &mut Vec<model::basic_item::BasicItem>, keys: &[SortKey]) {
+    items.sort_by(|a, b| {
+        keys.iter().fold(
+            Ordering::Equal,
+            |ordering, key| ordering.then_with(|| compare(a, b, key)),
+        )
+    });
+}
+
+/// Position of a new item, at the end of its project.
+pub fn next_position(
+    items: &[model::basic_item::BasicItem],
+    project_id: &model::ProjectId,
+) -> i64 {
+    items
+        .iter()
+        .filter(|item| &item.project_id == project_id)
+        .map(|item| item.position)
+        .max()
+        .map(|position| position + POSITION_GAP)
+        .unwrap_or(0)
+}
+
+/// Computes the new positions after moving `moved` right after `after` in its project, or first
+/// if `after` is `None`. Only items whose position changed are returned.
+pub fn move_after(
+    items: &[model::basic_item::BasicItem],
+    moved: &model::basic_item::BasicItem,
+    after: Option<&model::ItemId>,
+) -> Vec<model::basic_item::BasicItem> {
+    let mut siblings: Vec<&model::basic_item::BasicItem> = items
+        .iter()
+        .filter(|item| item.project_id == moved.project_id && item.id != moved.id)
+        .collect();
+    siblings.sort_by_key(|item| item.position);
+    let index = match after {
+        None => 0,
+        Some(after) => {
+            siblings
+                .iter()
+                .position(|item| &item.id == after)
+                .map(|index| index + 1)
+                .unwrap_or_else(|| siblings.len())
+        }
+    };
+
+    let previous = if index == 0 {
+        None
+    } else {
+        Some(siblings[index - 1].position)
+    };
+    let next = siblings.get(index).map(|item| item.position);
+    let free_position = match (previous, next) {
+        (None, None) => Some(0),
+        (Some(previous), None) => Some(previous + POSITION_GAP),
+        (None, Some(next)) => Some(next - POSITION_GAP),
+        (Some(previous), Some(next)) if next - previous > 1 => {
+            Some(previous + (next - previous) / 2)
+        }
+        _ => None,
+    };
+    if let Some(position) = free_position {
+        let mut updated = moved.clone();
+        updated.position = position;
+        return vec![updated];
+    }
+
+    // no room left between the neighbours, renumber the whole project
+    let mut ordered: Vec<model::basic_item::BasicItem> =
+        siblings.into_iter().cloned().collect();
+    ordered.insert(index, moved.clone());
+    ordered
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, mut item)| {
+            let position = index as i64 * POSITION_GAP;
+            if item.position == position && item.id != moved.id {
+                None
+            } else {
+                item.position = position;
+                Some(item)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn item(id: &str, position: i64) -> model::basic_item::BasicItem {
+        model::basic_item::BasicItem {
+            id: model::ItemId(id.to_string()),
+            title: id.to_string(),
+            position: position,
+            ..model::basic_item::test_item()
+        }
+    }
+
+    fn ids(items: &[model::basic_item::BasicItem]) -> Vec<String> {
+        items.iter().map(|item| item.id.to_string()).collect()
+    }
+
+    #[test]
+    fn can_parse_sort_keys() {
+        assert_eq!(
+            parse_sort("flagged,-due"),
+            Ok(vec![
+                SortKey {
+                    field: SortField::Flagged,
+                    descending: false,
+                },
+                SortKey {
+                    field: SortField::Due,
+                    descending: true,
+                },
+            ])
+        );
+        assert_eq!(parse_sort("priority"), Err(InvalidSort("priority".to_string())));
+    }
+
+    #[test]
+    fn should_sort_flagged_first_then_by_due_date() {
+        let mut a = item("a", 0);
+        a.due = Some(Utc.ymd(2026, 11, 2).and_hms(0, 0, 0));
+        let mut b = item("b", 0);
+        b.due = Some(Utc.ymd(2026, 11, 1).and_hms(0, 0, 0));
+        let c = item("c", 0);
+        let mut d = item("d", 0);
+        d.flagged = true;
+        let mut items = vec![a, b, c, d];
+
+        sort_items(&mut items, &parse_sort("flagged,due").unwrap());
+
+        assert_eq!(ids(&items), vec!["d", "b", "a", "c"]);
+    }
+
+    #[test]
+    fn can_move_an_item_between_two_others() {
+        let items = vec![item("a", 0), item("b", 1024), item("c", 2048)];
+
+        let moved = move_after(&items, &items[2], Some(&model::ItemId("a".to_string())));
+
+        assert_eq!(moved.len(), 1);
+        assert_eq!(moved[0].position, 512);
+    }
+
+    #[test]
+    fn should_renumber_project_when_no_room_is_left() {
+        let items = vec![item("a", 0), item("b", 1), item("c", 2048)];
+
+        let moved = move_after(&items, &items[2], Some(&model::ItemId("a".to_string())));
+
+        assert_eq!(ids(&moved), vec!["c", "b"]);
+        assert_eq!(moved[0].position, 1024);
+        assert_eq!(moved[1].position, 2048);
+    }
+}
@@ -0,0 +1,352 @@
+//! Incremental synchronisation of items for clients keeping a local copy.
+
+use std::env;
+
+use crowbar;
+use chrono;
+use chrono::{DateTime, Utc};
+use serde_dynamodb;
+use serde_dynamodb::ToQueryInput;
+
+use rusoto_dynamodb::{DynamoDb, PutItemInput};
+
+use model;
+
+use super::common::*;
+use super::member;
+use super::todo;
+
+/// how long deletions are remembered, a client that did not sync for longer has to start over
+const TOMBSTONE_RETENTION_DAYS: i64 = 30;
+/// the cursor given back is a bit in the past, so that an item saved by a request that started
+/// before the sync but finished after is not missed
+const CURSOR_OVERLAP_SECONDS: i64 = 60;
+
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid Cursor: '{}'", _0)]
+struct InvalidCursor(String);
+
+#[derive(Serialize, Debug)]
+pub struct SyncResult {
+    /// items moved to the trash are updated items, with their `deleted_at` set
+    pub created: Vec<model::basic_item::BasicItem>,
+    pub updated: Vec<model::basic_item::BasicItem>,
+    /// items purged from the trash, or of projects no longer shared with the user
+    pub deleted: Vec<model::tombstone::Tombstone>,
+    /// to send as `since` for the next sync
+    pub cursor: String,
+    /// `true` when the result contains all items instead of the changes since the cursor, the
+    /// client should then drop its local copy
+    pub reset: bool,
+}
+
+/// Trace of the deletion of an item, for the user `uid`.
+fn tombstone(
+    uid: &model::UserId,
+    item: &model::basic_item::BasicItem,
+    now: DateTime<Utc>,
+) -> model::tombstone::Tombstone {
+    model::tombstone::Tombstone {
+        uid: uid.clone(),
+        id: item.id.clone(),
+        project_id: Some(item.project_id.clone()),
+        deleted_at: now,
+        expires_at: (now + chrono::Duration::days(TOMBSTONE_RETENTION_DAYS)).timestamp(),
+    }
+}
+
+fn save_tombstone<D: DynamoDb>(client: &D, tombstone: &model::tombstone::Tombstone) {
+    let put_item = PutItemInput {
+        item: serde_dynamodb::to_hashmap(tombstone).unwrap(),
+        table_name: env::var("tombstones_table").unwrap(),
+        ..Default::default()
+    };
+    client.put_item(&put_item).unwrap();
+}
+
+/// Records that an item was deleted.
+pub fn record_deletion<D: DynamoDb>(
+    client: &D,
+    item: &model::basic_item::BasicItem,
+    now: DateTime<Utc>,
+) {
+    save_tombstone(client, &tombstone(&item.uid, item, now));
+}
+
+/// Records that an item moved out of its project is no longer visible to the members of that
+/// project. Members that can still see it in its new project get it as updated instead.
+pub fn record_move<D: DynamoDb>(
+    client: &D,
+    previous: &model::basic_item::BasicItem,
+    now: DateTime<Utc>,
+) {
+    for member in member::project_members(client, &previous.project_id) {
+        if member.owner == previous.uid {
+            save_tombstone(client, &tombstone(&member.uid, previous, now));
+        }
+    }
+}
+
+/// Records that the items of a project are no longer visible to a former member of the project.
+pub fn record_revocation<D: DynamoDb>(
+    client: &D,
+    member_id: &model::UserId,
+    items: &[model::basic_item::BasicItem],
+    now: DateTime<Utc>,
+) {
+    for item in items {
+        save_tombstone(client, &tombstone(member_id, item, now));
+    }
+}
+
+fn user_tombstones<D: DynamoDb>(
+    client: &D,
+    table: &str,
+    user_id: &model::UserId,
+) -> Vec<model::tombstone::Tombstone> {
+    let uid_filter = model::tombstone::TombstoneQueryInput {
+        uid: Some(user_id.clone()),
+        ..Default::default()
+    };
+    client
+        .query(&uid_filter.to_query_input(table.to_string()))
+        .unwrap()
+        .items
+        .unwrap_or_else(|| vec![])
+        .into_iter()
+        .map(|tombstone| serde_dynamodb::from_hashmap(tombstone).unwrap())
+        .collect()
+}
+
+/// Deletions of items of other users, restricted to the projects shared with the user. Older
+/// deletions without project are left out.
+fn shared_deletions(
+    memberships: &[model::member::Member],
+    tombstones: Vec<model::tombstone::Tombstone>,
+) -> Vec<model::tombstone::Tombstone> {
+    tombstones
+        .into_iter()
+        .filter(|tombstone| {
+            memberships.iter().any(|member| {
+                member.owner == tombstone.uid && Some(&member.id) == tombstone.project_id.as_ref()
+            })
+        })
+        .collect()
+}
+
+/// Whether an item is in a project shared with the user after `since`, so that it is new to the
+/// user even if it did not change.
+fn joined_since(
+    memberships: &[model::member::Member],
+    item: &model::basic_item::BasicItem,
+    since: DateTime<Utc>,
+) -> bool {
+    memberships.iter().any(|member| {
+        member.owner == item.uid && member.id == item.project_id &&
+            member.added_at.map(|added_at| added_at > since) == Some(true)
+    })
+}
+
+/// Splits items and deletions in what changed since the cursor, the `joined` items of projects
+/// shared since the cursor being all created. Deletions of items still visible, like items moved
+/// to another project the user can see, are left out. Without a cursor, or with one older than
+/// the retention of deletions, all items are returned as created.
+fn changes(
+    items: Vec<model::basic_item::BasicItem>,
+    joined: Vec<model::basic_item::BasicItem>,
+    tombstones: Vec<model::tombstone::Tombstone>,
+    since: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> SyncResult {
+    let cursor = (now - chrono::Duration::seconds(CURSOR_OVERLAP_SECONDS))
+        .format("%Y-%m-%dT%H:%M:%SZ")
+        .to_string();
+    let since = match since {
+        Some(since) if since > now - chrono::Duration::days(TOMBSTONE_RETENTION_DAYS) => since,
+        _ => {
+            return SyncResult {
+                created: items.into_iter().chain(joined).collect(),
+                updated: vec![],
+                deleted: vec![],
+                cursor: cursor,
+                reset: true,
+            }
+        }
+    };
+
+    let visible: Vec<model::ItemId> = items
+        .iter()
+        .chain(joined.iter())
+        .map(|item| item.id.clone())
+        .collect();
+    let (mut created, updated): (Vec<_>, Vec<_>) = items
+        .into_iter()
+        .filter(|item| item.updated_at.map(|updated_at| updated_at > since) == Some(true))
+        .partition(|item| item.created_at.map(|created_at| created_at > since) == Some(true));
+    created.extend(joined);
+    SyncResult {
+        created: created,
+        updated: updated,
+        deleted: tombstones
+            .into_iter()
+            .filter(|tombstone| tombstone.deleted_at > since)
+            .filter(|tombstone| !visible.contains(&tombstone.id))
+            .collect(),
+        cursor: cursor,
+        reset: false,
+    }
+}
+
+/// Returns the items created, updated and deleted since the cursor in the `since` query string
+/// parameter. Items of projects shared with the user are included, with their purges, and the
+/// items of projects no longer shared are reported as deleted.
+pub fn sync(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<SyncResult, SerializableError>> {
+    let since = match event["queryStringParameters"]["since"].as_str() {
+        Some(since) => {
+            match DateTime::parse_from_rfc3339(since) {
+                Ok(since) => Some(since.with_timezone(&Utc)),
+                Err(_) => return bad_request(InvalidCursor(since.to_string()).into()),
+            }
+        }
+        None => None,
+    };
+
+    let client = dynamodb_client();
+    let user_id = authorized_user_id(event);
+    let now = Utc::now();
+    let table = env::var("table").unwrap();
+    let memberships = member::memberships(&client, &user_id);
+    let (joined, shared): (Vec<_>, Vec<_>) = member::shared_items(&client, &table, &user_id)
+        .into_iter()
+        .partition(|item| {
+            since.map(|since| joined_since(&memberships, item, since)) == Some(true)
+        });
+    let mut items = todo::all_user_items(&client, &table, &user_id);
+    items.extend(shared);
+    let tombstones = match since {
+        Some(_) => {
+            let tombstones_table = env::var("tombstones_table").unwrap();
+            let mut tombstones = user_tombstones(&client, &tombstones_table, &user_id);
+            for owner in member::owners(&memberships) {
+                tombstones.extend(shared_deletions(
+                    &memberships,
+                    user_tombstones(&client, &tombstones_table, owner),
+                ));
+            }
+            tombstones
+        }
+        None => vec![],
+    };
+
+    ok(changes(items, joined, tombstones, since, now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn item(
+        id: &str,
+        created_at: DateTime<Utc>,
+        updated_at: DateTime<Utc>,
+    ) -> model::basic_item::BasicItem {
+        model::basic_item::BasicItem {
+            id: model::ItemId(id.to_string()),
+            title: id.to_string(),
+            created_at: Some(created_at),
+            updated_at: Some(updated_at),
+            version: 1,
+            ..model::basic_item::test_item()
+        }
+    }
+
+    fn ids(items: &[model::basic_item::BasicItem]) -> Vec<String> {
+        items.iter().map(|item| item.id.to_string()).collect()
+    }
+
+    #[test]
+    fn should_split_changes_since_cursor() {
+        let now = Utc.ymd(2026, 10, 10).and_hms(12, 0, 0);
+        let since = Utc.ymd(2026, 10, 9).and_hms(0, 0, 0);
+        let before = Utc.ymd(2026, 10, 8).and_hms(0, 0, 0);
+        let after = Utc.ymd(2026, 10, 9).and_hms(8, 0, 0);
+        let items = vec![
+            item("unchanged", before, before),
+            item("updated", before, after),
+            item("created", after, after),
+        ];
+        let deleted = item("deleted", before, before);
+        let tombstones = vec![
+            tombstone(&deleted.uid, &deleted, after),
+            tombstone(&deleted.uid, &deleted, before),
+        ];
+
+        let result = changes(items, vec![], tombstones, Some(since), now);
+
+        assert_eq!(ids(&result.created), vec!["created"]);
+        assert_eq!(ids(&result.updated), vec!["updated"]);
+        assert_eq!(result.deleted.len(), 1);
+        assert!(!result.reset);
+        assert_eq!(result.cursor, "2026-10-10T11:59:00Z");
+    }
+
+    #[test]
+    fn should_reset_when_cursor_is_too_old() {
+        let now = Utc.ymd(2026, 10, 10).and_hms(12, 0, 0);
+        let old = Utc.ymd(2026, 8, 1).and_hms(0, 0, 0);
+        let items = vec![item("a", old, old)];
+
+        let result = changes(items, vec![], vec![], Some(old), now);
+
+        assert!(result.reset);
+        assert_eq!(ids(&result.created), vec!["a"]);
+    }
+
+    #[test]
+    fn should_report_deletions_in_shared_projects_and_new_shares() {
+        let now = Utc.ymd(2026, 10, 10).and_hms(12, 0, 0);
+        let since = Utc.ymd(2026, 10, 9).and_hms(0, 0, 0);
+        let member = model::member::Member {
+            uid: model::UserId("u2".to_string()),
+            id: model::ProjectId("p1".to_string()),
+            owner: model::UserId("u1".to_string()),
+            role: model::member::Role::Viewer,
+            added_at: Some(Utc.ymd(2026, 10, 9).and_hms(8, 0, 0)),
+        };
+        let shared = item("shared", since, since);
+        let private = model::basic_item::BasicItem {
+            project_id: model::ProjectId("p2".to_string()),
+            ..item("private", since, since)
+        };
+        let tombstones = vec![
+            tombstone(&shared.uid, &shared, now),
+            tombstone(&private.uid, &private, now),
+        ];
+
+        let deleted = shared_deletions(&[member.clone()], tombstones);
+
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(deleted[0].id, shared.id);
+        assert!(joined_since(&[member.clone()], &shared, since));
+        assert!(!joined_since(&[member], &private, since));
+    }
+
+    #[test]
+    fn should_not_report_deletions_of_items_still_visible() {
+        let now = Utc.ymd(2026, 10, 10).and_hms(12, 0, 0);
+        let since = Utc.ymd(2026, 10, 9).and_hms(0, 0, 0);
+        let moved = item("moved", since, now);
+        let left = item("left", since, since);
+        let tombstones = vec![tombstone(&moved.uid, &moved, now), tombstone(&left.uid, &left, now)];
+
+        let result = changes(vec![moved], vec![], tombstones, Some(since), now);
+
+        assert_eq!(ids(&result.updated), vec!["moved"]);
+        assert_eq!(result.deleted.len(), 1);
+        assert_eq!(result.deleted[0].id, model::ItemId("left".to_string()));
+    }
+}
@@ -0,0 +1,493 @@
+//! Templates of items with ordered steps, like a release checklist, instantiated into a project as
+//! an item with one child per step.
+
+use std::env;
+
+use crowbar;
+use chrono;
+use chrono_tz;
+use uuid;
+use serde_dynamodb;
+use serde_dynamodb::ToQueryInput;
+
+use rusoto_dynamodb::{DeleteItemInput, DynamoDb, PutItemInput};
+
+use model;
+
+use super::batch;
+use super::common::*;
+use super::member;
+use super::sort;
+use super::todo;
+use super::user;
+
+/// maximum number of steps of a template, an instance and its steps being written in a single
+/// transaction of at most `MAX_TRANSACTION_ACTIONS` items
+const MAX_TEMPLATE_ITEMS: usize = MAX_TRANSACTION_ACTIONS - 1;
+
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid Template: {}", _0)]
+struct InvalidTemplate(&'static str);
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid Date for due: '{}'", _0)]
+struct InvalidDue(String);
+
+#[derive(Serialize, Debug)]
+pub struct TemplateList {
+    pub templates: Vec<model::template::Template>,
+}
+
+/// Item created from a template, with its children in the order of the steps.
+#[derive(Serialize, Debug)]
+pub struct Instance {
+    pub item: model::basic_item::BasicItem,
+    pub children: Vec<model::basic_item::BasicItem>,
+}
+
+#[derive(Deserialize)]
+struct TemplateInput {
+    title: Option<String>,
+    description: Option<String>,
+    items: Option<Vec<model::template::TemplateItem>>,
+}
+impl TemplateInput {
+    fn items(&self) -> Result<Option<Vec<model::template::TemplateItem>>, SerializableError> {
+        match self.items {
+            None => Ok(None),
+            Some(ref items) if items.len() > MAX_TEMPLATE_ITEMS => {
+                Err(InvalidTemplate("too many items").into())
+            }
+            Some(ref items) if items.iter().any(|item| item.title.trim().is_empty()) => {
+                Err(InvalidTemplate("item without title").into())
+            }
+            Some(ref items) => Ok(Some(items.clone())),
+        }
+    }
+
+    fn to_template(
+        &self,
+        user_id: &model::UserId,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<model::template::Template, SerializableError> {
+        let title = self.title.clone().ok_or_else(|| MissingField("title"))?;
+        Ok(model::template::Template {
+            uid: user_id.clone(),
+            id: model::TemplateId(format!("{}", uuid::Uuid::new_v4().hyphenated())),
+            title: title,
+            description: self.description.clone().unwrap_or_else(|| "".to_string()),
+            items: self.items()?.unwrap_or_else(|| vec![]),
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    fn apply_to(
+        &self,
+        template: &model::template::Template,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<model::template::Template, SerializableError> {
+        Ok(model::template::Template {
+            uid: template.uid.clone(),
+            id: template.id.clone(),
+            title: self.title.clone().unwrap_or_else(|| template.title.clone()),
+            description: self.description.clone().unwrap_or_else(
+                || template.description.clone(),
+            ),
+            items: self.items()?.unwrap_or_else(|| template.items.clone()),
+            created_at: template.created_at,
+            updated_at: now,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct InstanceInput {
+    project_id: Option<String>,
+    /// due date of the item created, its children having none
+    due: Option<String>,
+}
+
+pub fn user_templates<D: DynamoDb>(
+    client: &D,
+    table: &str,
+    user_id: &model::UserId,
+) -> Vec<model::template::Template> {
+    let uid_filter = model::template::TemplateQueryInput {
+        uid: Some(user_id.clone()),
+        ..Default::default()
+    };
+    client
+        .query(&uid_filter.to_query_input(table.to_string()))
+        .unwrap()
+        .items
+        .unwrap_or_else(|| vec![])
+        .into_iter()
+        .map(|template| serde_dynamodb::from_hashmap(template).unwrap())
+        .collect()
+}
+
+fn find_template<D: DynamoDb>(
+    client: &D,
+    table: &str,
+    user_id: &model::UserId,
+    template_id: &str,
+) -> Option<model::template::Template> {
+    let template_filter = model::template::TemplateQueryInput {
+        uid: Some(user_id.clone()),
+        id: Some(template_id.to_string().into()),
+        ..Default::default()
+    };
+    client
+        .query(&template_filter.to_query_input(table.to_string()))
+        .unwrap()
+        .items
+        .unwrap_or_else(|| vec![])
+        .pop()
+        .map(|template| serde_dynamodb::from_hashmap(template).unwrap())
+}
+
+fn save_template<D: DynamoDb>(client: &D, table: &str, template: &model::template::Template) {
+    let put_item = PutItemInput {
+        item: serde_dynamodb::to_hashmap(template).unwrap(),
+        table_name: table.to_string(),
+        ..Default::default()
+    };
+    client.put_item(&put_item).unwrap();
+}
+
+fn new_item(
+    owner: &model::UserId,
+    project_id: &model::ProjectId,
+    parent_id: Option<&model::ItemId>,
+    title: &str,
+    description: &str,
+    position: i64,
+) -> Result<model::basic_item::BasicItem, SerializableError> {
+    let mut item = todo::ItemInput::titled(title, description, project_id, parent_id)
+        .to_new_item(owner.clone(), &chrono_tz::Tz::UTC)?;
+    item.position = position;
+    Ok(item)
+}
+
+/// Items created from a template at the end of a project, the parent first, then its children
+/// in the order of the steps.
+fn instantiate_items(
+    template: &model::template::Template,
+    owner: &model::UserId,
+    project_id: &model::ProjectId,
+    position: i64,
+    due: Option<chrono::DateTime<chrono::Utc>>,
+) -> Result<(model::basic_item::BasicItem, Vec<model::basic_item::BasicItem>), SerializableError> {
+    let mut parent = new_item(
+        owner,
+        project_id,
+        None,
+        &template.title,
+        &template.description,
+        position,
+    )?;
+    parent.due = due;
+    let mut children = vec![];
+    for (index, step) in template.items.iter().enumerate() {
+        children.push(new_item(
+            owner,
+            project_id,
+            Some(&parent.id),
+            &step.title,
+            &step.description,
+            position + (index as i64 + 1) * sort::POSITION_GAP,
+        )?);
+    }
+    Ok((parent, children))
+}
+
+pub fn list(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<TemplateList, SerializableError>> {
+    let client = dynamodb_client();
+    ok(TemplateList {
+        templates: user_templates(
+            &client,
+            &env::var("templates_table").unwrap(),
+            &authorized_user_id(event),
+        ),
+    })
+}
+
+pub fn add(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::template::Template,
+        SerializableError,
+    >,
+> {
+    let user_id = authorized_user_id(event);
+    match parse_body::<TemplateInput>(event).and_then(|input| {
+        input.to_template(&user_id, chrono::Utc::now())
+    }) {
+        Ok(template) => {
+            let client = dynamodb_client();
+            save_template(&client, &env::var("templates_table").unwrap(), &template);
+            ok(template)
+        }
+        Err(error) => bad_request(error),
+    }
+}
+
+pub fn get(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::template::Template,
+        SerializableError,
+    >,
+> {
+    let template_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let client = dynamodb_client();
+    match find_template(
+        &client,
+        &env::var("templates_table").unwrap(),
+        &authorized_user_id(event),
+        &template_id,
+    ) {
+        Some(template) => ok(template),
+        None => not_found("template", template_id),
+    }
+}
+
+/// Changes a template. Given `items` replace all the steps, items already created from the
+/// template are not changed.
+pub fn update(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::template::Template,
+        SerializableError,
+    >,
+> {
+    let template_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let table = env::var("templates_table").unwrap();
+    let client = dynamodb_client();
+    let template = match find_template(&client, &table, &authorized_user_id(event), &template_id) {
+        Some(template) => template,
+        None => return not_found("template", template_id),
+    };
+
+    match parse_body::<TemplateInput>(event).and_then(|input| {
+        input.apply_to(&template, chrono::Utc::now())
+    }) {
+        Ok(updated) => {
+            save_template(&client, &table, &updated);
+            ok(updated)
+        }
+        Err(error) => bad_request(error),
+    }
+}
+
+pub fn delete(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::template::Template,
+        SerializableError,
+    >,
+> {
+    let template_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let table = env::var("templates_table").unwrap();
+    let client = dynamodb_client();
+    let user_id = authorized_user_id(event);
+    let template = match find_template(&client, &table, &user_id, &template_id) {
+        Some(template) => template,
+        None => return not_found("template", template_id),
+    };
+    let delete_item = DeleteItemInput {
+        key: user_key(&user_id, &template_id),
+        table_name: table,
+        ..Default::default()
+    };
+    client.delete_item(&delete_item).unwrap();
+    ok(template)
+}
+
+/// Creates an item from a template at the end of a project of the user or shared with the user
+/// as an editor, with one child per step of the template. The item and its children are written
+/// in one transaction.
+pub fn instantiate(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<Instance, SerializableError>> {
+    let template_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let client = dynamodb_client();
+    let actor = authorized_actor(event);
+    let template = match find_template(
+        &client,
+        &env::var("templates_table").unwrap(),
+        &actor.user_id,
+        &template_id,
+    ) {
+        Some(template) => template,
+        None => return not_found("template", template_id),
+    };
+    let input = match parse_body::<InstanceInput>(event) {
+        Ok(input) => input,
+        Err(error) => return bad_request(error),
+    };
+    let project_id = match input.project_id {
+        Some(project_id) => project_id,
+        None => return bad_request(MissingField("project_id").into()),
+    };
+    let project = match member::find_project(&client, &actor.user_id, &project_id) {
+        Some((ref project, ref role)) if !role.can_edit() => {
+            return forbidden("project", project.id.to_string())
+        }
+        Some((project, _)) => project,
+        None => return not_found("project", project_id),
+    };
+    let due = match input.due {
+        Some(ref due) => {
+            let tz = user::current_user(&client, &actor.user_id).timezone();
+            match parse_date(due, &tz) {
+                Some(date) => Some(date),
+                None => return bad_request(InvalidDue(due.clone()).into()),
+            }
+        }
+        None => None,
+    };
+
+    let table = env::var("table").unwrap();
+    let existing = todo::user_items(&client, &table, &project.uid);
+    let (mut item, mut children) = match instantiate_items(
+        &template,
+        &project.uid,
+        &project.id,
+        sort::next_position(&existing, &project.id),
+        due,
+    ) {
+        Ok(instance) => instance,
+        Err(error) => return bad_request(error),
+    };
+    let now = chrono::Utc::now();
+    item.created(&actor, now);
+    for child in &mut children {
+        child.created(&actor, now);
+    }
+    let mut items = vec![item.clone()];
+    items.extend(children.iter().cloned());
+    let writes = items.iter().map(|item| todo::item_write(&table, item)).collect();
+    if let Err(reasons) = transact_write(&client, writes) {
+        let reasons: Vec<String> = reasons.into_iter().filter_map(|reason| reason).collect();
+        panic!(
+            "error instantiating template {}: {}",
+            template.id.to_string(),
+            reasons.join(", ")
+        );
+    }
+    let changes: Vec<_> = items.iter().map(|item| (None, item)).collect();
+    batch::record_changes(&client, &changes);
+
+    ok(Instance {
+        item: item,
+        children: children,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(steps: &[&str]) -> model::template::Template {
+        model::template::Template {
+            uid: model::UserId("u1".to_string()),
+            id: model::TemplateId("t1".to_string()),
+            title: "Release".to_string(),
+            description: "Steps of a release".to_string(),
+            items: steps
+                .iter()
+                .map(|step| {
+                    model::template::TemplateItem {
+                        title: step.to_string(),
+                        description: "".to_string(),
+                    }
+                })
+                .collect(),
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn can_instantiate_template_with_ordered_children() {
+        let owner = model::UserId("u2".to_string());
+        let project_id = model::ProjectId("9f1d3c0e-5b7a-4e2f-8c6d-1a2b3c4d5e6f".to_string());
+
+        let (item, children) = instantiate_items(
+            &template(&["Bump version", "Tag", "Publish"]),
+            &owner,
+            &project_id,
+            2048,
+            None,
+        ).ok()
+            .unwrap();
+
+        assert_eq!(item.title, "Release");
+        assert_eq!(item.uid, owner);
+        assert_eq!(item.parent_id, None);
+        assert_eq!(
+            children
+                .iter()
+                .map(|child| child.title.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["Bump version", "Tag", "Publish"]
+        );
+        assert!(children.iter().all(
+            |child| child.parent_id == Some(item.id.clone()),
+        ));
+        assert!(children.windows(2).all(
+            |pair| pair[0].position < pair[1].position,
+        ));
+        assert!(children[0].position > item.position);
+    }
+
+    #[test]
+    fn should_reject_too_many_steps() {
+        let input = TemplateInput {
+            title: Some("Release".to_string()),
+            description: None,
+            items: Some(
+                (0..MAX_TEMPLATE_ITEMS + 1)
+                    .map(|index| {
+                        model::template::TemplateItem {
+                            title: format!("step {}", index),
+                            description: "".to_string(),
+                        }
+                    })
+                    .collect(),
+            ),
+        };
+
+        assert!(input.to_template(&model::UserId("u1".to_string()), chrono::Utc::now()).is_err());
+    }
+
+    #[test]
+    fn should_keep_steps_not_given() {
+        let input = TemplateInput {
+            title: Some("Release 2".to_string()),
+            description: None,
+            items: None,
+        };
+
+        let updated = input
+            .apply_to(&template(&["Tag"]), chrono::Utc::now())
+            .ok()
+            .unwrap();
+
+        assert_eq!(updated.title, "Release 2");
+        assert_eq!(updated.items.len(), 1);
+    }
+}
@@ -1,138 +1,907 @@
 use std::env;
+use std::collections::HashMap;
 
 use crowbar;
-use http;
-use mime;
-use serde_json;
+use chrono;
+use chrono_tz;
 use uuid;
-use serde::ser::{Serialize, Serializer, SerializeStruct};
-use failure::{Error, Fail};
 use serde_dynamodb;
 use serde_dynamodb::ToQueryInput;
 
-use rusoto_core::{DefaultCredentialsProvider, Region};
-use rusoto_dynamodb::{DynamoDb, DynamoDbClient, PutItemInput};
-use rusoto_core::default_tls_client;
+use rusoto_dynamodb::{AttributeValue, DeleteItemError, DeleteItemInput, DynamoDb, GetItemInput,
+                      Put, PutItemError, PutItemInput, TransactWriteItem};
 
 use model;
 
-pub struct SerializableError(pub Error);
-impl Serialize for SerializableError {
-    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        let mut state = serializer.serialize_struct("Error", 1)?;
-        state.serialize_field("error", &format!("{}", self.0))?;
-        state.end()
-    }
-}
-impl<F: Fail> From<F> for SerializableError {
-    fn from(failure: F) -> SerializableError {
-        SerializableError(failure.into())
-    }
-}
+use super::common::*;
+use super::member;
+use super::query;
+use super::sort;
+use super::user;
 
 #[derive(Debug, Fail)]
-#[fail(display = "Missing Body")]
-struct MissingBody();
+#[fail(display = "Invalid Parent: '{}'", _0)]
+struct InvalidParent(String);
 #[derive(Debug, Fail)]
-#[fail(display = "Missing Field: '{}'", _0)]
-struct MissingField(&'static str);
+#[fail(display = "Parent '{}' would create a cycle", _0)]
+struct CyclicParent(String);
 #[derive(Debug, Fail)]
-#[fail(display = "Parsing Error: {}", serde_error)]
-struct ParsingError {
-    #[cause]
-    serde_error: ::serde_json::Error,
-}
+#[fail(display = "Invalid Context: '{}'", _0)]
+struct InvalidContext(String);
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid Project: '{}'", _0)]
+struct InvalidProject(String);
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid Cost Category: '{}'", _0)]
+struct InvalidCostCategory(String);
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid Cost: total for category '{}' is too large", _0)]
+struct CostOverflow(String);
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid Assignee: '{}' is not a member of the project", _0)]
+struct InvalidAssignee(String);
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid Reminders: {}", _0)]
+struct InvalidReminders(&'static str);
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid Quick Add: {}", _0)]
+struct InvalidQuickAdd(String);
 #[derive(Debug, Fail)]
-#[fail(display = "Invalid UUID for {}: '{}'", field, uuid)]
-struct InvalidUUIDError {
-    uuid: String,
+#[fail(display = "Invalid Date for {}: '{}'", field, date)]
+struct InvalidDate {
+    date: String,
     field: &'static str,
-    #[cause]
-    uuid_error: uuid::ParseError,
 }
 #[derive(Debug, Fail)]
-#[fail(display = "not found: {} with id '{}'", object, id)]
-struct NotFound {
-    object: &'static str,
-    id: String,
+#[fail(display = "Item '{}' was modified by another request", _0)]
+pub struct VersionConflict(pub String);
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid Idempotency Key: '{}'", _0)]
+struct InvalidIdempotencyKey(String);
+
+/// how long a repeated `Idempotency-Key` returns the item returned to the first request
+const IDEMPOTENCY_WINDOW_HOURS: i64 = 24;
+const IDEMPOTENCY_KEY_MAX_LENGTH: usize = 255;
+const MAX_REMINDERS: usize = 5;
+/// reminders can be set up to 30 days before the due date
+const MAX_REMINDER_OFFSET_MINUTES: u32 = 30 * 24 * 60;
+
+/// Items of the user, except those in the trash.
+pub fn user_items<D: DynamoDb>(
+    client: &D,
+    table: &str,
+    user_id: &model::UserId,
+) -> Vec<model::basic_item::BasicItem> {
+    all_user_items(client, table, user_id)
+        .into_iter()
+        .filter(|item| item.deleted_at.is_none())
+        .collect()
 }
 
-pub fn list(
-    event: &crowbar::Value,
-    _context: &crowbar::LambdaContext,
-) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<model::api::ItemList>> {
-    let table = env::var("table").unwrap();
-    let provider = DefaultCredentialsProvider::new().unwrap();
-    let client = DynamoDbClient::new(default_tls_client().unwrap(), provider, Region::UsEast1);
+/// Items of the user, including those in the trash.
+pub fn all_user_items<D: DynamoDb>(
+    client: &D,
+    table: &str,
+    user_id: &model::UserId,
+) -> Vec<model::basic_item::BasicItem> {
+    filtered_user_items(client, table, user_id, None)
+}
+
+fn filtered_user_items<D: DynamoDb>(
+    client: &D,
+    table: &str,
+    user_id: &model::UserId,
+    filter: Option<query::FilterExpression>,
+) -> Vec<model::basic_item::BasicItem> {
     let uid_filter = model::basic_item::BasicItemQueryInput {
-        uid: Some(
-            event["requestContext"]["authorizer"]["user_id"]
-                .as_str()
-                .unwrap()
-                .to_string()
-                .into(),
-        ),
+        uid: Some(user_id.clone()),
         ..Default::default()
     };
-
-    let query_output: Vec<model::basic_item::BasicItem> = client
-        .query(&uid_filter.to_query_input(table))
+    let mut query_input = uid_filter.to_query_input(table.to_string());
+    if let Some(filter) = filter {
+        let mut names = query_input.expression_attribute_names.take().unwrap_or_else(
+            HashMap::new,
+        );
+        names.extend(filter.names);
+        let mut values = query_input.expression_attribute_values.take().unwrap_or_else(
+            HashMap::new,
+        );
+        values.extend(filter.values);
+        query_input.filter_expression = Some(filter.expression);
+        query_input.expression_attribute_names = Some(names);
+        query_input.expression_attribute_values = Some(values);
+    }
+    client
+        .query(&query_input)
         .unwrap()
         .items
         .unwrap_or_else(|| vec![])
         .into_iter()
         .map(|item| serde_dynamodb::from_hashmap(item).unwrap())
+        .collect()
+}
+
+/// Condition on the version stored for an item. Items stored before versions were introduced
+/// have no version attribute, and are considered at version 0.
+fn version_condition(
+    version: u64,
+) -> (String, HashMap<String, String>, HashMap<String, AttributeValue>) {
+    let mut names = HashMap::new();
+    names.insert("#version".to_string(), "version".to_string());
+    let mut values = HashMap::new();
+    values.insert(
+        ":version".to_string(),
+        AttributeValue {
+            n: Some(version.to_string()),
+            ..Default::default()
+        },
+    );
+    (
+        "attribute_not_exists(#version) OR #version = :version".to_string(),
+        names,
+        values,
+    )
+}
+
+/// Saves an item that was touched once since it was read as `previous`, failing if another
+/// request saved it in the meantime. The changes are recorded in the history of the item, and
+/// an item moved to another project is reported as deleted to the members of its old project.
+pub fn save_item<D: DynamoDb>(
+    client: &D,
+    table: &str,
+    previous: Option<&model::basic_item::BasicItem>,
+    item: &model::basic_item::BasicItem,
+) -> Result<(), VersionConflict> {
+    let (condition, names, values) = version_condition(item.version.saturating_sub(1));
+    let put_item = PutItemInput {
+        item: serde_dynamodb::to_hashmap(item).unwrap(),
+        table_name: table.to_string(),
+        condition_expression: Some(condition),
+        expression_attribute_names: Some(names),
+        expression_attribute_values: Some(values),
+        ..Default::default()
+    };
+    match client.put_item(&put_item) {
+        Ok(_) => {
+            super::history::record(client, previous, item);
+            if let Some(previous) = previous {
+                if previous.project_id != item.project_id {
+                    super::sync::record_move(client, previous, chrono::Utc::now());
+                }
+            }
+            super::webhook::notify(client, &[(previous, item)]);
+            Ok(())
+        }
+        Err(PutItemError::ConditionalCheckFailed(_)) => Err(VersionConflict(item.id.to_string())),
+        Err(error) => panic!("error saving item {}: {}", item.id.to_string(), error),
+    }
+}
+
+/// Write of an item in a transaction, with the same version check as `save_item`.
+pub fn item_write(table: &str, item: &model::basic_item::BasicItem) -> TransactWriteItem {
+    let (condition, names, values) = version_condition(item.version.saturating_sub(1));
+    TransactWriteItem {
+        put: Some(Put {
+            item: serde_dynamodb::to_hashmap(item).unwrap(),
+            table_name: table.to_string(),
+            condition_expression: Some(condition),
+            expression_attribute_names: Some(names),
+            expression_attribute_values: Some(values),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Removes an item, failing if it changed since it was read. The deletion is recorded for
+/// clients syncing later.
+pub fn remove_item<D: DynamoDb>(
+    client: &D,
+    table: &str,
+    item: &model::basic_item::BasicItem,
+) -> Result<(), VersionConflict> {
+    let (condition, names, values) = version_condition(item.version);
+    let delete_item = DeleteItemInput {
+        key: user_key(&item.uid, &item.id.to_string()),
+        table_name: table.to_string(),
+        condition_expression: Some(condition),
+        expression_attribute_names: Some(names),
+        expression_attribute_values: Some(values),
+        ..Default::default()
+    };
+    match client.delete_item(&delete_item) {
+        Ok(_) => {
+            super::sync::record_deletion(client, item, chrono::Utc::now());
+            Ok(())
+        }
+        Err(DeleteItemError::ConditionalCheckFailed(_)) => {
+            Err(VersionConflict(item.id.to_string()))
+        }
+        Err(error) => panic!("error removing item {}: {}", item.id.to_string(), error),
+    }
+}
+
+/// Entity tag of an item, from its version.
+pub fn etag(item: &model::basic_item::BasicItem) -> String {
+    format!("\"{}\"", item.version)
+}
+
+/// Checks an `If-Match` header against the current version of an item. The header can list
+/// several tags, weak or not, or be `*` to match any version.
+pub fn if_match(header: &str, item: &model::basic_item::BasicItem) -> bool {
+    let current = etag(item);
+    header.split(',').map(|tag| tag.trim()).any(|tag| {
+        tag == "*" || tag.trim_start_matches("W/") == current
+    })
+}
+
+/// Fails with a conflict if the request has an `If-Match` header that does not match the item.
+fn check_if_match(
+    event: &crowbar::Value,
+    item: &model::basic_item::BasicItem,
+) -> Result<(), VersionConflict> {
+    match header(event, "If-Match") {
+        Some(header) if !if_match(header, item) => Err(VersionConflict(item.id.to_string())),
+        _ => Ok(()),
+    }
+}
+
+pub fn find_item<D: DynamoDb>(
+    client: &D,
+    table: &str,
+    user_id: &model::UserId,
+    todo_id: &str,
+) -> Option<model::basic_item::BasicItem> {
+    let todo_filter = model::basic_item::BasicItemQueryInput {
+        uid: Some(user_id.clone()),
+        id: Some(todo_id.to_string().into()),
+        ..Default::default()
+    };
+
+    client
+        .query(&todo_filter.to_query_input(table.to_string()))
+        .unwrap()
+        .items
+        .unwrap_or_else(|| vec![])
+        .pop()
+        .map(|item| serde_dynamodb::from_hashmap(item).unwrap())
+}
+
+/// Returns all items below `id` in the hierarchy, children before grand children.
+pub fn descendants<'a>(
+    items: &'a [model::basic_item::BasicItem],
+    id: &model::ItemId,
+) -> Vec<&'a model::basic_item::BasicItem> {
+    let mut found: Vec<&model::basic_item::BasicItem> = items
+        .iter()
+        .filter(|item| item.parent_id.as_ref() == Some(id))
         .collect();
-    let todos = model::api::ItemList { items: query_output };
+    let mut index = 0;
+    while index < found.len() {
+        let current = found[index].id.clone();
+        let next: Vec<&model::basic_item::BasicItem> = items
+            .iter()
+            .filter(|item| item.parent_id.as_ref() == Some(&current))
+            .filter(|item| !found.iter().any(|known| known.id == item.id))
+            .collect();
+        found.extend(next);
+        index += 1;
+    }
+    found
+}
 
-    Ok(crowbar::ApiGatewayResponse {
-        status_code: http::StatusCode::OK,
-        body: Some((Ok(todos), mime::APPLICATION_JSON)),
+/// Checks that `parent_id` can be set as parent of `id`: it must be one of the `visible` items,
+/// and must not be `id` itself or one of its descendants among all the `items` of the owner.
+pub fn check_parent(
+    items: &[model::basic_item::BasicItem],
+    visible: &[model::basic_item::BasicItem],
+    id: &model::ItemId,
+    parent_id: &model::ItemId,
+) -> Result<(), SerializableError> {
+    if !visible.iter().any(|item| &item.id == parent_id) {
+        return Err(InvalidParent(parent_id.to_string()).into());
+    }
+    if parent_id == id || descendants(items, id).iter().any(|item| &item.id == parent_id) {
+        return Err(CyclicParent(parent_id.to_string()).into());
+    }
+    Ok(())
+}
+
+/// Checks that all contexts of an item are contexts defined by the user.
+pub fn check_contexts<D: DynamoDb>(
+    client: &D,
+    item: &model::basic_item::BasicItem,
+) -> Result<(), SerializableError> {
+    if item.contexts.is_empty() {
+        return Ok(());
+    }
+    let known = super::context::user_contexts(
+        client,
+        &env::var("contexts_table").unwrap(),
+        &item.uid,
+    );
+    check_known_contexts(item, &known)
+}
+
+/// Checks that all contexts of an item are among the `known` contexts of its owner.
+pub fn check_known_contexts(
+    item: &model::basic_item::BasicItem,
+    known: &[model::context::Context],
+) -> Result<(), SerializableError> {
+    match item.contexts.iter().find(|context_id| {
+        !known.iter().any(|context| &context.id == *context_id)
+    }) {
+        Some(unknown) => Err(InvalidContext(unknown.to_string()).into()),
+        None => Ok(()),
+    }
+}
+
+/// Checks that all costs of an item are in cost categories declared by its project.
+pub fn check_costs<D: DynamoDb>(
+    client: &D,
+    item: &model::basic_item::BasicItem,
+) -> Result<(), SerializableError> {
+    if item.costs.is_empty() {
+        return Ok(());
+    }
+    let project = super::project::find_project(
+        client,
+        &env::var("projects_table").unwrap(),
+        &item.uid,
+        &item.project_id.to_string(),
+    );
+    check_project_costs(item, project.as_ref())
+}
+
+/// Checks that all costs of an item are in cost categories declared by its `project`, `None` if
+/// the project does not exist.
+pub fn check_project_costs(
+    item: &model::basic_item::BasicItem,
+    project: Option<&model::project::Project>,
+) -> Result<(), SerializableError> {
+    if item.costs.is_empty() {
+        return Ok(());
+    }
+    let project = project.ok_or_else(|| InvalidProject(item.project_id.to_string()))?;
+    match item.costs.iter().find(|cost| {
+        !project.costs_info.categories.iter().any(|category| {
+            category.id == cost.category
+        })
+    }) {
+        Some(unknown) => Err(InvalidCostCategory(unknown.category.to_string()).into()),
+        None => Ok(()),
+    }
+}
+
+/// Checks that the assignee of an item, if any, is the owner or a member of its project.
+pub fn check_assignee<D: DynamoDb>(
+    client: &D,
+    item: &model::basic_item::BasicItem,
+) -> Result<(), SerializableError> {
+    let role = item.assignee.as_ref().and_then(|assignee| {
+        member::role(client, assignee, &item.uid, &item.project_id)
+    });
+    check_assignee_role(item, role)
+}
+
+/// Checks that the assignee of an item, if any, has a `role` on its project.
+pub fn check_assignee_role(
+    item: &model::basic_item::BasicItem,
+    role: Option<model::member::Role>,
+) -> Result<(), SerializableError> {
+    match item.assignee {
+        Some(ref assignee) if role.is_none() => Err(InvalidAssignee(assignee.to_string()).into()),
+        _ => Ok(()),
+    }
+}
+
+fn idempotency_key_key(
+    user_id: &model::UserId,
+    key: &str,
+) -> HashMap<String, AttributeValue> {
+    let mut item_key = HashMap::new();
+    item_key.insert(
+        "uid".to_string(),
+        AttributeValue {
+            s: Some(user_id.to_string()),
+            ..Default::default()
+        },
+    );
+    item_key.insert(
+        "key".to_string(),
+        AttributeValue {
+            s: Some(key.to_string()),
+            ..Default::default()
+        },
+    );
+    item_key
+}
+
+/// Item returned to an earlier request with the same idempotency key, if the key has not expired.
+/// DynamoDB only removes expired keys eventually, so the expiry is checked here too.
+fn idempotent_replay(
+    key: Option<model::idempotency_key::IdempotencyKey>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Option<model::basic_item::BasicItem> {
+    key.and_then(|key| if key.expires_at > now.timestamp() {
+        Some(key.item)
+    } else {
+        None
+    })
+}
+
+fn find_idempotency_key<D: DynamoDb>(
+    client: &D,
+    user_id: &model::UserId,
+    key: &str,
+) -> Option<model::idempotency_key::IdempotencyKey> {
+    let get_item = GetItemInput {
+        key: idempotency_key_key(user_id, key),
+        table_name: env::var("idempotency_keys_table").unwrap(),
+        consistent_read: Some(true),
         ..Default::default()
+    };
+    client.get_item(&get_item).unwrap().item.map(|key| {
+        serde_dynamodb::from_hashmap(key).unwrap()
     })
 }
 
+/// Write of an idempotency key, claimed for the item created with it, failing if another request
+/// claimed the key and it has not expired.
+fn idempotency_key_write(
+    user_id: &model::UserId,
+    key: &str,
+    item: &model::basic_item::BasicItem,
+    now: chrono::DateTime<chrono::Utc>,
+) -> TransactWriteItem {
+    let idempotency_key = model::idempotency_key::IdempotencyKey {
+        uid: user_id.clone(),
+        key: key.to_string(),
+        item: item.clone(),
+        expires_at: (now + chrono::Duration::hours(IDEMPOTENCY_WINDOW_HOURS)).timestamp(),
+    };
+    let mut names = HashMap::new();
+    names.insert("#key".to_string(), "key".to_string());
+    names.insert("#expires_at".to_string(), "expires_at".to_string());
+    let mut values = HashMap::new();
+    values.insert(
+        ":now".to_string(),
+        AttributeValue {
+            n: Some(now.timestamp().to_string()),
+            ..Default::default()
+        },
+    );
+    TransactWriteItem {
+        put: Some(Put {
+            item: serde_dynamodb::to_hashmap(&idempotency_key).unwrap(),
+            table_name: env::var("idempotency_keys_table").unwrap(),
+            condition_expression: Some(
+                "attribute_not_exists(#key) OR #expires_at <= :now".to_string(),
+            ),
+            expression_attribute_names: Some(names),
+            expression_attribute_values: Some(values),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+fn condition_failed(reason: Option<&Option<String>>) -> bool {
+    reason.and_then(|reason| reason.as_ref()).map(|reason| reason.as_str()) ==
+        Some(CONDITIONAL_CHECK_FAILED)
+}
+
+/// Saves a new item and claims its idempotency key in one transaction, so that the key is only
+/// claimed if the item is written and concurrent retries can not both create it. Returns the
+/// item of the request that claimed the key first, if any, instead of saving the item.
+fn save_claiming_idempotency_key<D: DynamoDb>(
+    client: &D,
+    table: &str,
+    user_id: &model::UserId,
+    key: &str,
+    item: &model::basic_item::BasicItem,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<Option<model::basic_item::BasicItem>, VersionConflict> {
+    let writes = vec![
+        item_write(table, item),
+        idempotency_key_write(user_id, key, item, now),
+    ];
+    match transact_write(client, writes) {
+        Ok(()) => {
+            super::history::record(client, None, item);
+            super::webhook::notify(client, &[(None, item)]);
+            Ok(None)
+        }
+        Err(ref reasons) if condition_failed(reasons.get(1)) => {
+            Ok(Some(
+                idempotent_replay(find_idempotency_key(client, user_id, key), now)
+                    .unwrap_or_else(|| panic!("idempotency key {} vanished", key)),
+            ))
+        }
+        Err(ref reasons) if condition_failed(reasons.get(0)) => {
+            Err(VersionConflict(item.id.to_string()))
+        }
+        Err(reasons) => {
+            let reasons: Vec<String> = reasons.into_iter().filter_map(|reason| reason).collect();
+            panic!("error saving item {}: {}", item.id.to_string(), reasons.join(", "))
+        }
+    }
+}
+
+/// What to do with the children of an item when it is completed or deleted, read from the
+/// `children` query string parameter.
+#[derive(Debug, PartialEq)]
+enum ChildrenPolicy {
+    /// children are left untouched on completion, and attached to the grand parent on deletion
+    Keep,
+    /// children are completed or deleted with their parent
+    Cascade,
+}
+impl ChildrenPolicy {
+    fn from_event(event: &crowbar::Value) -> ChildrenPolicy {
+        match event["queryStringParameters"]["children"].as_str() {
+            Some("cascade") => ChildrenPolicy::Cascade,
+            _ => ChildrenPolicy::Keep,
+        }
+    }
+}
+
+/// Lists the items of the user and of the projects shared with the user, restricted to those
+/// matching the `filter` query string parameter if present. Items in the trash are listed instead
+/// of the others with `trash=true`.
+pub fn list(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::api::ItemList,
+        SerializableError,
+    >,
+> {
+    let table = env::var("table").unwrap();
+    let client = dynamodb_client();
+    let user_id = authorized_user_id(event);
+    let filter = match event["queryStringParameters"]["filter"].as_str() {
+        Some(filter) => {
+            let tz = user::current_user(&client, &user_id).timezone();
+            match query::Query::parse(filter, &tz) {
+                Ok(query) => query,
+                Err(error) => return bad_request(error.into()),
+            }
+        }
+        None => query::Query::default(),
+    };
+
+    let sort_keys = match event["queryStringParameters"]["sort"].as_str() {
+        Some(sort) => {
+            match sort::parse_sort(sort) {
+                Ok(sort_keys) => sort_keys,
+                Err(error) => return bad_request(error.into()),
+            }
+        }
+        None => vec![],
+    };
+
+    let trash = event["queryStringParameters"]["trash"].as_str() == Some("true");
+    let filter_expression = filter.to_filter_expression();
+    let mut query_output: Vec<model::basic_item::BasicItem> =
+        filtered_user_items(&client, &table, &user_id, filter_expression)
+            .into_iter()
+            .chain(member::shared_items(&client, &table, &user_id))
+            .filter(|item| item.deleted_at.is_some() == trash)
+            .filter(|item| filter.matches(item))
+            .collect();
+    sort::sort_items(&mut query_output, &sort_keys);
+    ok(model::api::ItemList { items: query_output })
+}
+
+#[derive(Deserialize)]
+struct CostInput {
+    category: String,
+    cost: u32,
+}
+
 #[derive(Deserialize)]
-struct ItemInput {
+pub struct ItemInput {
     title: Option<String>,
     description: Option<String>,
     project_id: Option<String>,
+    parent_id: Option<String>,
+    flagged: Option<bool>,
+    status: Option<String>,
+    contexts: Option<Vec<String>>,
+    costs: Option<Vec<CostInput>>,
+    due: Option<String>,
+    assignee: Option<String>,
+    reminders: Option<Vec<u32>>,
+    /// line typed by the user, like `Call Bob tomorrow 3pm #work !`, filling the fields not
+    /// given, for `api_todo_add` only
+    quick: Option<String>,
 }
 impl ItemInput {
-    fn to_new_item(
+    /// Input of an item having only a title and a description, like the steps of a template.
+    pub fn titled(
+        title: &str,
+        description: &str,
+        project_id: &model::ProjectId,
+        parent_id: Option<&model::ItemId>,
+    ) -> ItemInput {
+        ItemInput {
+            title: Some(title.to_string()),
+            description: Some(description.to_string()),
+            project_id: Some(project_id.to_string()),
+            parent_id: parent_id.map(|parent_id| parent_id.to_string()),
+            flagged: None,
+            status: None,
+            contexts: None,
+            costs: None,
+            due: None,
+            assignee: None,
+            reminders: None,
+            quick: None,
+        }
+    }
+
+    pub fn project_id(&self) -> Option<&str> {
+        self.project_id.as_ref().map(|project_id| project_id.as_str())
+    }
+
+    pub fn to_new_item(
         &self,
         user_id: model::UserId,
+        tz: &chrono_tz::Tz,
     ) -> Result<model::basic_item::BasicItem, SerializableError> {
         let id = model::ItemId(format!("{}", uuid::Uuid::new_v4().hyphenated()));
         let title = self.title.clone().ok_or_else(
             || MissingField("title").into(),
         );
         let description = self.description.clone().unwrap_or_else(|| "".to_string());
-        let input_project_id = uuid::Uuid::parse_str(
+        let project_id = model::ProjectId(parse_uuid(
             &self.project_id.clone().unwrap_or_else(|| "".to_string()),
-        ).map_err(|err| {
-            InvalidUUIDError {
-                uuid: self.project_id.clone().unwrap_or_else(|| "".to_string()),
-                field: "project_id",
-                uuid_error: err,
-            }
-        })?;
-        let project_id = model::ProjectId(format!("{}", input_project_id));
+            "project_id",
+        )?);
+        let parent_id = self.parent_id()?;
+        let contexts = self.contexts()?.unwrap_or_else(|| vec![]);
+        let costs = self.costs()?.unwrap_or_else(|| vec![]);
+        let due = self.due(tz)?.unwrap_or(None);
+        let reminders = self.reminders()?.unwrap_or_else(|| vec![]);
         title.map(|title| {
             model::basic_item::BasicItem {
                 uid: user_id,
                 description: description,
-                flagged: false,
+                flagged: self.flagged.unwrap_or(false),
                 id: id,
                 project_id: project_id,
-                status: model::State { name: "".to_string() },
+                parent_id: parent_id,
+                contexts: contexts,
+                costs: costs,
+                due: due,
+                position: 0,
+                created_at: None,
+                created_by: None,
+                updated_at: None,
+                updated_by: None,
+                completed_at: None,
+                version: 0,
+                deleted_at: None,
+                assignee: self.assignee().unwrap_or(None),
+                attachments: vec![],
+                reminders: reminders,
+                status: model::State {
+                    name: self.status.clone().unwrap_or_else(|| "".to_string()),
+                },
                 title: title,
             }
         })
     }
+
+    /// Fills the fields not given from the `quick` line. Its project is found by name among the
+    /// projects of the user and the projects shared with them, and its contexts among the
+    /// contexts of the owner of the project.
+    fn expand_quick<D: DynamoDb>(
+        mut self,
+        client: &D,
+        user_id: &model::UserId,
+        tz: &chrono_tz::Tz,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<ItemInput, SerializableError> {
+        let quick = match self.quick.take() {
+            Some(quick) => super::quick::parse(&quick, tz, now).map_err(InvalidQuickAdd)?,
+            None => return Ok(self),
+        };
+        if let (None, Some(name)) = (self.project_id.clone(), quick.project) {
+            let mut projects = super::project::user_projects(
+                client,
+                &env::var("projects_table").unwrap(),
+                user_id,
+            );
+            projects.extend(member::shared_projects(client, user_id));
+            let project = projects
+                .into_iter()
+                .find(|project| project.name.to_lowercase() == name.to_lowercase())
+                .ok_or_else(|| InvalidQuickAdd(format!("unknown project '#{}'", name)))?;
+            self.project_id = Some(project.id.to_string());
+        }
+        if !quick.contexts.is_empty() {
+            let owner = self.project_id
+                .as_ref()
+                .and_then(|project_id| member::find_membership(client, user_id, project_id))
+                .map(|member| member.owner)
+                .unwrap_or_else(|| user_id.clone());
+            let known = super::context::user_contexts(
+                client,
+                &env::var("contexts_table").unwrap(),
+                &owner,
+            );
+            let mut contexts = self.contexts.take().unwrap_or_else(|| vec![]);
+            for name in &quick.contexts {
+                let context = known
+                    .iter()
+                    .find(|context| context.name.to_lowercase() == name.to_lowercase())
+                    .ok_or_else(|| InvalidQuickAdd(format!("unknown context '{}'", name)))?;
+                contexts.push(context.id.to_string());
+            }
+            self.contexts = Some(contexts);
+        }
+        if self.title.is_none() {
+            self.title = Some(quick.title);
+        }
+        if self.due.is_none() {
+            self.due = quick.due.map(|due| due.to_rfc3339());
+        }
+        if self.flagged.is_none() && quick.flagged {
+            self.flagged = Some(true);
+        }
+        Ok(self)
+    }
+
+    /// An empty `parent_id` detaches the item from its parent.
+    fn parent_id(&self) -> Result<Option<model::ItemId>, SerializableError> {
+        match self.parent_id {
+            None => Ok(None),
+            Some(ref parent_id) if parent_id.is_empty() => Ok(None),
+            Some(ref parent_id) => Ok(Some(model::ItemId(parse_uuid(parent_id, "parent_id")?))),
+        }
+    }
+
+    fn contexts(&self) -> Result<Option<Vec<model::ContextId>>, SerializableError> {
+        match self.contexts {
+            None => Ok(None),
+            Some(ref contexts) => {
+                let mut context_ids = vec![];
+                for context_id in contexts {
+                    let context_id = model::ContextId(parse_uuid(context_id, "contexts")?);
+                    if !context_ids.contains(&context_id) {
+                        context_ids.push(context_id);
+                    }
+                }
+                Ok(Some(context_ids))
+            }
+        }
+    }
+
+    /// Costs are summed by category.
+    fn costs(&self) -> Result<Option<Vec<model::Cost>>, SerializableError> {
+        match self.costs {
+            None => Ok(None),
+            Some(ref costs) => {
+                let mut item_costs: Vec<model::Cost> = vec![];
+                for cost in costs {
+                    let category = model::CostCategoryId(parse_uuid(&cost.category, "costs")?);
+                    match item_costs.iter().position(|known| known.category == category) {
+                        Some(index) => {
+                            item_costs[index].cost = item_costs[index]
+                                .cost
+                                .checked_add(cost.cost)
+                                .ok_or_else(|| CostOverflow(cost.category.clone()))?
+                        }
+                        None => {
+                            item_costs.push(model::Cost {
+                                category: category,
+                                cost: cost.cost,
+                            })
+                        }
+                    }
+                }
+                Ok(Some(item_costs))
+            }
+        }
+    }
+
+    /// Days are interpreted in the timezone of the user, and an empty `due` removes the due date.
+    fn due(
+        &self,
+        tz: &chrono_tz::Tz,
+    ) -> Result<Option<Option<chrono::DateTime<chrono::Utc>>>, SerializableError> {
+        match self.due {
+            None => Ok(None),
+            Some(ref due) if due.is_empty() => Ok(Some(None)),
+            Some(ref due) => {
+                let date = parse_date(due, tz).ok_or_else(|| {
+                    InvalidDate {
+                        date: due.clone(),
+                        field: "due",
+                    }
+                })?;
+                Ok(Some(Some(date)))
+            }
+        }
+    }
+
+    /// Reminders are sorted, the earliest first.
+    fn reminders(&self) -> Result<Option<Vec<u32>>, SerializableError> {
+        match self.reminders {
+            None => Ok(None),
+            Some(ref reminders) => {
+                if reminders.len() > MAX_REMINDERS {
+                    return Err(InvalidReminders("too many reminders").into());
+                }
+                if reminders.iter().any(
+                    |offset| *offset > MAX_REMINDER_OFFSET_MINUTES,
+                )
+                {
+                    return Err(InvalidReminders("offset too large").into());
+                }
+                let mut reminders = reminders.clone();
+                reminders.sort_by(|a, b| b.cmp(a));
+                reminders.dedup();
+                Ok(Some(reminders))
+            }
+        }
+    }
+
+    /// An empty `assignee` unassigns the item.
+    fn assignee(&self) -> Option<Option<model::UserId>> {
+        match self.assignee {
+            None => None,
+            Some(ref assignee) if assignee.is_empty() => Some(None),
+            Some(ref assignee) => Some(Some(assignee.clone().into())),
+        }
+    }
+
+    pub fn apply_to(
+        &self,
+        item: &model::basic_item::BasicItem,
+        tz: &chrono_tz::Tz,
+    ) -> Result<model::basic_item::BasicItem, SerializableError> {
+        let project_id = match self.project_id {
+            Some(ref project_id) => model::ProjectId(parse_uuid(project_id, "project_id")?),
+            None => item.project_id.clone(),
+        };
+        let parent_id = match self.parent_id {
+            Some(_) => self.parent_id()?,
+            None => item.parent_id.clone(),
+        };
+        Ok(model::basic_item::BasicItem {
+            uid: item.uid.clone(),
+            id: item.id.clone(),
+            title: self.title.clone().unwrap_or_else(|| item.title.clone()),
+            description: self.description.clone().unwrap_or_else(
+                || item.description.clone(),
+            ),
+            status: self.status
+                .clone()
+                .map(|name| model::State { name: name })
+                .unwrap_or_else(|| item.status.clone()),
+            flagged: self.flagged.unwrap_or(item.flagged),
+            project_id: project_id,
+            parent_id: parent_id,
+            contexts: self.contexts()?.unwrap_or_else(|| item.contexts.clone()),
+            costs: self.costs()?.unwrap_or_else(|| item.costs.clone()),
+            due: self.due(tz)?.unwrap_or(item.due),
+            position: item.position,
+            created_at: item.created_at,
+            created_by: item.created_by.clone(),
+            updated_at: item.updated_at,
+            updated_by: item.updated_by.clone(),
+            completed_at: item.completed_at,
+            version: item.version,
+            deleted_at: item.deleted_at,
+            assignee: self.assignee().unwrap_or_else(|| item.assignee.clone()),
+            attachments: item.attachments.clone(),
+            reminders: self.reminders()?.unwrap_or_else(|| item.reminders.clone()),
+        })
+    }
 }
 
 pub fn add(
@@ -144,47 +913,73 @@ pub fn add(
         SerializableError,
     >,
 > {
-    let data_result: Result<ItemInput, SerializableError> = event["body"]
-        .as_str()
-        .ok_or_else(|| MissingBody().into())
-        .and_then(|valid_body| {
-            serde_json::from_slice::<ItemInput>(valid_body.as_bytes())
-                .map_err(|err| ParsingError { serde_error: err }.into())
-        });
-    match data_result.and_then(|item| {
-        item.to_new_item(
-            event["requestContext"]["authorizer"]["user_id"]
-                .as_str()
-                .unwrap()
-                .to_string()
-                .into(),
-        )
-    }) {
-        Ok(item) => {
-            let table = env::var("table").unwrap();
-            let put_item = PutItemInput {
-                item: serde_dynamodb::to_hashmap(&item).unwrap(),
-                table_name: table,
-                ..Default::default()
-            };
-            let provider = DefaultCredentialsProvider::new().unwrap();
-            let client =
-                DynamoDbClient::new(default_tls_client().unwrap(), provider, Region::UsEast1);
-            client.put_item(&put_item).unwrap();
-            Ok(crowbar::ApiGatewayResponse {
-                status_code: http::StatusCode::OK,
-                body: Some((Ok(item), mime::APPLICATION_JSON)),
-                ..Default::default()
-            })
+    let idempotency_key = match header(event, "Idempotency-Key") {
+        Some(key) if key.is_empty() || key.len() > IDEMPOTENCY_KEY_MAX_LENGTH => {
+            return bad_request(InvalidIdempotencyKey(key.to_string()).into())
         }
-        Err(error) => {
-            Ok(crowbar::ApiGatewayResponse {
-                status_code: http::StatusCode::BAD_REQUEST,
-                body: Some((Err(error), mime::APPLICATION_JSON)),
-                ..Default::default()
-            })
+        key => key.map(|key| key.to_string()),
+    };
+    let table = env::var("table").unwrap();
+    let client = dynamodb_client();
+    let actor = authorized_actor(event);
+    let now = chrono::Utc::now();
+    let tz = user::current_user(&client, &actor.user_id).timezone();
+    let data_result = parse_body::<ItemInput>(event).and_then(|input| {
+        input.expand_quick(&client, &actor.user_id, &tz, now)
+    });
+    // items added to a project shared with the user are stored with its owner
+    let project_id = data_result.as_ref().ok().and_then(|input| input.project_id()).map(
+        |project_id| project_id.to_string(),
+    );
+    let owner = match member::project_owner(
+        &client,
+        &actor.user_id,
+        project_id.as_ref().map(|project_id| project_id.as_str()),
+    ) {
+        Ok(owner) => owner,
+        Err(denied) => return denied.response("project", project_id.unwrap_or_default()),
+    };
+    if let Some(ref key) = idempotency_key {
+        let replay = find_idempotency_key(&client, &actor.user_id, key);
+        if let Some(item) = idempotent_replay(replay, now) {
+            let tag = etag(&item);
+            return with_header(ok(item), "ETag", tag);
         }
+    }
+    let items = user_items(&client, &table, &owner);
 
+    match data_result
+        .and_then(|item| item.to_new_item(owner.clone(), &tz))
+        .and_then(|mut item| {
+            if let Some(ref parent_id) = item.parent_id {
+                let visible =
+                    member::accessible_items(&client, &actor.user_id, items.clone(), false);
+                check_parent(&items, &visible, &item.id, parent_id)?;
+            }
+            check_contexts(&client, &item)?;
+            check_costs(&client, &item)?;
+            check_assignee(&client, &item)?;
+            item.position = sort::next_position(&items, &item.project_id);
+            item.created(&actor, now);
+            Ok(item)
+        }) {
+        Ok(item) => {
+            let saved = match idempotency_key {
+                Some(ref key) => {
+                    save_claiming_idempotency_key(&client, &table, &actor.user_id, key, &item, now)
+                }
+                None => save_item(&client, &table, None, &item).map(|()| None),
+            };
+            match saved {
+                Ok(None) => with_header(ok(item.clone()), "ETag", etag(&item)),
+                Ok(Some(first)) => {
+                    let tag = etag(&first);
+                    with_header(ok(first), "ETag", tag)
+                }
+                Err(error) => precondition_failed(error.into()),
+            }
+        }
+        Err(error) => bad_request(error),
     }
 }
 
@@ -199,48 +994,543 @@ pub fn get(
 > {
     let todo_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
     let table = env::var("table").unwrap();
-    let provider = DefaultCredentialsProvider::new().unwrap();
-    let client = DynamoDbClient::new(default_tls_client().unwrap(), provider, Region::UsEast1);
-    let todo_filter = model::basic_item::BasicItemQueryInput {
-        uid: Some(
-            event["requestContext"]["authorizer"]["user_id"]
-                .as_str()
-                .unwrap()
-                .to_string()
-                .into(),
-        ),
-        id: Some(todo_id.clone().into()),
-        ..Default::default()
+    let client = dynamodb_client();
+    let query_output = member::find_item(&client, &table, &authorized_user_id(event), &todo_id);
+
+    if let Some((todo, _)) = query_output {
+        let tag = etag(&todo);
+        with_header(ok(todo), "ETag", tag)
+    } else {
+        not_found("todo", todo_id)
+    }
+}
+
+pub fn children(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::api::ItemList,
+        SerializableError,
+    >,
+> {
+    let todo_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let table = env::var("table").unwrap();
+    let client = dynamodb_client();
+    let user_id = authorized_user_id(event);
+    let owner = match member::item_owner(&client, &table, &user_id, &todo_id, false) {
+        Ok(owner) => owner,
+        Err(denied) => return denied.response("todo", todo_id),
     };
+    let items = user_items(&client, &table, &owner);
+    if !items.iter().any(|item| item.id.to_string() == todo_id) {
+        return not_found("todo", todo_id);
+    }
 
-    let query_output: Option<model::basic_item::BasicItem> =
-        client
-            .query(&todo_filter.to_query_input(table))
-            .unwrap()
-            .items
-            .unwrap_or_else(|| vec![])
-            .pop()
-            .map(|item| serde_dynamodb::from_hashmap(item).unwrap());
+    // children in projects not shared with the user are not visible
+    let children = items
+        .into_iter()
+        .filter(|item| item.parent_id.as_ref().map(|id| id.to_string()) == Some(todo_id.clone()))
+        .collect();
+    ok(model::api::ItemList {
+        items: member::accessible_items(&client, &user_id, children, false),
+    })
+}
 
-    if let Some(todo) = query_output {
-        Ok(crowbar::ApiGatewayResponse {
-            status_code: http::StatusCode::OK,
-            body: Some((Ok(todo), mime::APPLICATION_JSON)),
-            ..Default::default()
-        })
-    } else {
-        Ok(crowbar::ApiGatewayResponse {
-            status_code: http::StatusCode::NOT_FOUND,
-            body: Some((
+/// Updates an item. Children that could not be completed with it because they were changed
+/// concurrently are listed in the `X-Vleue-Skipped-Items` header.
+pub fn update(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::basic_item::BasicItem,
+        SerializableError,
+    >,
+> {
+    let todo_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let table = env::var("table").unwrap();
+    let client = dynamodb_client();
+    let actor = authorized_actor(event);
+    let now = chrono::Utc::now();
+    let owner = match member::item_owner(&client, &table, &actor.user_id, &todo_id, true) {
+        Ok(owner) => owner,
+        Err(denied) => return denied.response("todo", todo_id),
+    };
+    let items = user_items(&client, &table, &owner);
+    let existing = match items.iter().find(|item| item.id.to_string() == todo_id) {
+        Some(item) => item,
+        None => return not_found("todo", todo_id),
+    };
+    if let Err(error) = check_if_match(event, existing) {
+        return precondition_failed(error.into());
+    }
+
+    let data_result: Result<ItemInput, SerializableError> = parse_body(event);
+    let tz = user::current_user(&client, &existing.uid).timezone();
+    let updated = match data_result.and_then(|input| input.apply_to(existing, &tz)).and_then(
+        |mut item| {
+            if let Some(ref parent_id) = item.parent_id {
+                if existing.parent_id.as_ref() != Some(parent_id) {
+                    let visible =
+                        member::accessible_items(&client, &actor.user_id, items.clone(), false);
+                    check_parent(&items, &visible, &item.id, parent_id)?;
+                }
+            }
+            check_contexts(&client, &item)?;
+            check_costs(&client, &item)?;
+            // a former member can stay assigned until the assignee or project changes
+            if item.assignee != existing.assignee || item.project_id != existing.project_id {
+                check_assignee(&client, &item)?;
+            }
+            if item.project_id != existing.project_id {
+                item.position = sort::next_position(&items, &item.project_id);
+            }
+            item.touch(&actor, now);
+            Ok(item)
+        },
+    ) {
+        Ok(item) => item,
+        Err(error) => return bad_request(error),
+    };
+    // an item can only be moved to a project the user can edit
+    if updated.project_id != existing.project_id &&
+        !member::role(&client, &actor.user_id, &owner, &updated.project_id)
+            .map(|role| role.can_edit())
+            .unwrap_or(false)
+    {
+        return forbidden("project", updated.project_id.to_string());
+    }
+
+    if let Err(error) = save_item(&client, &table, Some(existing), &updated) {
+        return precondition_failed(error.into());
+    }
+    let mut skipped = vec![];
+    if updated.status.is_done() && !existing.status.is_done() &&
+        ChildrenPolicy::from_event(event) == ChildrenPolicy::Cascade
+    {
+        // children in projects the user can not edit are left untouched
+        let editable = member::accessible_items(&client, &actor.user_id, items.clone(), true);
+        for child in descendants(&editable, &updated.id) {
+            if !child.status.is_done() {
+                let mut completed = child.clone();
+                completed.status = model::State::done();
+                completed.touch(&actor, now);
+                // a child changed concurrently keeps the state given by the other request
+                if save_item(&client, &table, Some(child), &completed).is_err() {
+                    skipped.push(child.id.clone());
+                }
+            }
+        }
+    }
+
+    let tag = etag(&updated);
+    with_skipped_items(with_header(ok(updated), "ETag", tag), &skipped)
+}
+
+/// Moves an item to the trash, from where it can be restored until it is purged. Children left
+/// unchanged because they were changed concurrently are listed in the `X-Vleue-Skipped-Items`
+/// header.
+pub fn delete(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::basic_item::BasicItem,
+        SerializableError,
+    >,
+> {
+    let todo_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let table = env::var("table").unwrap();
+    let client = dynamodb_client();
+    let actor = authorized_actor(event);
+    let owner = match member::item_owner(&client, &table, &actor.user_id, &todo_id, true) {
+        Ok(owner) => owner,
+        Err(denied) => return denied.response("todo", todo_id),
+    };
+    let items = user_items(&client, &table, &owner);
+    let existing = match items.iter().find(|item| item.id.to_string() == todo_id) {
+        Some(item) => item,
+        None => return not_found("todo", todo_id),
+    };
+    if let Err(error) = check_if_match(event, existing) {
+        return precondition_failed(error.into());
+    }
+    let now = chrono::Utc::now();
+    let mut trashed = existing.clone();
+    trashed.deleted_at = Some(now);
+    trashed.touch(&actor, now);
+    if let Err(error) = save_item(&client, &table, Some(existing), &trashed) {
+        return precondition_failed(error.into());
+    }
+
+    // children changed concurrently are left as the other request saved them, and reported
+    let mut skipped = vec![];
+    match ChildrenPolicy::from_event(event) {
+        ChildrenPolicy::Cascade => {
+            // trashed at the same time as their parent, so that they are restored with it, unless
+            // they are in projects the user can not edit
+            let editable = member::accessible_items(&client, &actor.user_id, items.clone(), true);
+            for child in descendants(&editable, &existing.id) {
+                let mut trashed_child = child.clone();
+                trashed_child.deleted_at = Some(now);
+                trashed_child.touch(&actor, now);
+                if save_item(&client, &table, Some(child), &trashed_child).is_err() {
+                    skipped.push(child.id.clone());
+                }
+            }
+        }
+        ChildrenPolicy::Keep => {
+            // children in projects the user can not edit are moved too, so that none is left
+            // under an item in the trash
+            for child in items.iter().filter(|item| {
+                item.parent_id.as_ref() == Some(&existing.id)
+            })
+            {
+                let mut moved = child.clone();
+                moved.parent_id = existing.parent_id.clone();
+                moved.touch(&actor, now);
+                if save_item(&client, &table, Some(child), &moved).is_err() {
+                    skipped.push(child.id.clone());
+                }
+            }
+        }
+    }
+
+    let tag = etag(&trashed);
+    with_skipped_items(with_header(ok(trashed), "ETag", tag), &skipped)
+}
+
+/// Lists the items assigned to the user, in all the projects of the user or shared with the user.
+pub fn assigned(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::api::ItemList,
+        SerializableError,
+    >,
+> {
+    let sort_keys = match event["queryStringParameters"]["sort"].as_str() {
+        Some(sort) => {
+            match sort::parse_sort(sort) {
+                Ok(sort_keys) => sort_keys,
+                Err(error) => return bad_request(error.into()),
+            }
+        }
+        None => vec![],
+    };
+    let table = env::var("table").unwrap();
+    let client = dynamodb_client();
+    let user_id = authorized_user_id(event);
+    let mut items: Vec<model::basic_item::BasicItem> = user_items(&client, &table, &user_id)
+        .into_iter()
+        .chain(member::shared_items(&client, &table, &user_id))
+        .filter(|item| is_assigned_to(item, &user_id))
+        .collect();
+    sort::sort_items(&mut items, &sort_keys);
+    ok(model::api::ItemList { items: items })
+}
+
+/// Whether an item not in the trash is assigned to the user.
+fn is_assigned_to(item: &model::basic_item::BasicItem, user_id: &model::UserId) -> bool {
+    item.deleted_at.is_none() && item.assignee.as_ref() == Some(user_id)
+}
+
+#[derive(Deserialize)]
+struct MoveInput {
+    /// item after which to move, `None` to move first in the project
+    after: Option<String>,
+}
+
+/// Moves an item in the manual order of its project.
+pub fn move_item(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::basic_item::BasicItem,
+        SerializableError,
+    >,
+> {
+    let todo_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let table = env::var("table").unwrap();
+    let client = dynamodb_client();
+    let actor = authorized_actor(event);
+    let owner = match member::item_owner(&client, &table, &actor.user_id, &todo_id, true) {
+        Ok(owner) => owner,
+        Err(denied) => return denied.response("todo", todo_id),
+    };
+    let items = user_items(&client, &table, &owner);
+    let existing = match items.iter().find(|item| item.id.to_string() == todo_id) {
+        Some(item) => item,
+        None => return not_found("todo", todo_id),
+    };
+    if let Err(error) = check_if_match(event, existing) {
+        return precondition_failed(error.into());
+    }
+
+    let after = match parse_body::<MoveInput>(event).and_then(|input| match input.after {
+        None => Ok(None),
+        Some(after) => {
+            let after = model::ItemId(parse_uuid(&after, "after")?);
+            if items.iter().any(|item| {
+                item.id == after && item.project_id == existing.project_id
+            })
+            {
+                Ok(Some(after))
+            } else {
                 Err(
                     NotFound {
                         object: "todo",
-                        id: todo_id,
+                        id: after.to_string(),
                     }.into(),
-                ),
-                mime::APPLICATION_JSON,
-            )),
-            ..Default::default()
-        })
+                )
+            }
+        }
+    }) {
+        Ok(after) => after,
+        Err(error) => return bad_request(error),
+    };
+
+    let now = chrono::Utc::now();
+    let mut moved = existing.clone();
+    for mut item in sort::move_after(&items, existing, after.as_ref()) {
+        item.touch(&actor, now);
+        let previous = items.iter().find(|previous| previous.id == item.id);
+        match save_item(&client, &table, previous, &item) {
+            Ok(()) if item.id == existing.id => moved = item,
+            Ok(()) => (),
+            Err(error) => return precondition_failed(error.into()),
+        }
+    }
+    let tag = etag(&moved);
+    with_header(ok(moved), "ETag", tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: &str, parent_id: Option<&str>) -> model::basic_item::BasicItem {
+        model::basic_item::BasicItem {
+            id: model::ItemId(id.to_string()),
+            title: id.to_string(),
+            parent_id: parent_id.map(|parent_id| model::ItemId(parent_id.to_string())),
+            ..model::basic_item::test_item()
+        }
+    }
+
+    #[test]
+    fn can_find_all_descendants() {
+        let items = vec![
+            item("a", None),
+            item("b", Some("a")),
+            item("c", Some("b")),
+            item("d", None),
+        ];
+
+        let found: Vec<String> = descendants(&items, &model::ItemId("a".to_string()))
+            .iter()
+            .map(|item| item.id.to_string())
+            .collect();
+
+        assert_eq!(found, vec!["b", "c"]);
+    }
+
+    #[test]
+    fn should_reject_parent_from_descendants() {
+        let items = vec![item("a", None), item("b", Some("a")), item("c", Some("b"))];
+
+        let result = check_parent(
+            &items,
+            &items,
+            &model::ItemId("a".to_string()),
+            &model::ItemId("c".to_string()),
+        );
+
+        assert!(result.is_err());
+        assert_eq!(
+            format!("{}", result.unwrap_err().0),
+            "Parent 'c' would create a cycle"
+        );
+    }
+
+    #[test]
+    fn should_reject_unknown_parent() {
+        let items = vec![item("a", None)];
+
+        let result = check_parent(
+            &items,
+            &items,
+            &model::ItemId("a".to_string()),
+            &model::ItemId("z".to_string()),
+        );
+
+        assert!(result.is_err());
+        assert_eq!(format!("{}", result.unwrap_err().0), "Invalid Parent: 'z'");
+    }
+
+    #[test]
+    fn should_not_reach_private_children_of_shared_parents() {
+        let mut private = item("b", Some("a"));
+        private.project_id = model::ProjectId("p2".to_string());
+        let items = vec![item("a", None), private, item("c", None)];
+        // shared with the user as the project `p1` only
+        let visible: Vec<model::basic_item::BasicItem> = items
+            .iter()
+            .filter(|item| item.project_id == model::ProjectId("p1".to_string()))
+            .cloned()
+            .collect();
+
+        let result = check_parent(
+            &items,
+            &visible,
+            &model::ItemId("c".to_string()),
+            &model::ItemId("b".to_string()),
+        );
+
+        assert!(descendants(&visible, &model::ItemId("a".to_string())).is_empty());
+        assert_eq!(format!("{}", result.unwrap_err().0), "Invalid Parent: 'b'");
+    }
+
+    #[test]
+    fn should_deduplicate_contexts() {
+        let input = ItemInput {
+            title: None,
+            description: None,
+            project_id: None,
+            parent_id: None,
+            flagged: None,
+            status: None,
+            contexts: Some(vec![
+                "936da01f-9abd-4d9d-80c7-02af85c822a8".to_string(),
+                "936da01f-9abd-4d9d-80c7-02af85c822a8".to_string(),
+            ]),
+            costs: None,
+            due: None,
+            assignee: None,
+            reminders: None,
+            quick: None,
+        };
+
+        let contexts = input.contexts();
+
+        assert!(contexts.is_ok());
+        assert_eq!(contexts.ok().unwrap().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn should_reject_overflowing_costs() {
+        let category = "936da01f-9abd-4d9d-80c7-02af85c822a8".to_string();
+        let input = ItemInput {
+            title: None,
+            description: None,
+            project_id: None,
+            parent_id: None,
+            flagged: None,
+            status: None,
+            contexts: None,
+            costs: Some(vec![
+                CostInput {
+                    category: category.clone(),
+                    cost: u32::max_value(),
+                },
+                CostInput {
+                    category: category.clone(),
+                    cost: 1,
+                },
+            ]),
+            due: None,
+            assignee: None,
+            reminders: None,
+            quick: None,
+        };
+
+        assert!(input.costs().is_err());
+    }
+
+    #[test]
+    fn should_validate_reminders() {
+        let input = |reminders: Vec<u32>| ItemInput {
+            title: None,
+            description: None,
+            project_id: None,
+            parent_id: None,
+            flagged: None,
+            status: None,
+            contexts: None,
+            costs: None,
+            due: None,
+            assignee: None,
+            reminders: Some(reminders),
+            quick: None,
+        };
+
+        assert_eq!(
+            input(vec![0, 60, 1440, 60]).reminders().ok().unwrap(),
+            Some(vec![1440, 60, 0])
+        );
+        assert!(input(vec![0, 1, 2, 3, 4, 5]).reminders().is_err());
+        assert!(
+            input(vec![MAX_REMINDER_OFFSET_MINUTES + 1])
+                .reminders()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn should_match_current_etag() {
+        let mut item = item("a", None);
+        item.version = 3;
+
+        assert!(if_match("\"3\"", &item));
+        assert!(if_match("W/\"3\"", &item));
+        assert!(if_match("\"2\", \"3\"", &item));
+        assert!(if_match("*", &item));
+        assert!(!if_match("\"2\"", &item));
+    }
+
+    #[test]
+    fn should_replay_items_of_unexpired_idempotency_keys() {
+        let now = chrono::Utc::now();
+        let key = |expires_at: chrono::DateTime<chrono::Utc>| {
+            Some(model::idempotency_key::IdempotencyKey {
+                uid: model::UserId("u1".to_string()),
+                key: "k1".to_string(),
+                item: item("a", None),
+                expires_at: expires_at.timestamp(),
+            })
+        };
+
+        assert_eq!(
+            idempotent_replay(key(now + chrono::Duration::hours(1)), now)
+                .map(|item| item.id.to_string()),
+            Some("a".to_string())
+        );
+        assert!(idempotent_replay(key(now - chrono::Duration::hours(1)), now).is_none());
+        assert!(idempotent_replay(None, now).is_none());
+    }
+
+    #[test]
+    fn should_list_live_items_assigned_to_user() {
+        let user_id = model::UserId("u2".to_string());
+        let mut assigned = item("a", None);
+        assigned.assignee = Some(user_id.clone());
+        let mut trashed = assigned.clone();
+        trashed.deleted_at = Some(chrono::Utc::now());
+
+        assert!(is_assigned_to(&assigned, &user_id));
+        assert!(!is_assigned_to(&trashed, &user_id));
+        assert!(!is_assigned_to(&item("b", None), &user_id));
+    }
+
+    #[test]
+    fn should_tell_which_write_failed_its_condition() {
+        let reasons = vec![None, Some(CONDITIONAL_CHECK_FAILED.to_string())];
+
+        assert!(!condition_failed(reasons.get(0)));
+        assert!(condition_failed(reasons.get(1)));
+        assert!(!condition_failed(reasons.get(2)));
     }
 }
@@ -0,0 +1,232 @@
+//! Deleted items stay in the trash, from where they can be restored until they are purged.
+
+use std::env;
+use std::collections::HashMap;
+
+use crowbar;
+use chrono;
+use serde_dynamodb;
+
+use rusoto_dynamodb::{AttributeValue, DynamoDb, ScanInput};
+
+use model;
+
+use super::attachment;
+use super::common::*;
+use super::member;
+use super::todo;
+
+const DEFAULT_RETENTION_DAYS: i64 = 30;
+
+#[derive(Debug, Fail)]
+#[fail(display = "Item '{}' is not in the trash", _0)]
+struct NotInTrash(String);
+
+#[derive(Serialize, Debug)]
+pub struct PurgeReport {
+    pub purged: usize,
+}
+
+/// Number of days items stay in the trash, from the `trash_retention_days` environment variable.
+fn retention() -> chrono::Duration {
+    chrono::Duration::days(
+        env::var("trash_retention_days")
+            .ok()
+            .and_then(|days| days.parse().ok())
+            .unwrap_or(DEFAULT_RETENTION_DAYS),
+    )
+}
+
+/// Items to restore with a trashed item: the item itself and its descendants among `editable`
+/// that were trashed at the same time, by a cascading deletion. The item is detached from its
+/// parent if the parent is not restored.
+fn restored_items(
+    items: &[model::basic_item::BasicItem],
+    editable: &[model::basic_item::BasicItem],
+    item: &model::basic_item::BasicItem,
+) -> Vec<model::basic_item::BasicItem> {
+    let mut restored = item.clone();
+    if let Some(parent_id) = item.parent_id.clone() {
+        if !items.iter().any(|parent| {
+            parent.id == parent_id && parent.deleted_at.is_none()
+        })
+        {
+            restored.parent_id = None;
+        }
+    }
+    restored.deleted_at = None;
+
+    let mut found = vec![restored];
+    found.extend(
+        todo::descendants(editable, &item.id)
+            .into_iter()
+            .filter(|child| child.deleted_at == item.deleted_at)
+            .cloned()
+            .map(|mut child| {
+                child.deleted_at = None;
+                child
+            }),
+    );
+    found
+}
+
+/// Restores an item from the trash, with the children deleted with it. Children changed
+/// concurrently stay in the trash and are listed in the `X-Vleue-Skipped-Items` header.
+pub fn restore(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::api::ItemList,
+        SerializableError,
+    >,
+> {
+    let todo_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let table = env::var("table").unwrap();
+    let client = dynamodb_client();
+    let actor = authorized_actor(event);
+    let owner = match member::find_item(&client, &table, &actor.user_id, &todo_id) {
+        Some((_, ref role)) if !role.can_edit() => return forbidden("todo", todo_id),
+        Some((item, _)) => item.uid,
+        None => return not_found("todo", todo_id),
+    };
+    let items = todo::all_user_items(&client, &table, &owner);
+    let existing = match items.iter().find(|item| item.id.to_string() == todo_id) {
+        Some(item) => item,
+        None => return not_found("todo", todo_id),
+    };
+    if existing.deleted_at.is_none() {
+        return bad_request(NotInTrash(todo_id).into());
+    }
+
+    let now = chrono::Utc::now();
+    let mut restored = vec![];
+    let mut skipped = vec![];
+    // children in projects the user can not edit stay in the trash
+    let editable = member::accessible_items(&client, &actor.user_id, items.clone(), true);
+    for mut item in restored_items(&items, &editable, existing) {
+        item.touch(&actor, now);
+        let previous = items.iter().find(|previous| previous.id == item.id);
+        match todo::save_item(&client, &table, previous, &item) {
+            Ok(()) => restored.push(item),
+            Err(error) if item.id == existing.id => return precondition_failed(error.into()),
+            // a child changed concurrently stays as the other request saved it
+            Err(_) => skipped.push(item.id.clone()),
+        }
+    }
+    with_skipped_items(ok(model::api::ItemList { items: restored }), &skipped)
+}
+
+/// Items of all users that are in the trash since before `limit`.
+fn expired_items<D: DynamoDb>(
+    client: &D,
+    table: &str,
+    limit: chrono::DateTime<chrono::Utc>,
+) -> Vec<model::basic_item::BasicItem> {
+    let mut names = HashMap::new();
+    names.insert("#deleted_at".to_string(), "deleted_at".to_string());
+    let mut values = HashMap::new();
+    values.insert(
+        ":string".to_string(),
+        AttributeValue {
+            s: Some("S".to_string()),
+            ..Default::default()
+        },
+    );
+
+    let mut expired = vec![];
+    let mut start_key = None;
+    loop {
+        let scan_input = ScanInput {
+            table_name: table.to_string(),
+            filter_expression: Some("attribute_type(#deleted_at, :string)".to_string()),
+            expression_attribute_names: Some(names.clone()),
+            expression_attribute_values: Some(values.clone()),
+            exclusive_start_key: start_key,
+            ..Default::default()
+        };
+        let output = client.scan(&scan_input).unwrap();
+        expired.extend(
+            output
+                .items
+                .unwrap_or_else(|| vec![])
+                .into_iter()
+                .map(|item| serde_dynamodb::from_hashmap(item).unwrap())
+                .filter(|item: &model::basic_item::BasicItem| {
+                    item.deleted_at.map(|deleted_at| deleted_at < limit) == Some(true)
+                }),
+        );
+        start_key = output.last_evaluated_key;
+        if start_key.is_none() {
+            return expired;
+        }
+    }
+}
+
+/// Scheduled task removing the items that stayed in the trash longer than the retention period.
+pub fn purge(
+    _event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<PurgeReport> {
+    let table = env::var("table").unwrap();
+    let client = dynamodb_client();
+    let mut purged = 0;
+    for item in expired_items(&client, &table, chrono::Utc::now() - retention()) {
+        // an item restored since the scan has a new version and is not removed
+        match todo::remove_item(&client, &table, &item) {
+            Ok(()) => {
+                attachment::delete_contents(&item);
+                purged += 1
+            }
+            Err(error) => println!("{}", error),
+        }
+    }
+    Ok(PurgeReport { purged: purged })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn item(
+        id: &str,
+        parent_id: Option<&str>,
+        deleted_at: Option<u32>,
+    ) -> model::basic_item::BasicItem {
+        model::basic_item::BasicItem {
+            id: model::ItemId(id.to_string()),
+            title: id.to_string(),
+            parent_id: parent_id.map(|parent_id| model::ItemId(parent_id.to_string())),
+            version: 1,
+            deleted_at: deleted_at.map(|day| chrono::Utc.ymd(2026, 10, day).and_hms(0, 0, 0)),
+            ..model::basic_item::test_item()
+        }
+    }
+
+    #[test]
+    fn should_restore_children_deleted_with_their_parent() {
+        let items = vec![
+            item("a", None, Some(2)),
+            item("b", Some("a"), Some(2)),
+            item("c", Some("a"), Some(1)),
+            item("d", Some("b"), Some(2)),
+        ];
+
+        let restored = restored_items(&items, &items, &items[0]);
+
+        let ids: Vec<String> = restored.iter().map(|item| item.id.to_string()).collect();
+        assert_eq!(ids, vec!["a", "b", "d"]);
+        assert!(restored.iter().all(|item| item.deleted_at.is_none()));
+    }
+
+    #[test]
+    fn should_detach_item_from_trashed_parent() {
+        let items = vec![item("a", None, Some(1)), item("b", Some("a"), Some(2))];
+
+        let restored = restored_items(&items, &items, &items[1]);
+
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored[0].parent_id, None);
+    }
+}
@@ -0,0 +1,482 @@
+use std::env;
+use std::collections::HashMap;
+
+use crowbar;
+use chrono;
+use chrono_tz;
+use serde_dynamodb;
+
+use rusoto_dynamodb::{AttributeValue, DynamoDb, GetItemInput, PutItemInput};
+
+use model;
+
+use super::common::*;
+use super::feed;
+use super::reminder;
+
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid Timezone: '{}'", _0)]
+struct InvalidTimezone(String);
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid Channel: '{}'", _0)]
+struct InvalidChannel(String);
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid Verification Token")]
+struct InvalidVerificationToken;
+#[derive(Debug, Fail)]
+#[fail(display = "No Email Address To Verify")]
+struct NoEmailToVerify;
+#[derive(Debug, Fail)]
+#[fail(display = "Verification Email Already Sent, Retry After {}", _0)]
+struct VerificationCooldown(String);
+#[derive(Debug, Fail)]
+#[fail(display = "Verification Email Not Sent: {}", _0)]
+struct VerificationNotSent(String);
+
+/// Minimum delay between two verification emails to the same user.
+const VERIFICATION_COOLDOWN_MINUTES: i64 = 5;
+
+fn user_key(user_id: &model::UserId) -> HashMap<String, AttributeValue> {
+    let mut key = HashMap::new();
+    key.insert(
+        "user_id".to_string(),
+        AttributeValue {
+            s: Some(user_id.to_string()),
+            ..Default::default()
+        },
+    );
+    key
+}
+
+pub fn save_user<D: DynamoDb>(client: &D, user: &model::User) {
+    let put_item = PutItemInput {
+        item: serde_dynamodb::to_hashmap(user).unwrap(),
+        table_name: env::var("users_table").unwrap(),
+        ..Default::default()
+    };
+    client.put_item(&put_item).unwrap();
+}
+
+/// Returns the profile of a user, with default settings if it was never saved.
+pub fn current_user<D: DynamoDb>(client: &D, user_id: &model::UserId) -> model::User {
+    let get_item = GetItemInput {
+        key: user_key(user_id),
+        table_name: env::var("users_table").unwrap(),
+        ..Default::default()
+    };
+    client
+        .get_item(&get_item)
+        .unwrap()
+        .item
+        .map(|user| serde_dynamodb::from_hashmap(user).unwrap())
+        .unwrap_or_else(|| {
+            model::User {
+                user_id: user_id.clone(),
+                email: "".to_string(),
+                email_verified: false,
+                email_verification: None,
+                verification_sent_at: None,
+                tz: None,
+                feed_token: None,
+                reminder_channels: vec![],
+                push_endpoint: None,
+            }
+        })
+}
+
+/// Settings of a user as shown to them, without the feed token nor the verification hash.
+#[derive(Serialize, Debug)]
+pub struct Profile {
+    pub user_id: model::UserId,
+    pub email: String,
+    pub email_verified: bool,
+    pub verification_sent_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub tz: Option<String>,
+    pub reminder_channels: Vec<String>,
+    pub push_endpoint: Option<String>,
+}
+impl<'a> From<&'a model::User> for Profile {
+    fn from(user: &model::User) -> Profile {
+        Profile {
+            user_id: user.user_id.clone(),
+            email: user.email.clone(),
+            email_verified: user.email_verified,
+            verification_sent_at: user.verification_sent_at,
+            tz: user.tz.clone(),
+            reminder_channels: user.reminder_channels.clone(),
+            push_endpoint: user.push_endpoint.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct UserInput {
+    email: Option<String>,
+    tz: Option<String>,
+    reminder_channels: Option<Vec<String>>,
+    push_endpoint: Option<String>,
+}
+impl UserInput {
+    /// An empty `tz` resets the timezone to UTC. A new `email` has to be verified again.
+    fn apply_to(&self, user: &model::User) -> Result<model::User, SerializableError> {
+        let tz = match self.tz {
+            None => user.tz.clone(),
+            Some(ref tz) if tz.is_empty() => None,
+            Some(ref tz) => {
+                let timezone = tz.parse::<chrono_tz::Tz>().map_err(
+                    |_| InvalidTimezone(tz.clone()),
+                )?;
+                Some(timezone.name().to_string())
+            }
+        };
+        let email = self.email.clone().unwrap_or_else(|| user.email.clone());
+        let email_changed = email != user.email;
+        Ok(model::User {
+            user_id: user.user_id.clone(),
+            email: email,
+            email_verified: user.email_verified && !email_changed,
+            email_verification: if email_changed {
+                None
+            } else {
+                user.email_verification.clone()
+            },
+            verification_sent_at: user.verification_sent_at,
+            feed_token: user.feed_token.clone(),
+            tz: tz,
+            reminder_channels: self.reminder_channels()?.unwrap_or_else(
+                || user.reminder_channels.clone(),
+            ),
+            push_endpoint: self.push_endpoint()?.unwrap_or_else(
+                || user.push_endpoint.clone(),
+            ),
+        })
+    }
+
+    fn reminder_channels(&self) -> Result<Option<Vec<String>>, SerializableError> {
+        match self.reminder_channels {
+            None => Ok(None),
+            Some(ref channels) => {
+                if let Some(channel) = channels.iter().find(|channel| {
+                    !model::notification::CHANNELS.contains(&channel.as_str())
+                })
+                {
+                    return Err(InvalidChannel(channel.clone()).into());
+                }
+                let mut channels = channels.clone();
+                channels.sort();
+                channels.dedup();
+                Ok(Some(channels))
+            }
+        }
+    }
+
+    /// An empty `push_endpoint` unsubscribes from web push.
+    fn push_endpoint(&self) -> Result<Option<Option<String>>, SerializableError> {
+        match self.push_endpoint {
+            None => Ok(None),
+            Some(ref endpoint) if endpoint.is_empty() => Ok(Some(None)),
+            Some(ref endpoint) => {
+                reminder::push_service_origin(endpoint)?;
+                Ok(Some(Some(endpoint.clone())))
+            }
+        }
+    }
+}
+
+/// Proof of owning the email address of a user, bound to that address.
+fn verification_hash(email: &str, token: &str) -> String {
+    hex(&hmac_sha256(email.as_bytes(), token.as_bytes()))
+}
+
+/// Fails until the cooldown following the last verification email sent to the user is over.
+fn check_verification_cooldown(
+    user: &model::User,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<(), VerificationCooldown> {
+    match user.verification_sent_at {
+        Some(sent_at) => {
+            let retry_at = sent_at + chrono::Duration::minutes(VERIFICATION_COOLDOWN_MINUTES);
+            if now < retry_at {
+                Err(VerificationCooldown(retry_at.to_rfc3339()))
+            } else {
+                Ok(())
+            }
+        }
+        None => Ok(()),
+    }
+}
+
+/// Emails a new verification token to the address of the user, replacing the previous one.
+fn request_verification(
+    user: &mut model::User,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<(), VerificationNotSent> {
+    let token = feed::new_token();
+    reminder::send_email(
+        &user.email,
+        "Verify your email address",
+        &format!(
+            "Use this code to confirm that reminders can be sent to this address: {}",
+            token
+        ),
+    ).map_err(VerificationNotSent)?;
+    user.email_verification = Some(verification_hash(&user.email, &token));
+    user.verification_sent_at = Some(now);
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct EmailVerificationInput {
+    token: String,
+}
+
+/// Marks the email of the user as verified if the token is the one sent to it.
+fn verify(user: &model::User, token: &str) -> Result<model::User, SerializableError> {
+    if user.email.is_empty() ||
+        user.email_verification != Some(verification_hash(&user.email, token))
+    {
+        return Err(InvalidVerificationToken.into());
+    }
+    Ok(model::User {
+        email_verified: true,
+        email_verification: None,
+        ..user.clone()
+    })
+}
+
+pub fn get_me(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<Profile, SerializableError>> {
+    let client = dynamodb_client();
+    ok(Profile::from(&current_user(&client, &authorized_user_id(event))))
+}
+
+pub fn update_me(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<Profile, SerializableError>> {
+    let client = dynamodb_client();
+    let existing = current_user(&client, &authorized_user_id(event));
+    match parse_body::<UserInput>(event).and_then(|input| input.apply_to(&existing)) {
+        Ok(mut user) => {
+            let now = chrono::Utc::now();
+            // a failed email leaves `verification_sent_at` unchanged, so that it can be sent
+            // again with `send_email_verification`
+            if user.email != existing.email && !user.email.is_empty() &&
+                check_verification_cooldown(&user, now).is_ok()
+            {
+                let _ = request_verification(&mut user, now);
+            }
+            save_user(&client, &user);
+            ok(Profile::from(&user))
+        }
+        Err(error) => bad_request(error),
+    }
+}
+
+/// Sends again a verification token to the email of the user, when it was lost or not received.
+pub fn send_email_verification(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<Profile, SerializableError>> {
+    let client = dynamodb_client();
+    let mut user = current_user(&client, &authorized_user_id(event));
+    if user.email.is_empty() {
+        return bad_request(NoEmailToVerify.into());
+    }
+    let now = chrono::Utc::now();
+    if let Err(error) = check_verification_cooldown(&user, now) {
+        return too_many_requests(error.into());
+    }
+    match request_verification(&mut user, now) {
+        Ok(()) => {
+            save_user(&client, &user);
+            ok(Profile::from(&user))
+        }
+        Err(error) => service_unavailable(error.into()),
+    }
+}
+
+/// Verifies the email of the user with the token that was sent to it.
+pub fn verify_email(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<Profile, SerializableError>> {
+    let client = dynamodb_client();
+    let existing = current_user(&client, &authorized_user_id(event));
+    match parse_body::<EmailVerificationInput>(event).and_then(|input| {
+        verify(&existing, &input.token)
+    }) {
+        Ok(user) => {
+            save_user(&client, &user);
+            ok(Profile::from(&user))
+        }
+        Err(error) => bad_request(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user() -> model::User {
+        model::User {
+            user_id: model::UserId("u1".to_string()),
+            email: "testemail".to_string(),
+            email_verified: true,
+            email_verification: None,
+            verification_sent_at: None,
+            feed_token: None,
+            reminder_channels: vec![],
+            push_endpoint: None,
+            tz: Some("Europe/Paris".to_string()),
+        }
+    }
+
+    #[test]
+    fn can_change_timezone() {
+        let input = UserInput {
+            email: None,
+            reminder_channels: None,
+            push_endpoint: None,
+            tz: Some("America/New_York".to_string()),
+        };
+
+        let updated = input.apply_to(&user());
+
+        assert!(updated.is_ok());
+        let updated = updated.ok().unwrap();
+        assert_eq!(updated.tz, Some("America/New_York".to_string()));
+        assert_eq!(updated.timezone(), chrono_tz::America::New_York);
+    }
+
+    #[test]
+    fn should_reject_unknown_timezone() {
+        let input = UserInput {
+            email: None,
+            reminder_channels: None,
+            push_endpoint: None,
+            tz: Some("+02:00".to_string()),
+        };
+
+        let updated = input.apply_to(&user());
+
+        assert!(updated.is_err());
+        assert_eq!(
+            format!("{}", updated.err().unwrap().0),
+            "Invalid Timezone: '+02:00'"
+        );
+    }
+
+    #[test]
+    fn should_default_to_utc() {
+        let input = UserInput {
+            email: None,
+            reminder_channels: None,
+            push_endpoint: None,
+            tz: Some("".to_string()),
+        };
+
+        let updated = input.apply_to(&user()).ok().unwrap();
+
+        assert_eq!(updated.tz, None);
+        assert_eq!(updated.timezone(), chrono_tz::Tz::UTC);
+    }
+
+    #[test]
+    fn should_reject_unknown_channel() {
+        let input = UserInput {
+            email: None,
+            tz: None,
+            reminder_channels: Some(vec!["email".to_string(), "sms".to_string()]),
+            push_endpoint: None,
+        };
+
+        let updated = input.apply_to(&user());
+
+        assert!(updated.is_err());
+        assert_eq!(
+            format!("{}", updated.err().unwrap().0),
+            "Invalid Channel: 'sms'"
+        );
+    }
+
+    #[test]
+    fn can_unsubscribe_from_web_push() {
+        let mut user = user();
+        user.push_endpoint = Some("https://fcm.googleapis.com/fcm/send/abc".to_string());
+        let input = UserInput {
+            email: None,
+            tz: None,
+            reminder_channels: None,
+            push_endpoint: Some("".to_string()),
+        };
+
+        let updated = input.apply_to(&user).ok().unwrap();
+
+        assert_eq!(updated.push_endpoint, None);
+        assert!(
+            UserInput {
+                email: None,
+                tz: None,
+                reminder_channels: None,
+                push_endpoint: Some("https://push.example.com/abc".to_string()),
+            }.apply_to(&user)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn should_verify_new_email_addresses() {
+        let mut user = user();
+        user.email_verification = Some(verification_hash("testemail", "t0"));
+        let input = UserInput {
+            email: Some("new@example.com".to_string()),
+            tz: None,
+            reminder_channels: None,
+            push_endpoint: None,
+        };
+
+        let updated = input.apply_to(&user).ok().unwrap();
+
+        assert!(!updated.email_verified);
+        assert_eq!(updated.email_verification, None);
+
+        user.email_verified = false;
+        user.email_verification = Some(verification_hash("testemail", "t1"));
+        assert!(verify(&user, "t2").is_err());
+        assert!(
+            verify(
+                &model::User {
+                    email: "new@example.com".to_string(),
+                    ..user.clone()
+                },
+                "t1",
+            ).is_err()
+        );
+        let verified = verify(&user, "t1").ok().unwrap();
+        assert!(verified.email_verified);
+        assert_eq!(verified.email_verification, None);
+    }
+
+    #[test]
+    fn should_wait_before_sending_another_verification_email() {
+        let now = chrono::Utc::now();
+        let mut user = user();
+
+        assert!(check_verification_cooldown(&user, now).is_ok());
+
+        user.verification_sent_at = Some(now - chrono::Duration::minutes(1));
+        assert_eq!(
+            format!("{}", check_verification_cooldown(&user, now).err().unwrap()),
+            format!(
+                "Verification Email Already Sent, Retry After {}",
+                (now + chrono::Duration::minutes(4)).to_rfc3339()
+            )
+        );
+        assert!(
+            check_verification_cooldown(&user, now + chrono::Duration::minutes(4)).is_ok()
+        );
+    }
+}
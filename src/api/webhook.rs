@@ -0,0 +1,856 @@
+//! Webhooks called when items are created, completed or changed. Deliveries are JSON documents
+//! signed with the secret of the webhook. They are queued when items change, and sent by a
+//! scheduled task that retries them with an exponential backoff until they succeed, and logs them.
+//!
+//! Webhooks are called from inside the infrastructure, so only `https` URLs of public addresses
+//! are accepted. Deliveries connect to the address that was checked, and not to a new resolution
+//! of the host, which could have changed to a private address in between.
+
+use std::env;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crowbar;
+use chrono;
+use native_tls;
+use reqwest;
+use serde_dynamodb;
+use serde_dynamodb::ToQueryInput;
+use serde_json;
+use uuid;
+
+use rusoto_dynamodb::{AttributeValue, DeleteItemInput, DynamoDb, PutItemInput, QueryInput};
+
+use model;
+use model::webhook::{ITEM_COMPLETED, ITEM_CREATED, ITEM_DELETED, ITEM_EVENTS, ITEM_UPDATED,
+                     PENDING};
+
+use super::common::*;
+use super::history;
+
+const SIGNATURE_HEADER: &str = "X-Vleue-Signature";
+const EVENT_HEADER: &str = "X-Vleue-Event";
+const DELIVERY_HEADER: &str = "X-Vleue-Delivery";
+/// a delivery is abandoned after this many failed attempts
+const MAX_ATTEMPTS: usize = 6;
+/// delay before the first retry, doubled after each failed attempt
+const RETRY_DELAY_SECONDS: i64 = 60;
+const TIMEOUT_SECONDS: u64 = 5;
+const MAX_LISTED_DELIVERIES: i64 = 100;
+/// how long delivered and abandoned deliveries are kept in the log
+const DELIVERY_RETENTION_DAYS: i64 = 30;
+/// index of the pending deliveries by `next_attempt_at`
+const PENDING_INDEX: &str = "pending_index";
+
+#[derive(Debug, Fail)]
+#[fail(display = "Invalid Webhook URL: '{}'", _0)]
+struct InvalidUrl(String);
+
+#[derive(Debug, Fail)]
+#[fail(display = "Unknown Webhook Event: '{}'", _0)]
+struct UnknownEvent(String);
+
+/// Sends deliveries to webhooks.
+pub trait Sender {
+    /// Posts a JSON body to an URL, returning the HTTP status of the response.
+    fn post(&self, url: &str, headers: &[(&str, String)], body: &str) -> Result<u16, String>;
+}
+
+pub struct HttpSender;
+impl Sender for HttpSender {
+    /// The TLS connection is opened to a public address of the host, checked just before, and
+    /// authenticates the host by its name. Redirections are not followed, as they could lead to a
+    /// private address.
+    fn post(&self, url: &str, headers: &[(&str, String)], body: &str) -> Result<u16, String> {
+        let (host, port) = webhook_host(url).map_err(|error| error.to_string())?;
+        let address = public_addresses(&host, port)?[0];
+        let timeout = Duration::from_secs(TIMEOUT_SECONDS);
+        let stream = TcpStream::connect_timeout(&address, timeout).map_err(
+            |error| error.to_string(),
+        )?;
+        stream.set_read_timeout(Some(timeout)).map_err(
+            |error| error.to_string(),
+        )?;
+        stream.set_write_timeout(Some(timeout)).map_err(
+            |error| error.to_string(),
+        )?;
+        let connector = native_tls::TlsConnector::builder()
+            .and_then(|builder| builder.build())
+            .map_err(|error| error.to_string())?;
+        let mut stream = connector
+            .connect(host.trim_start_matches('[').trim_end_matches(']'), stream)
+            .map_err(|error| error.to_string())?;
+        let request = http_request(url, headers, body).map_err(|error| error.to_string())?;
+        stream.write_all(request.as_bytes()).map_err(
+            |error| error.to_string(),
+        )?;
+        let mut status_line = String::new();
+        BufReader::new(stream).read_line(&mut status_line).map_err(
+            |error| error.to_string(),
+        )?;
+        response_status(&status_line)
+    }
+}
+
+#[derive(Deserialize)]
+struct WebhookInput {
+    url: Option<String>,
+    /// all events if not given
+    events: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WebhookList {
+    pub webhooks: Vec<model::webhook::Webhook>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DeliveryList {
+    pub deliveries: Vec<model::webhook::Delivery>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RetryReport {
+    pub delivered: usize,
+    pub failed: usize,
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    id: &'a str,
+    event: &'a str,
+    at: chrono::DateTime<chrono::Utc>,
+    item: &'a model::basic_item::BasicItem,
+}
+
+/// Whether an address can be reached from the internet, and not only from inside the
+/// infrastructure like loopback, private and link-local addresses.
+fn is_public(address: &IpAddr) -> bool {
+    match *address {
+        IpAddr::V4(address) => {
+            let octets = address.octets();
+            !(address.is_private() || address.is_loopback() || address.is_link_local() ||
+                  address.is_broadcast() || address.is_documentation() ||
+                  octets[0] == 0 ||
+                  // shared address space of carrier-grade NAT
+                  (octets[0] == 100 && octets[1] & 0xc0 == 64))
+        }
+        IpAddr::V6(address) => {
+            let first = address.segments()[0];
+            match address.to_ipv4() {
+                // IPv4-mapped and IPv4-compatible addresses, except `::` and `::1`
+                Some(v4) if !address.is_unspecified() && !address.is_loopback() => {
+                    is_public(&IpAddr::V4(v4))
+                }
+                _ => {
+                    !(address.is_unspecified() || address.is_loopback() ||
+                          // unique local addresses
+                          first & 0xfe00 == 0xfc00 ||
+                          // link-local addresses
+                          first & 0xffc0 == 0xfe80)
+                }
+            }
+        }
+    }
+}
+
+/// Host and port of a webhook URL, which must be `https` and not name a local or private host.
+/// Hosts given by name are resolved separately with `resolves_publicly`.
+fn webhook_host(url: &str) -> Result<(String, u16), InvalidUrl> {
+    let invalid = || InvalidUrl(url.to_string());
+    let parsed = reqwest::Url::parse(url).map_err(|_| invalid())?;
+    if parsed.scheme() != "https" {
+        return Err(invalid());
+    }
+    let host = parsed.host_str().ok_or_else(invalid)?.to_lowercase();
+    let is_local_name = host == "localhost" || host.ends_with(".localhost") ||
+        host.ends_with(".local") || host.ends_with(".internal");
+    let is_private_address = host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+        .map(|address| !is_public(&address))
+        .unwrap_or(false);
+    if is_local_name || is_private_address {
+        return Err(invalid());
+    }
+    Ok((host, parsed.port_or_known_default().unwrap_or(443)))
+}
+
+/// Addresses of a host, which must all be public. Checked again before each delivery, as the
+/// addresses of a name can change after the webhook is registered.
+fn public_addresses(host: &str, port: u16) -> Result<Vec<SocketAddr>, String> {
+    let not_public = || format!("{} does not resolve to a public address", host);
+    let addresses: Vec<SocketAddr> = (host.trim_start_matches('[').trim_end_matches(']'), port)
+        .to_socket_addrs()
+        .map_err(|_| not_public())?
+        .collect();
+    if addresses.is_empty() || !addresses.iter().all(|address| is_public(&address.ip())) {
+        return Err(not_public());
+    }
+    Ok(addresses)
+}
+
+fn resolves_publicly(host: &str, port: u16) -> bool {
+    public_addresses(host, port).is_ok()
+}
+
+/// HTTP/1.1 `POST` of a JSON body to an URL, closing the connection after the response.
+fn http_request(url: &str, headers: &[(&str, String)], body: &str) -> Result<String, InvalidUrl> {
+    let parsed = reqwest::Url::parse(url).map_err(|_| InvalidUrl(url.to_string()))?;
+    let host = parsed.host_str().ok_or_else(|| InvalidUrl(url.to_string()))?;
+    let mut request = format!(
+        "POST {}{} HTTP/1.1\r\nHost: {}{}\r\n",
+        parsed.path(),
+        parsed.query().map(|query| format!("?{}", query)).unwrap_or_default(),
+        host,
+        parsed.port().map(|port| format!(":{}", port)).unwrap_or_default()
+    );
+    request.push_str("Content-Type: application/json\r\n");
+    request.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    request.push_str("Connection: close\r\n");
+    for &(name, ref value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("\r\n");
+    request.push_str(body);
+    Ok(request)
+}
+
+/// Status code of the status line of an HTTP response, like `HTTP/1.1 204 No Content`.
+fn response_status(status_line: &str) -> Result<u16, String> {
+    let mut parts = status_line.split_whitespace();
+    match (parts.next(), parts.next().and_then(|status| status.parse().ok())) {
+        (Some(version), Some(status)) if version.starts_with("HTTP/") => Ok(status),
+        _ => Err(format!("invalid response: '{}'", status_line.trim_end())),
+    }
+}
+
+/// Event triggered by a change made to an item, `None` if nothing visible changed.
+fn item_event(
+    before: Option<&model::basic_item::BasicItem>,
+    after: &model::basic_item::BasicItem,
+) -> Option<&'static str> {
+    match before {
+        None => Some(ITEM_CREATED),
+        Some(before) if before.deleted_at.is_none() && after.deleted_at.is_some() => {
+            Some(ITEM_DELETED)
+        }
+        Some(before) if !before.status.is_done() && after.status.is_done() => Some(ITEM_COMPLETED),
+        Some(before) if !history::diff(Some(before), after).is_empty() => Some(ITEM_UPDATED),
+        Some(_) => None,
+    }
+}
+
+/// HMAC-SHA256 of the body, keyed by the secret of the webhook, as `sha256=<hex digest>`.
+fn sign(secret: &str, body: &str) -> String {
+    format!(
+        "sha256={}",
+        hex(&hmac_sha256(secret.as_bytes(), body.as_bytes()))
+    )
+}
+
+/// Random secret, long enough not to be guessed.
+fn new_secret() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+fn new_delivery(
+    webhook: &model::webhook::Webhook,
+    event: &str,
+    item: &model::basic_item::BasicItem,
+    now: chrono::DateTime<chrono::Utc>,
+) -> model::webhook::Delivery {
+    let id = format!(
+        "{}/{}/{}",
+        webhook.id.to_string(),
+        now.format("%Y%m%dT%H%M%S%.6fZ"),
+        uuid::Uuid::new_v4().simple()
+    );
+    let payload = serde_json::to_string(&Payload {
+        id: &id,
+        event: event,
+        at: now,
+        item: item,
+    }).unwrap();
+    model::webhook::Delivery {
+        uid: webhook.uid.clone(),
+        id: id,
+        webhook_id: webhook.id.clone(),
+        event: event.to_string(),
+        payload: payload,
+        attempts: vec![],
+        delivered: false,
+        next_attempt_at: Some(now.timestamp()),
+        pending: Some(PENDING.to_string()),
+        expires_at: None,
+    }
+}
+
+/// Stops attempting a delivery, which leaves the pending index and expires after the retention.
+fn close(delivery: &mut model::webhook::Delivery, now: chrono::DateTime<chrono::Utc>) {
+    delivery.next_attempt_at = None;
+    delivery.pending = None;
+    delivery.expires_at = Some((now + chrono::Duration::days(DELIVERY_RETENTION_DAYS)).timestamp());
+}
+
+/// Delay before the next attempt, after `attempts` failed ones.
+fn retry_delay(attempts: usize) -> chrono::Duration {
+    chrono::Duration::seconds(RETRY_DELAY_SECONDS << (attempts.max(1) - 1))
+}
+
+/// Sends a delivery to its webhook, and schedules the next attempt if it failed.
+fn attempt<S: Sender>(
+    sender: &S,
+    webhook: &model::webhook::Webhook,
+    delivery: &mut model::webhook::Delivery,
+    now: chrono::DateTime<chrono::Utc>,
+) {
+    let headers = vec![
+        (SIGNATURE_HEADER, sign(&webhook.secret, &delivery.payload)),
+        (EVENT_HEADER, delivery.event.clone()),
+        (DELIVERY_HEADER, delivery.id.clone()),
+    ];
+    let result = sender.post(&webhook.url, &headers, &delivery.payload);
+    let delivered = match result {
+        Ok(status) => status >= 200 && status < 300,
+        Err(_) => false,
+    };
+    delivery.attempts.push(model::webhook::DeliveryAttempt {
+        at: now,
+        status: result.as_ref().ok().cloned(),
+        error: result.err(),
+    });
+    delivery.delivered = delivered;
+    if delivered || delivery.attempts.len() >= MAX_ATTEMPTS {
+        close(delivery, now);
+    } else {
+        delivery.next_attempt_at = Some((now + retry_delay(delivery.attempts.len())).timestamp());
+    }
+}
+
+pub fn user_webhooks<D: DynamoDb>(
+    client: &D,
+    table: &str,
+    user_id: &model::UserId,
+) -> Vec<model::webhook::Webhook> {
+    let uid_filter = model::webhook::WebhookQueryInput {
+        uid: Some(user_id.clone()),
+        ..Default::default()
+    };
+    client
+        .query(&uid_filter.to_query_input(table.to_string()))
+        .unwrap()
+        .items
+        .unwrap_or_else(|| vec![])
+        .into_iter()
+        .map(|webhook| serde_dynamodb::from_hashmap(webhook).unwrap())
+        .collect()
+}
+
+fn find_webhook<D: DynamoDb>(
+    client: &D,
+    table: &str,
+    user_id: &model::UserId,
+    webhook_id: &str,
+) -> Option<model::webhook::Webhook> {
+    let webhook_filter = model::webhook::WebhookQueryInput {
+        uid: Some(user_id.clone()),
+        id: Some(webhook_id.to_string().into()),
+        ..Default::default()
+    };
+    client
+        .query(&webhook_filter.to_query_input(table.to_string()))
+        .unwrap()
+        .items
+        .unwrap_or_else(|| vec![])
+        .pop()
+        .map(|webhook| serde_dynamodb::from_hashmap(webhook).unwrap())
+}
+
+fn save_delivery<D: DynamoDb>(client: &D, delivery: &model::webhook::Delivery) {
+    let put_item = PutItemInput {
+        item: serde_dynamodb::to_hashmap(delivery).unwrap(),
+        table_name: env::var("webhook_deliveries_table").unwrap(),
+        ..Default::default()
+    };
+    client.put_item(&put_item).unwrap();
+}
+
+/// Queues the events triggered by changes made to items of a user for the webhooks registered
+/// for them, to be sent by `retry`. The changes are already saved, so errors are only logged.
+pub fn notify<D: DynamoDb>(
+    client: &D,
+    changes: &[(Option<&model::basic_item::BasicItem>, &model::basic_item::BasicItem)],
+) {
+    let events: Vec<(&str, &model::basic_item::BasicItem)> = changes
+        .iter()
+        .filter_map(|&(before, after)| {
+            item_event(before, after).map(|event| (event, after))
+        })
+        .collect();
+    if events.is_empty() {
+        return;
+    }
+
+    let uid_filter = model::webhook::WebhookQueryInput {
+        uid: Some(events[0].1.uid.clone()),
+        ..Default::default()
+    };
+    let webhooks: Vec<model::webhook::Webhook> =
+        match client.query(&uid_filter.to_query_input(env::var("webhooks_table").unwrap())) {
+            Ok(output) => {
+                output
+                    .items
+                    .unwrap_or_else(|| vec![])
+                    .into_iter()
+                    .filter_map(|webhook| serde_dynamodb::from_hashmap(webhook).ok())
+                    .collect()
+            }
+            Err(error) => {
+                println!("error listing webhooks of {}: {}", events[0].1.uid.to_string(), error);
+                return;
+            }
+        };
+    let now = chrono::Utc::now();
+    for &(event, item) in &events {
+        for webhook in webhooks.iter().filter(|webhook| {
+            webhook.events.iter().any(|name| name == event)
+        })
+        {
+            let delivery = new_delivery(webhook, event, item, now);
+            let put_item = PutItemInput {
+                item: serde_dynamodb::to_hashmap(&delivery).unwrap(),
+                table_name: env::var("webhook_deliveries_table").unwrap(),
+                ..Default::default()
+            };
+            if let Err(error) = client.put_item(&put_item) {
+                println!("error queuing delivery {}: {}", delivery.id, error);
+            }
+        }
+    }
+}
+
+pub fn list(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<WebhookList, SerializableError>> {
+    let client = dynamodb_client();
+    ok(WebhookList {
+        webhooks: user_webhooks(
+            &client,
+            &env::var("webhooks_table").unwrap(),
+            &authorized_user_id(event),
+        ),
+    })
+}
+
+/// Registers a webhook. Its secret is returned so that the receiver can check the signatures.
+pub fn add(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::webhook::Webhook,
+        SerializableError,
+    >,
+> {
+    let input = match parse_body::<WebhookInput>(event) {
+        Ok(input) => input,
+        Err(error) => return bad_request(error),
+    };
+    let url = match input.url {
+        Some(url) => url,
+        None => return bad_request(MissingField("url").into()),
+    };
+    match webhook_host(&url) {
+        Ok((ref host, port)) if resolves_publicly(host, port) => (),
+        _ => return bad_request(InvalidUrl(url).into()),
+    }
+    let events = input.events.unwrap_or_else(|| {
+        ITEM_EVENTS.iter().map(|event| event.to_string()).collect()
+    });
+    if let Some(unknown) = events.iter().find(
+        |event| !ITEM_EVENTS.contains(&event.as_str()),
+    )
+    {
+        return bad_request(UnknownEvent(unknown.clone()).into());
+    }
+
+    let actor = authorized_actor(event);
+    let webhook = model::webhook::Webhook {
+        uid: actor.user_id.clone(),
+        id: model::WebhookId(format!("{}", uuid::Uuid::new_v4().hyphenated())),
+        url: url,
+        secret: new_secret(),
+        events: events,
+        created_by: actor,
+    };
+    let client = dynamodb_client();
+    let put_item = PutItemInput {
+        item: serde_dynamodb::to_hashmap(&webhook).unwrap(),
+        table_name: env::var("webhooks_table").unwrap(),
+        ..Default::default()
+    };
+    client.put_item(&put_item).unwrap();
+    ok(webhook)
+}
+
+/// Removes a webhook. Its pending deliveries are abandoned.
+pub fn delete(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<
+    crowbar::ApiGatewayResponse<
+        model::webhook::Webhook,
+        SerializableError,
+    >,
+> {
+    let webhook_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let table = env::var("webhooks_table").unwrap();
+    let client = dynamodb_client();
+    let user_id = authorized_user_id(event);
+    let webhook = match find_webhook(&client, &table, &user_id, &webhook_id) {
+        Some(webhook) => webhook,
+        None => return not_found("webhook", webhook_id),
+    };
+    let delete_item = DeleteItemInput {
+        key: user_key(&user_id, &webhook_id),
+        table_name: table,
+        ..Default::default()
+    };
+    client.delete_item(&delete_item).unwrap();
+    ok(webhook)
+}
+
+/// Lists the latest deliveries of a webhook, newest first.
+pub fn deliveries(
+    event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<crowbar::ApiGatewayResponse<DeliveryList, SerializableError>> {
+    let webhook_id = event["pathParameters"]["id"].as_str().unwrap().to_string();
+    let client = dynamodb_client();
+    let user_id = authorized_user_id(event);
+    if find_webhook(
+        &client,
+        &env::var("webhooks_table").unwrap(),
+        &user_id,
+        &webhook_id,
+    ).is_none()
+    {
+        return not_found("webhook", webhook_id);
+    }
+
+    let mut names = HashMap::new();
+    names.insert("#uid".to_string(), "uid".to_string());
+    names.insert("#id".to_string(), "id".to_string());
+    let mut values = HashMap::new();
+    values.insert(
+        ":uid".to_string(),
+        AttributeValue {
+            s: Some(user_id.to_string()),
+            ..Default::default()
+        },
+    );
+    values.insert(
+        ":prefix".to_string(),
+        AttributeValue {
+            s: Some(format!("{}/", webhook_id)),
+            ..Default::default()
+        },
+    );
+    let query_input = QueryInput {
+        table_name: env::var("webhook_deliveries_table").unwrap(),
+        key_condition_expression: Some("#uid = :uid AND begins_with(#id, :prefix)".to_string()),
+        expression_attribute_names: Some(names),
+        expression_attribute_values: Some(values),
+        scan_index_forward: Some(false),
+        limit: Some(MAX_LISTED_DELIVERIES),
+        ..Default::default()
+    };
+    ok(DeliveryList {
+        deliveries: client
+            .query(&query_input)
+            .unwrap()
+            .items
+            .unwrap_or_else(|| vec![])
+            .into_iter()
+            .map(|delivery| serde_dynamodb::from_hashmap(delivery).unwrap())
+            .collect(),
+    })
+}
+
+/// Deliveries of all users whose next attempt is due, from the index of the pending ones.
+fn due_deliveries<D: DynamoDb>(
+    client: &D,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Vec<model::webhook::Delivery> {
+    let mut names = HashMap::new();
+    names.insert("#pending".to_string(), "pending".to_string());
+    names.insert(
+        "#next_attempt_at".to_string(),
+        "next_attempt_at".to_string(),
+    );
+    let mut values = HashMap::new();
+    values.insert(
+        ":pending".to_string(),
+        AttributeValue {
+            s: Some(PENDING.to_string()),
+            ..Default::default()
+        },
+    );
+    values.insert(
+        ":now".to_string(),
+        AttributeValue {
+            n: Some(now.timestamp().to_string()),
+            ..Default::default()
+        },
+    );
+
+    let mut due = vec![];
+    let mut start_key = None;
+    loop {
+        let query_input = QueryInput {
+            table_name: env::var("webhook_deliveries_table").unwrap(),
+            index_name: Some(PENDING_INDEX.to_string()),
+            key_condition_expression: Some(
+                "#pending = :pending AND #next_attempt_at <= :now".to_string(),
+            ),
+            expression_attribute_names: Some(names.clone()),
+            expression_attribute_values: Some(values.clone()),
+            exclusive_start_key: start_key,
+            ..Default::default()
+        };
+        let output = client.query(&query_input).unwrap();
+        due.extend(
+            output
+                .items
+                .unwrap_or_else(|| vec![])
+                .into_iter()
+                .map(|delivery| serde_dynamodb::from_hashmap(delivery).unwrap()),
+        );
+        start_key = output.last_evaluated_key;
+        if start_key.is_none() {
+            return due;
+        }
+    }
+}
+
+/// Scheduled task sending the deliveries whose next attempt is due, new ones included.
+pub fn retry(
+    _event: &crowbar::Value,
+    _context: &crowbar::LambdaContext,
+) -> crowbar::LambdaResult<RetryReport> {
+    let table = env::var("webhooks_table").unwrap();
+    let client = dynamodb_client();
+    let now = chrono::Utc::now();
+    let mut report = RetryReport {
+        delivered: 0,
+        failed: 0,
+    };
+    for mut delivery in due_deliveries(&client, now) {
+        match find_webhook(
+            &client,
+            &table,
+            &delivery.uid,
+            &delivery.webhook_id.to_string(),
+        ) {
+            Some(webhook) => attempt(&HttpSender, &webhook, &mut delivery, now),
+            // the webhook was removed
+            None => close(&mut delivery, now),
+        }
+        if delivery.delivered {
+            report.delivered += 1;
+        } else {
+            report.failed += 1;
+        }
+        save_delivery(&client, &delivery);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use chrono::TimeZone;
+
+    /// Answers with the given results, and records the requests.
+    struct FakeSender {
+        results: RefCell<Vec<Result<u16, String>>>,
+        requests: RefCell<Vec<(String, Vec<(String, String)>, String)>>,
+    }
+    impl FakeSender {
+        fn new(results: Vec<Result<u16, String>>) -> FakeSender {
+            FakeSender {
+                results: RefCell::new(results),
+                requests: RefCell::new(vec![]),
+            }
+        }
+    }
+    impl Sender for FakeSender {
+        fn post(&self, url: &str, headers: &[(&str, String)], body: &str) -> Result<u16, String> {
+            self.requests.borrow_mut().push((
+                url.to_string(),
+                headers
+                    .iter()
+                    .map(|&(name, ref value)| (name.to_string(), value.clone()))
+                    .collect(),
+                body.to_string(),
+            ));
+            self.results.borrow_mut().remove(0)
+        }
+    }
+
+    fn item() -> model::basic_item::BasicItem {
+        model::basic_item::BasicItem {
+            version: 1,
+            ..model::basic_item::test_item()
+        }
+    }
+
+    fn webhook() -> model::webhook::Webhook {
+        model::webhook::Webhook {
+            uid: model::UserId("u1".to_string()),
+            id: model::WebhookId("w1".to_string()),
+            url: "https://example.com/hook".to_string(),
+            secret: "key".to_string(),
+            events: vec![ITEM_CREATED.to_string()],
+            created_by: model::Actor {
+                user_id: model::UserId("u1".to_string()),
+                app_id: model::AppId("a1".to_string()),
+            },
+        }
+    }
+
+    #[test]
+    fn should_only_accept_https_urls_of_public_hosts() {
+        assert_eq!(
+            webhook_host("https://example.com/hook").ok(),
+            Some(("example.com".to_string(), 443))
+        );
+        assert_eq!(
+            webhook_host("https://93.184.216.34:8443/hook").ok(),
+            Some(("93.184.216.34".to_string(), 8443))
+        );
+        assert!(webhook_host("http://example.com/hook").is_err());
+        assert!(webhook_host("https://localhost/hook").is_err());
+        assert!(webhook_host("https://127.0.0.1/hook").is_err());
+        assert!(webhook_host("https://10.0.0.1/hook").is_err());
+        assert!(webhook_host("https://169.254.169.254/latest/meta-data").is_err());
+        assert!(webhook_host("https://[::1]/hook").is_err());
+        assert!(webhook_host("https://[fe80::1]/hook").is_err());
+        assert!(webhook_host("https://[::ffff:192.168.0.1]/hook").is_err());
+        assert!(webhook_host("not an url").is_err());
+    }
+
+    #[test]
+    fn can_write_http_requests() {
+        let headers = vec![(EVENT_HEADER, ITEM_CREATED.to_string())];
+
+        assert_eq!(
+            http_request("https://example.com:8443/hook?team=1", &headers, "{}").ok(),
+            Some(
+                "POST /hook?team=1 HTTP/1.1\r\nHost: example.com:8443\r\n\
+                 Content-Type: application/json\r\nContent-Length: 2\r\n\
+                 Connection: close\r\nX-Vleue-Event: item.created\r\n\r\n{}"
+                    .to_string(),
+            )
+        );
+        assert_eq!(response_status("HTTP/1.1 204 No Content\r\n"), Ok(204));
+        assert!(response_status("").is_err());
+        assert!(response_status("SSH-2.0-OpenSSH_7.4\r\n").is_err());
+    }
+
+    #[test]
+    fn can_sign_body() {
+        assert_eq!(
+            sign("key", "The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn can_classify_item_events() {
+        let before = item();
+        let mut completed = item();
+        completed.status = model::State::done();
+        let mut deleted = item();
+        deleted.deleted_at = Some(chrono::Utc::now());
+        let mut renamed = item();
+        renamed.title = "renamed".to_string();
+
+        assert_eq!(item_event(None, &before), Some(ITEM_CREATED));
+        assert_eq!(item_event(Some(&before), &completed), Some(ITEM_COMPLETED));
+        assert_eq!(item_event(Some(&before), &deleted), Some(ITEM_DELETED));
+        assert_eq!(item_event(Some(&before), &renamed), Some(ITEM_UPDATED));
+        assert_eq!(item_event(Some(&before), &item()), None);
+    }
+
+    #[test]
+    fn should_send_signed_delivery() {
+        let now = chrono::Utc.ymd(2026, 10, 19).and_hms(12, 0, 0);
+        let sender = FakeSender::new(vec![Ok(204)]);
+        let mut delivery = new_delivery(&webhook(), ITEM_CREATED, &item(), now);
+
+        attempt(&sender, &webhook(), &mut delivery, now);
+
+        let requests = sender.requests.borrow();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].0, "https://example.com/hook");
+        assert!(requests[0].1.contains(&(
+            SIGNATURE_HEADER.to_string(),
+            sign("key", &delivery.payload),
+        )));
+        assert!(requests[0].1.contains(
+            &(EVENT_HEADER.to_string(), ITEM_CREATED.to_string()),
+        ));
+        assert!(delivery.delivered);
+        assert_eq!(delivery.next_attempt_at, None);
+        assert_eq!(delivery.pending, None);
+        assert_eq!(
+            delivery.expires_at,
+            Some(now.timestamp() + DELIVERY_RETENTION_DAYS * 24 * 3600)
+        );
+        assert_eq!(delivery.attempts[0].status, Some(204));
+    }
+
+    #[test]
+    fn should_retry_failed_delivery_with_backoff() {
+        let now = chrono::Utc.ymd(2026, 10, 19).and_hms(12, 0, 0);
+        let sender = FakeSender::new(vec![Ok(500), Err("timeout".to_string())]);
+        let mut delivery = new_delivery(&webhook(), ITEM_CREATED, &item(), now);
+
+        attempt(&sender, &webhook(), &mut delivery, now);
+        assert_eq!(delivery.next_attempt_at, Some(now.timestamp() + 60));
+
+        attempt(&sender, &webhook(), &mut delivery, now);
+        assert_eq!(delivery.next_attempt_at, Some(now.timestamp() + 120));
+        assert_eq!(delivery.pending, Some(PENDING.to_string()));
+        assert_eq!(delivery.expires_at, None);
+        assert!(!delivery.delivered);
+        assert_eq!(delivery.attempts[1].error, Some("timeout".to_string()));
+    }
+
+    #[test]
+    fn should_abandon_delivery_after_max_attempts() {
+        let now = chrono::Utc.ymd(2026, 10, 19).and_hms(12, 0, 0);
+        let sender = FakeSender::new((0..MAX_ATTEMPTS).map(|_| Ok(503)).collect());
+        let mut delivery = new_delivery(&webhook(), ITEM_CREATED, &item(), now);
+
+        for _ in 0..MAX_ATTEMPTS {
+            attempt(&sender, &webhook(), &mut delivery, now);
+        }
+
+        assert_eq!(delivery.attempts.len(), MAX_ATTEMPTS);
+        assert_eq!(delivery.next_attempt_at, None);
+        assert_eq!(delivery.pending, None);
+        assert!(delivery.expires_at.is_some());
+        assert!(!delivery.delivered);
+    }
+}
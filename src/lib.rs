@@ -23,9 +23,19 @@ extern crate time;
 extern crate http;
 extern crate mime;
 extern crate chrono;
+extern crate chrono_tz;
 
 extern crate rusoto_core;
 extern crate rusoto_dynamodb;
+extern crate rusoto_ses;
+
+extern crate reqwest;
+extern crate native_tls;
+extern crate hmac;
+extern crate sha2;
+extern crate ring;
+extern crate untrusted;
+extern crate base64;
 
 mod model;
 mod api;
@@ -41,4 +51,56 @@ lambda!(
     "api_todo_list" => api::todo::list,
     "api_todo_add" => api::todo::add,
     "api_todo_get" => api::todo::get,
+    "api_todo_update" => api::todo::update,
+    "api_todo_delete" => api::todo::delete,
+    "api_todo_children" => api::todo::children,
+    "api_todo_move" => api::todo::move_item,
+    "api_todo_search" => api::search::search,
+    "api_todo_assigned" => api::todo::assigned,
+    "api_todo_comments" => api::comment::list,
+    "api_todo_add_comment" => api::comment::add,
+    "api_todo_update_comment" => api::comment::update,
+    "api_todo_delete_comment" => api::comment::delete,
+    "api_todo_add_attachment" => api::attachment::add,
+    "api_todo_get_attachment" => api::attachment::get,
+    "api_todo_delete_attachment" => api::attachment::delete,
+    "api_todo_batch" => api::batch::batch,
+    "api_todo_sync" => api::sync::sync,
+    "api_todo_restore" => api::trash::restore,
+    "api_todo_history" => api::history::history,
+    "api_todo_import" => api::import::import,
+    "api_user_export" => api::export::export,
+    "api_user_create_feed" => api::feed::create,
+    "api_user_revoke_feed" => api::feed::revoke,
+    "api_feed" => api::feed::feed,
+    "api_context_list" => api::context::list,
+    "api_context_add" => api::context::add,
+    "api_context_delete" => api::context::delete,
+    "api_context_todos" => api::context::todos,
+    "api_project_list" => api::project::list,
+    "api_project_add" => api::project::add,
+    "api_project_get" => api::project::get,
+    "api_project_update" => api::project::update,
+    "api_project_summary" => api::project::summary,
+    "api_project_members" => api::member::list,
+    "api_project_add_member" => api::member::add,
+    "api_project_remove_member" => api::member::remove,
+    "api_template_list" => api::template::list,
+    "api_template_add" => api::template::add,
+    "api_template_get" => api::template::get,
+    "api_template_update" => api::template::update,
+    "api_template_delete" => api::template::delete,
+    "api_template_instantiate" => api::template::instantiate,
+    "api_user_get_me" => api::user::get_me,
+    "api_user_update_me" => api::user::update_me,
+    "api_user_send_email_verification" => api::user::send_email_verification,
+    "api_user_verify_email" => api::user::verify_email,
+    "api_user_notifications" => api::reminder::notifications,
+    "api_webhook_list" => api::webhook::list,
+    "api_webhook_add" => api::webhook::add,
+    "api_webhook_delete" => api::webhook::delete,
+    "api_webhook_deliveries" => api::webhook::deliveries,
+    "scheduled_trash_purge" => api::trash::purge,
+    "scheduled_webhook_retry" => api::webhook::retry,
+    "scheduled_reminders" => api::reminder::remind,
 );
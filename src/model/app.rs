@@ -1,14 +1,58 @@
 use chrono;
+use chrono_tz;
 use uuid;
 
 typed_id!(UserId);
 typed_id!(AppId);
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct User {
     pub user_id: UserId,
     pub email: String,
-    pub tz: Option<chrono::offset::FixedOffset>,
+    /// whether the user proved owning `email`, reminders are only emailed to verified addresses
+    #[serde(default)]
+    pub email_verified: bool,
+    /// HMAC, keyed by `email`, of the token sent to verify it, the token itself is only in the
+    /// verification email
+    #[serde(default)]
+    pub email_verification: Option<String>,
+    /// when the last verification email was sent, to limit how often one is sent
+    #[serde(default)]
+    pub verification_sent_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// IANA name of the timezone of the user, like `Europe/Paris`
+    pub tz: Option<String>,
+    /// token giving read access to the calendar feed of the user
+    #[serde(default)]
+    pub feed_token: Option<String>,
+    /// channels on which reminders are sent, see `model::notification::CHANNELS`
+    #[serde(default)]
+    pub reminder_channels: Vec<String>,
+    /// web push subscription endpoint of the browser of the user
+    #[serde(default)]
+    pub push_endpoint: Option<String>,
+}
+impl User {
+    /// Timezone in which dates given by the user are interpreted, UTC if none was set.
+    pub fn timezone(&self) -> chrono_tz::Tz {
+        self.tz
+            .as_ref()
+            .and_then(|tz| tz.parse::<chrono_tz::Tz>().ok())
+            .unwrap_or(chrono_tz::Tz::UTC)
+    }
+}
+
+/// Calendar feed token, stored apart from the user to be found from the token.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FeedToken {
+    pub token: String,
+    pub user_id: UserId,
+}
+
+/// Who made a change: a user, through an application.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Actor {
+    pub user_id: UserId,
+    pub app_id: AppId,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -1,10 +1,38 @@
 use rusoto_dynamodb::QueryInput;
 use serde_dynamodb;
 use serde_dynamodb::ToQueryInput;
+use chrono;
+use serde::{Deserialize, Deserializer, Serializer};
 
 use super::*;
 
-#[derive(Serialize, Deserialize, Debug, ToQueryInput)]
+/// Due dates are stored in UTC to the second, like `2026-10-20T07:30:00Z`, so that DynamoDB can
+/// compare them as strings in filter expressions.
+pub mod due_date {
+    use super::*;
+
+    pub fn format(date: &chrono::DateTime<chrono::Utc>) -> String {
+        date.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+    }
+
+    pub fn serialize<S: Serializer>(
+        date: &Option<chrono::DateTime<chrono::Utc>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match *date {
+            Some(ref date) => serializer.serialize_some(&format(date)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<chrono::DateTime<chrono::Utc>>, D::Error> {
+        Option::<chrono::DateTime<chrono::Utc>>::deserialize(deserializer)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToQueryInput)]
 pub struct BasicItem {
     pub uid: super::super::UserId,
     pub id: ItemId,
@@ -13,9 +41,58 @@ pub struct BasicItem {
     pub status: State,
     pub flagged: bool,
     pub project_id: ProjectId,
+    pub parent_id: Option<ItemId>,
+    #[serde(default)]
+    pub contexts: Vec<ContextId>,
+    #[serde(default)]
+    pub costs: Vec<Cost>,
+    #[serde(default, with = "due_date")]
+    pub due: Option<chrono::DateTime<chrono::Utc>>,
+    /// manual order of the item in its project
+    #[serde(default)]
+    pub position: i64,
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_by: Option<super::super::Actor>,
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub updated_by: Option<super::super::Actor>,
+    pub completed_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// incremented on each change, used as the ETag of the item
+    #[serde(default)]
+    pub version: u64,
+    /// set when the item is in the trash
+    pub deleted_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// user who should do the item, the owner or a member of its project
+    #[serde(default)]
+    pub assignee: Option<super::super::UserId>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    /// minutes before the due date at which to remind the user
+    #[serde(default)]
+    pub reminders: Vec<u32>,
     //pub tags: Vec<TagId>,
 }
 
+impl BasicItem {
+    /// Sets the server managed metadata of a new item.
+    pub fn created(&mut self, actor: &super::super::Actor, now: chrono::DateTime<chrono::Utc>) {
+        self.created_at = Some(now);
+        self.created_by = Some(actor.clone());
+        self.touch(actor, now);
+    }
+
+    /// Updates the server managed metadata after a change made by `actor`.
+    pub fn touch(&mut self, actor: &super::super::Actor, now: chrono::DateTime<chrono::Utc>) {
+        self.updated_at = Some(now);
+        self.updated_by = Some(actor.clone());
+        self.version += 1;
+        self.completed_at = match (self.status.is_done(), self.completed_at) {
+            (true, None) => Some(now),
+            (true, completed_at) => completed_at,
+            (false, _) => None,
+        };
+    }
+}
+
 impl Item for BasicItem {
     fn id(&self) -> &ItemId {
         &self.id
@@ -35,13 +112,92 @@ impl Item for BasicItem {
     fn flagged(&self) -> bool {
         self.flagged
     }
+    fn costs(&self) -> &Vec<Cost> {
+        &self.costs
+    }
     /*fn tags(&self) -> &Vec<TagId> {
         &self.tags
     }*/
     fn project(&self) -> &ProjectId {
         &self.project_id
     }
+    fn contexts(&self) -> &Vec<ContextId> {
+        &self.contexts
+    }
     fn parent(&self) -> Option<Box<ItemId>> {
-        None
+        self.parent_id.clone().map(Box::new)
+    }
+    fn due(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.due
+    }
+}
+
+/// Open item `i1` of the user `u1` in the project `p1`, to build items in tests with the struct
+/// update syntax.
+#[cfg(test)]
+pub fn test_item() -> BasicItem {
+    BasicItem {
+        uid: super::super::UserId("u1".to_string()),
+        id: ItemId("i1".to_string()),
+        title: "item".to_string(),
+        description: "".to_string(),
+        status: State { name: "".to_string() },
+        flagged: false,
+        project_id: ProjectId("p1".to_string()),
+        parent_id: None,
+        contexts: vec![],
+        costs: vec![],
+        due: None,
+        position: 0,
+        created_at: None,
+        created_by: None,
+        updated_at: None,
+        updated_by: None,
+        completed_at: None,
+        version: 0,
+        deleted_at: None,
+        assignee: None,
+        attachments: vec![],
+        reminders: vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use model;
+
+    fn actor() -> model::Actor {
+        model::Actor {
+            user_id: model::UserId("u1".to_string()),
+            app_id: model::AppId("a1".to_string()),
+        }
+    }
+
+    #[test]
+    fn should_set_completion_date_once() {
+        let mut item = test_item();
+        item.created(&actor(), chrono::Utc.ymd(2026, 10, 1).and_hms(0, 0, 0));
+        item.status = State::done();
+        item.touch(&actor(), chrono::Utc.ymd(2026, 10, 2).and_hms(0, 0, 0));
+        item.touch(&actor(), chrono::Utc.ymd(2026, 10, 3).and_hms(0, 0, 0));
+
+        assert_eq!(item.created_at, Some(chrono::Utc.ymd(2026, 10, 1).and_hms(0, 0, 0)));
+        assert_eq!(item.updated_at, Some(chrono::Utc.ymd(2026, 10, 3).and_hms(0, 0, 0)));
+        assert_eq!(item.completed_at, Some(chrono::Utc.ymd(2026, 10, 2).and_hms(0, 0, 0)));
+        assert_eq!(item.version, 3);
+    }
+
+    #[test]
+    fn should_clear_completion_date_when_reopened() {
+        let mut item = test_item();
+        item.status = State::done();
+        item.touch(&actor(), chrono::Utc.ymd(2026, 10, 2).and_hms(0, 0, 0));
+        item.status = State { name: "".to_string() };
+        item.touch(&actor(), chrono::Utc.ymd(2026, 10, 3).and_hms(0, 0, 0));
+
+        assert_eq!(item.completed_at, None);
+        assert_eq!(item.updated_by, Some(actor()));
     }
 }
@@ -0,0 +1,21 @@
+use rusoto_dynamodb::QueryInput;
+use serde_dynamodb;
+use serde_dynamodb::ToQueryInput;
+use chrono;
+
+use super::*;
+
+/// Comment in the thread of an item, stored with the owner of the item.
+#[derive(Serialize, Deserialize, Debug, Clone, ToQueryInput)]
+pub struct Comment {
+    pub uid: super::super::UserId,
+    /// item id followed by the creation date, so that comments of an item are sorted
+    pub id: String,
+    pub comment_id: CommentId,
+    pub item_id: ItemId,
+    pub author: super::super::UserId,
+    /// Markdown text
+    pub body: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+}
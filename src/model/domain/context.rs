@@ -0,0 +1,12 @@
+use rusoto_dynamodb::QueryInput;
+use serde_dynamodb;
+use serde_dynamodb::ToQueryInput;
+
+use super::*;
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToQueryInput)]
+pub struct Context {
+    pub uid: super::super::UserId,
+    pub id: ContextId,
+    pub name: String,
+}
@@ -0,0 +1,27 @@
+use rusoto_dynamodb::QueryInput;
+use serde_dynamodb;
+use serde_dynamodb::ToQueryInput;
+use chrono;
+
+use super::*;
+
+/// Change of one field of an item, values being JSON encoded.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: String,
+    pub after: String,
+}
+
+/// Changes made to an item by one request.
+#[derive(Serialize, Deserialize, Debug, Clone, ToQueryInput)]
+pub struct HistoryEntry {
+    pub uid: super::super::UserId,
+    /// item id followed by the version, so that entries of an item are sorted
+    pub id: String,
+    pub item_id: ItemId,
+    pub version: u64,
+    pub at: chrono::DateTime<chrono::Utc>,
+    pub actor: super::super::Actor,
+    pub changes: Vec<FieldChange>,
+}
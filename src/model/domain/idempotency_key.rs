@@ -0,0 +1,13 @@
+use super::*;
+
+/// `Idempotency-Key` of a request that created an item, kept with the response to replay it to
+/// retries of the request.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdempotencyKey {
+    pub uid: super::super::UserId,
+    pub key: String,
+    /// item created by the first request, as it was returned
+    pub item: basic_item::BasicItem,
+    /// epoch timestamp after which the key can be reused, and DynamoDB can expire it
+    pub expires_at: i64,
+}
@@ -0,0 +1,35 @@
+use rusoto_dynamodb::QueryInput;
+use serde_dynamodb;
+use serde_dynamodb::ToQueryInput;
+use chrono;
+
+use super::*;
+
+/// Rights of a user on a project and its items.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Owner,
+    Editor,
+    Viewer,
+}
+impl Role {
+    pub fn can_edit(&self) -> bool {
+        *self != Role::Viewer
+    }
+}
+
+/// Membership of a user in a project owned by another user. The items of the project stay
+/// stored with its owner.
+#[derive(Serialize, Deserialize, Debug, Clone, ToQueryInput)]
+pub struct Member {
+    /// the member
+    pub uid: super::super::UserId,
+    /// the shared project
+    pub id: ProjectId,
+    pub owner: super::super::UserId,
+    pub role: Role,
+    /// when the project was shared with the member, or the role last changed
+    #[serde(default)]
+    pub added_at: Option<chrono::DateTime<chrono::Utc>>,
+}
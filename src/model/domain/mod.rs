@@ -1,13 +1,27 @@
-//use chrono;
+use chrono;
 use uuid;
 
 pub mod basic_item;
+pub mod comment;
+pub mod context;
+pub mod history;
+pub mod idempotency_key;
+pub mod member;
+pub mod notification;
+pub mod project;
+pub mod template;
+pub mod tombstone;
+pub mod webhook;
 
 typed_id!(ItemId);
 typed_id!(TagId);
 typed_id!(ProjectId);
 typed_id!(ContextId);
 typed_id!(CostCategoryId);
+typed_id!(WebhookId);
+typed_id!(CommentId);
+typed_id!(AttachmentId);
+typed_id!(TemplateId);
 
 pub trait Item {
     fn id(&self) -> &ItemId;
@@ -16,40 +30,54 @@ pub trait Item {
     fn description(&self) -> &str;
     fn status(&self) -> &State;
     fn flagged(&self) -> bool;
-    //    fn costs(&self) -> &Vec<Cost>;
+    fn costs(&self) -> &Vec<Cost>;
     //    fn tags(&self) -> &Vec<TagId>;
     fn project(&self) -> &ProjectId;
-    //    fn contexts(&self) -> &Vec<Context>;
+    fn contexts(&self) -> &Vec<ContextId>;
     fn parent(&self) -> Option<Box<ItemId>>;
-    /*    fn due(&self) -> Option<chrono::DateTime<chrono::Utc>>;
-    fn defer(&self) -> Option<chrono::DateTime<chrono::Utc>>;
+    fn due(&self) -> Option<chrono::DateTime<chrono::Utc>>;
+    /*    fn defer(&self) -> Option<chrono::DateTime<chrono::Utc>>;
     fn repeat(&self) -> Option<Repeat>;*/
 }
 
-/*#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CostCategory {
-    //    pub id: CostCategoryId,
+    pub id: CostCategoryId,
     pub name: String,
 }
-*/
-/*#[derive(Serialize, Deserialize, Debug)]
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Cost {
-    pub category: CostCategory,
-    pub cost: u8,
+    pub category: CostCategoryId,
+    pub cost: u32,
 }
-*/
+
+/// File attached to an item, its content being kept in the attachments storage.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Attachment {
+    pub id: AttachmentId,
+    pub name: String,
+    pub content_type: String,
+    /// size in bytes, counted in the quota of the owner of the item
+    pub size: u64,
+    /// key of the content in the storage
+    pub key: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub created_by: super::UserId,
+}
+
 /*#[derive(Serialize, Deserialize, Debug)]
 pub struct Tag {
     pub id: TagId,
     pub name: String,
 }
 */
-/*#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct CostInfo {
     pub categories: Vec<CostCategory>,
     pub unit: String,
 }
-*/
+
 /*#[derive(Serialize, Deserialize, Debug)]
 pub struct Transition {
     pub name: String,
@@ -57,10 +85,20 @@ pub struct Transition {
     pub to: State,
 }
 */
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct State {
     pub name: String,
 }
+impl State {
+    pub fn done() -> State {
+        State { name: DONE_STATE.to_string() }
+    }
+    pub fn is_done(&self) -> bool {
+        self.name == DONE_STATE
+    }
+}
+
+pub const DONE_STATE: &str = "done";
 
 /*#[derive(Serialize, Deserialize, Debug)]
 pub struct Workflow {
@@ -68,20 +106,6 @@ pub struct Workflow {
     pub transitions: Vec<Transition>,
 }
 */
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Project {
-    pub id: ProjectId,
-    pub name: String,
-    //pub costs_info: CostInfo,
-    //pub workflow: Workflow,
-}
-
-/*#[derive(Serialize, Deserialize, Debug)]
-pub struct Context {
-    //    pub id: ContextId,
-    pub name: String,
-}
-*/
 /*#[derive(Serialize, Deserialize, Debug)]
 pub struct Repeat {}
 */
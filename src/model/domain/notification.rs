@@ -0,0 +1,24 @@
+use rusoto_dynamodb::QueryInput;
+use serde_dynamodb;
+use serde_dynamodb::ToQueryInput;
+use chrono;
+
+use super::*;
+
+pub const EMAIL_CHANNEL: &str = "email";
+pub const WEB_PUSH_CHANNEL: &str = "web_push";
+pub const CHANNELS: &[&str] = &[EMAIL_CHANNEL, WEB_PUSH_CHANNEL];
+
+/// Reminder sent to a user for an item.
+#[derive(Serialize, Deserialize, Debug, Clone, ToQueryInput)]
+pub struct Notification {
+    pub uid: super::super::UserId,
+    /// date of the reminder followed by the item id, so that notifications are sorted and each
+    /// reminder is sent once
+    pub id: String,
+    pub item_id: ItemId,
+    pub title: String,
+    pub message: String,
+    pub remind_at: chrono::DateTime<chrono::Utc>,
+    pub due: chrono::DateTime<chrono::Utc>,
+}
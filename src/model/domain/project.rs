@@ -0,0 +1,15 @@
+use rusoto_dynamodb::QueryInput;
+use serde_dynamodb;
+use serde_dynamodb::ToQueryInput;
+
+use super::*;
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToQueryInput)]
+pub struct Project {
+    pub uid: super::super::UserId,
+    pub id: ProjectId,
+    pub name: String,
+    #[serde(default)]
+    pub costs_info: CostInfo,
+    //pub workflow: Workflow,
+}
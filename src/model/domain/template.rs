@@ -0,0 +1,27 @@
+use rusoto_dynamodb::QueryInput;
+use serde_dynamodb;
+use serde_dynamodb::ToQueryInput;
+use chrono;
+
+use super::*;
+
+/// Step of a template, created as a child of the item instantiated from the template.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TemplateItem {
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// Reusable item with ordered steps, like a release checklist.
+#[derive(Serialize, Deserialize, Debug, Clone, ToQueryInput)]
+pub struct Template {
+    pub uid: super::super::UserId,
+    pub id: TemplateId,
+    pub title: String,
+    pub description: String,
+    /// steps, in the order of the children created
+    pub items: Vec<TemplateItem>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
@@ -0,0 +1,20 @@
+use rusoto_dynamodb::QueryInput;
+use serde_dynamodb;
+use serde_dynamodb::ToQueryInput;
+use chrono;
+
+use super::*;
+
+/// Trace of a deleted item, kept so that clients syncing later can remove it too.
+#[derive(Serialize, Deserialize, Debug, Clone, ToQueryInput)]
+pub struct Tombstone {
+    /// the owner of the item, or a former member of its project
+    pub uid: super::super::UserId,
+    pub id: ItemId,
+    /// project of the item, to report deletions to the members of the project
+    #[serde(default)]
+    pub project_id: Option<ProjectId>,
+    pub deleted_at: chrono::DateTime<chrono::Utc>,
+    /// epoch timestamp after which DynamoDB can expire the tombstone
+    pub expires_at: i64,
+}
@@ -0,0 +1,58 @@
+use rusoto_dynamodb::QueryInput;
+use serde_dynamodb;
+use serde_dynamodb::ToQueryInput;
+use chrono;
+
+use super::*;
+
+pub const ITEM_CREATED: &str = "item.created";
+pub const ITEM_UPDATED: &str = "item.updated";
+pub const ITEM_COMPLETED: &str = "item.completed";
+pub const ITEM_DELETED: &str = "item.deleted";
+pub const ITEM_EVENTS: &[&str] = &[ITEM_CREATED, ITEM_UPDATED, ITEM_COMPLETED, ITEM_DELETED];
+/// value of `Delivery::pending` while a delivery waits for its next attempt
+pub const PENDING: &str = "pending";
+
+/// URL called when items of the user change.
+#[derive(Serialize, Deserialize, Debug, Clone, ToQueryInput)]
+pub struct Webhook {
+    pub uid: super::super::UserId,
+    pub id: WebhookId,
+    pub url: String,
+    /// key of the HMAC signature of the deliveries
+    pub secret: String,
+    /// names of the events sent to the webhook
+    pub events: Vec<String>,
+    pub created_by: super::super::Actor,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeliveryAttempt {
+    pub at: chrono::DateTime<chrono::Utc>,
+    /// HTTP status of the response, if one was received
+    pub status: Option<u16>,
+    pub error: Option<String>,
+}
+
+/// Event sent to a webhook, with all attempts made to deliver it.
+#[derive(Serialize, Deserialize, Debug, Clone, ToQueryInput)]
+pub struct Delivery {
+    pub uid: super::super::UserId,
+    /// webhook id followed by the creation date, so that deliveries of a webhook are sorted
+    pub id: String,
+    pub webhook_id: WebhookId,
+    pub event: String,
+    pub payload: String,
+    pub attempts: Vec<DeliveryAttempt>,
+    pub delivered: bool,
+    /// epoch timestamp of the next attempt, `None` once delivered or abandoned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_attempt_at: Option<i64>,
+    /// `PENDING` until delivered or abandoned, only pending deliveries are in the index of the
+    /// deliveries to send
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending: Option<String>,
+    /// epoch timestamp after which a delivered or abandoned delivery is removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+}
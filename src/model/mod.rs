@@ -1,6 +1,6 @@
 macro_rules! typed_id {
     ($name:ident) => (
-        #[derive(Serialize, Deserialize, Debug)]
+        #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
         pub struct $name (pub String);
         impl ToString for $name {
             fn to_string(&self) -> String {